@@ -28,8 +28,51 @@
 //! - [SetLastTransation]: Set last transaction in which the asset was used
 //! - [SetStake]: Set stake for a block creation
 //! - [GetStake]: Get stake for a block  ID
+//! - [GetAssetsInfo]: Get info of several assets
+//! - [ChangeAssetOwners]: Change owners of several assets
+//! - [SetLastTransactions]: Set last transaction of several assets
+//! - [UndoAssetOwners]: Undo ownership changes of several assets
+//! - [CheckSpend]: Check if a transaction can spend an output
+//! - [CheckChange]: Check if a split, merge or burn conserves value
+//! - [ApplyBlocks]: Apply asset changes of several blocks
+//! - [UndoBlocks]: Undo asset changes of several blocks
+//! - [GetSupply]: Get total value of unspent assets
+//! - [CheckStake]: Check if a transaction respects stake locks
+//! - [GetSlashable]: Get assets burned if a peer is slashed
+//! - [GetVotingStake]: Get value of assets staked by every peer
+//! - [MergeLedger]: Add new and newly distributed assets to the ledger
+//! - [SetAsk]: Ask a price for an asset
+//! - [TakeAsk]: Accept an offer for an asset
+//! - [LockStake]: Lock staked assets that aren't bonded
 //! synchronising state
+//!
+//! Assets can be split into several assets, merged with assets of the
+//! same owner and burned, see [TxKind]. Assets that are used up this way
+//! are kept in the ledger but marked as spent: they can't be transacted
+//! and don't count towards stake.
+//!
+//! The auditor can mint assets at runtime. Peers only accept minted
+//! assets that respect their [SupplyPolicy].
+//!
+//! Assets can be bonded as stake. Bonded assets are locked: they can't
+//! be transacted until they are unbonded and the unbonding period is
+//! over. Peers that bond assets only stake their bonded assets, peers
+//! that don't stake all of their assets and those assets are locked for
+//! the unbonding period once they are staked for a block, see
+//! [LockStake]. Validators that sign conflicting blocks lose their
+//! locked assets when [Evidence](crate::block::Evidence) is submitted.
+//!
+//! Ownership is tracked with an owner field on every asset by default.
+//! Ledgers created with [LedgerModel::Utxo] additionally track outputs:
+//! every ownership change spends the asset's unspent output and creates
+//! a new one, and transactions that spend outputs that were already
+//! spent are rejected with [CheckSpend].
+//!
+//! Assets are only sold for tokens when their owner asks for a price
+//! with [SetAsk]. Asks are kept by the owner's ledger and aren't
+//! synchronised with other peers.
 
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::{Display, Formatter, Result};
 
 use actix::prelude::*;
@@ -37,6 +80,8 @@ use derive_builder::Builder;
 use log::debug;
 use serde::{Deserialize, Serialize};
 
+use crate::block::{Block, TxKind};
+use crate::error::{TxError, TxResult};
 use crate::payload::GetStake as PayloadGetStake;
 
 /// /// [Asset]s are objects that can be transacted on the blockchain
@@ -50,6 +95,20 @@ pub struct Asset {
     /// This value should be the same as [Block.get_serial_no()]
     /// transaction 0 = never been used
     last_transaction: usize,
+    /// IDs of outputs created for the asset, oldest first. Only the
+    /// last output is unspent. Empty unless the ledger uses
+    /// [LedgerModel::Utxo]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    outputs: Vec<String>,
+    /// set when the asset was split, merged or burned
+    #[serde(default)]
+    spent: bool,
+    /// set while the asset is bonded as stake
+    #[serde(default)]
+    bonded: bool,
+    /// block from which an unbonded asset can be transacted again
+    #[serde(default)]
+    unlock: Option<usize>,
 }
 
 impl Display for Asset {
@@ -83,6 +142,10 @@ impl Asset {
             owner: None,
             hash,
             last_transaction: 0,
+            outputs: Vec::default(),
+            spent: false,
+            bonded: false,
+            unlock: None,
         }
     }
 
@@ -120,6 +183,117 @@ impl Asset {
     pub fn get_last_transaction(&self) -> usize {
         self.last_transaction
     }
+
+    /// get unspent output of the asset, only set on UTXO ledgers
+    pub fn get_output(&self) -> Option<&String> {
+        self.outputs.last()
+    }
+
+    /// get all outputs created for the asset, oldest first
+    pub fn get_outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    /// checks if the asset was split, merged or burned
+    pub fn is_spent(&self) -> bool {
+        self.spent
+    }
+
+    /// checks if the asset is bonded as stake
+    pub fn is_bonded(&self) -> bool {
+        self.bonded
+    }
+
+    /// get block from which an unbonded asset can be transacted again
+    pub fn get_unlock(&self) -> Option<usize> {
+        self.unlock
+    }
+
+    /// checks if the asset can't be transacted in block `block_id`:
+    /// bonded assets and unbonding assets are locked
+    pub fn is_locked(&self, block_id: usize) -> bool {
+        self.bonded || self.unlock.is_some_and(|unlock| block_id < unlock)
+    }
+}
+
+/// number of blocks an unbonded asset stays locked by default
+pub const DEFAULT_UNBONDING_PERIOD: usize = 10;
+
+/// ID of the `n`th asset created by splitting asset `asset_id`
+pub fn split_id(asset_id: &str, n: usize) -> String {
+    use crate::utils::hasher;
+    hasher(&format!("{}-split-{}", asset_id, n))
+}
+
+/// ID of asset `name` worth `value` minted on top of block `prev`
+pub fn mint_id(prev: &str, name: &str, value: usize) -> String {
+    use crate::utils::hasher;
+    hasher(&format!("mint-{}-{}-{}", prev, name, value))
+}
+
+/// ID of the asset created by merging `asset_ids`
+pub fn merge_id(asset_ids: &[String]) -> String {
+    use crate::utils::hasher;
+    hasher(&format!("merge-{}", asset_ids.join("-")))
+}
+
+/// ID of the output assigning an asset to `owner`, created by spending
+/// output `input`. The first output of an asset spends the asset's hash
+pub fn output_id(input: &str, owner: &str) -> String {
+    use crate::utils::hasher;
+    hasher(&format!("{}{}", input, owner))
+}
+
+/// Limits on assets minted at runtime. Every peer enforces its own
+/// policy, so all peers in a network should use the same policy
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct SupplyPolicy {
+    /// maximum total value of unspent assets
+    pub cap: Option<usize>,
+    /// maximum value that can be minted per block: by block `n`, no
+    /// more than `n * issuance` can have been minted
+    pub issuance: Option<usize>,
+}
+
+impl SupplyPolicy {
+    /// checks if an asset worth `value` can be minted when unspent assets
+    /// are worth `supply`, `minted` was minted so far and the minted asset
+    /// would be recorded in block `block_id`. Values that overflow exceed
+    /// every limit
+    pub fn check(
+        &self,
+        supply: usize,
+        minted: usize,
+        block_id: usize,
+        value: usize,
+    ) -> TxResult<()> {
+        if let Some(cap) = self.cap {
+            match supply.checked_add(value) {
+                Some(supply) if supply <= cap => (),
+                _ => return Err(TxError::SupplyCapExceeded),
+            }
+        }
+        if let Some(issuance) = self.issuance {
+            let limit = issuance.saturating_mul(block_id);
+            match minted.checked_add(value) {
+                Some(minted) if minted <= limit => (),
+                _ => return Err(TxError::IssuanceExceeded),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ownership model of [AssetLedger]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerModel {
+    /// assets have an owner field that transactions overwrite
+    #[default]
+    Account,
+    /// transactions spend the asset's unspent output and create a
+    /// new output for the buyer
+    Utxo,
 }
 
 /// represents the world(full network) state of [Asset]
@@ -134,12 +308,62 @@ impl Asset {
 /// - [SetLastTransation]: Set last transaction in which the asset was used
 /// - [SetStake]: Set stake for a block creation
 /// - [GetStake]: Get stake for a block  ID
+/// - [GetAssetsInfo]: Get info of several assets
+/// - [ChangeAssetOwners]: Change owners of several assets
+/// - [SetLastTransactions]: Set last transaction of several assets
+/// - [UndoAssetOwners]: Undo ownership changes of several assets
+/// - [CheckSpend]: Check if a transaction can spend an output
+/// - [CheckChange]: Check if a split, merge or burn conserves value
+/// - [ApplyBlocks]: Apply asset changes of several blocks
+/// - [UndoBlocks]: Undo asset changes of several blocks
+/// - [GetSupply]: Get total value of unspent assets
+/// - [CheckStake]: Check if a transaction respects stake locks
+/// - [GetSlashable]: Get assets burned if a peer is slashed
+/// - [GetVotingStake]: Get value of assets staked by every peer
+/// - [MergeLedger]: Add new and newly distributed assets to the ledger
+/// - [SetAsk]: Ask a price for an asset
+/// - [TakeAsk]: Accept an offer for an asset
+/// - [LockStake]: Lock staked assets that aren't bonded
 /// synchronising state
+///
+/// Assets are looked up through hash and owner indexes, call
+/// [AssetLedger::reindex] after modifying `assets` directly
 #[derive(Deserialize, Serialize, Default, Clone, Debug)]
 pub struct AssetLedger {
     pub assets: Vec<Asset>,
     pub stake: Vec<Stake>,
     pub peer_id: String,
+    /// ownership model, all peers in a network must use the same model
+    #[serde(default)]
+    pub model: LedgerModel,
+    #[serde(skip)]
+    index: AssetIndex,
+    /// prices asked for assets, by asset hash
+    #[serde(skip)]
+    asks: HashMap<String, u64>,
+}
+
+/// Positions of assets in [AssetLedger], keyed by hash and owner
+#[derive(Clone, Debug, Default)]
+struct AssetIndex {
+    by_hash: HashMap<String, usize>,
+    by_owner: HashMap<String, BTreeSet<usize>>,
+}
+
+impl AssetIndex {
+    fn build(assets: &[Asset]) -> Self {
+        let mut index = AssetIndex::default();
+        for (pos, asset) in assets.iter().enumerate() {
+            index.by_hash.insert(asset.get_hash().into(), pos);
+            if asset.is_spent() {
+                continue;
+            }
+            if let Some(owner) = asset.get_owner() {
+                index.by_owner.entry(owner.clone()).or_default().insert(pos);
+            }
+        }
+        index
+    }
 }
 
 /// represents the stake each peer is willing to send
@@ -159,22 +383,528 @@ impl AssetLedger {
             assets: Vec::default(),
             stake: Vec::default(),
             peer_id: peer_id.into(),
+            model: LedgerModel::default(),
+            index: AssetIndex::default(),
+            asks: HashMap::default(),
         }
     }
 
-    fn get_peer_assets(&self, peer_id: &str) -> Vec<Asset> {
-        let mut payload: Vec<Asset> = Vec::new();
-        self.assets.iter().for_each(|asset| {
-            if asset.get_owner().is_some() && asset.get_owner().as_ref().unwrap() == peer_id {
-                payload.push(asset.clone());
+    /// create ledger with `assets`
+    pub fn from_assets(peer_id: &str, assets: Vec<Asset>) -> Self {
+        let mut ledger = AssetLedger::new(peer_id);
+        ledger.assets = assets;
+        ledger.reindex();
+        ledger
+    }
+
+    /// rebuilds indexes, required after `assets` is modified directly
+    pub fn reindex(&mut self) {
+        self.index = AssetIndex::build(&self.assets);
+    }
+
+    /// get asset by hash
+    pub fn get_asset(&self, asset_id: &str) -> Option<&Asset> {
+        self.index
+            .by_hash
+            .get(asset_id)
+            .map(|pos| &self.assets[*pos])
+    }
+
+    /// changes owner of asset at position `pos`, on UTXO ledgers
+    /// the asset's unspent output is spent
+    fn set_owner_at(&mut self, pos: usize, new_owner: &str) {
+        if self.model == LedgerModel::Utxo {
+            let asset = &mut self.assets[pos];
+            let input = asset
+                .get_output()
+                .cloned()
+                .unwrap_or_else(|| asset.get_hash().into());
+            asset.outputs.push(output_id(&input, new_owner));
+        }
+        self.move_owner_at(pos, new_owner);
+    }
+
+    /// removes asset at position `pos` from the owner index
+    fn unindex_owner(&mut self, pos: usize) {
+        if let Some(owner) = self.assets[pos].get_owner() {
+            if let Some(positions) = self.index.by_owner.get_mut(owner) {
+                positions.remove(&pos);
+                if positions.is_empty() {
+                    self.index.by_owner.remove(owner);
+                }
             }
-        });
+        }
+    }
+
+    /// adds asset at position `pos` to the owner index, spent assets
+    /// aren't indexed
+    fn index_owner(&mut self, pos: usize) {
+        let asset = &self.assets[pos];
+        if asset.is_spent() {
+            return;
+        }
+        if let Some(owner) = asset.get_owner() {
+            self.index
+                .by_owner
+                .entry(owner.clone())
+                .or_default()
+                .insert(pos);
+        }
+    }
+
+    /// updates owner of asset at position `pos` and the owner index
+    fn move_owner_at(&mut self, pos: usize, new_owner: &str) {
+        self.unindex_owner(pos);
+        self.assets[pos].set_owner(new_owner);
+        self.index_owner(pos);
+    }
+
+    /// marks asset `asset_id` as spent or unspent
+    fn set_spent(&mut self, asset_id: &str, spent: bool) {
+        if let Some(pos) = self.index.by_hash.get(asset_id).copied() {
+            self.unindex_owner(pos);
+            self.assets[pos].spent = spent;
+            self.index_owner(pos);
+        }
+    }
+
+    /// bonds or unbonds asset `asset_id`, an unbonded asset stays
+    /// locked until block `unlock`
+    fn set_bond(&mut self, asset_id: &str, bonded: bool, unlock: Option<usize>) {
+        if let Some(pos) = self.index.by_hash.get(asset_id).copied() {
+            self.assets[pos].bonded = bonded;
+            self.assets[pos].unlock = unlock;
+        }
+    }
+
+    /// locks assets `asset_ids` that were staked for a block until block
+    /// `unlock`, so that validators that didn't bond assets can still be
+    /// slashed. Bonded and spent assets are left as they are
+    pub fn lock_stake(&mut self, asset_ids: &[String], unlock: usize) {
+        for asset_id in asset_ids.iter() {
+            if let Some(pos) = self.index.by_hash.get(asset_id).copied() {
+                let asset = &mut self.assets[pos];
+                if !asset.bonded && !asset.spent {
+                    asset.unlock = Some(asset.unlock.unwrap_or(0).max(unlock));
+                }
+            }
+        }
+    }
+
+    /// adds an asset created by a split or merge, assets that are
+    /// already in the ledger are ignored
+    fn push_asset(&mut self, mut asset: Asset) {
+        if self.index.by_hash.contains_key(asset.get_hash()) {
+            return;
+        }
+        if self.model == LedgerModel::Utxo {
+            if let Some(owner) = asset.get_owner().clone() {
+                asset.outputs.push(output_id(asset.get_hash(), &owner));
+            }
+        }
+        let pos = self.assets.len();
+        self.index.by_hash.insert(asset.get_hash().into(), pos);
+        self.assets.push(asset);
+        self.index_owner(pos);
+    }
+
+    /// removes assets created by a split or merge that is undone
+    fn remove_assets(&mut self, asset_ids: &[String]) {
+        self.assets
+            .retain(|asset| !asset_ids.iter().any(|id| id == asset.get_hash()));
+        self.reindex();
+    }
+
+    /// assets created by splitting asset `asset_id` into `values`
+    fn split_assets(&self, asset_id: &str, values: &[usize]) -> Vec<Asset> {
+        let parent = match self.get_asset(asset_id) {
+            Some(parent) => parent,
+            None => return Vec::new(),
+        };
+        values
+            .iter()
+            .enumerate()
+            .map(|(n, value)| Asset {
+                name: format!("{} #{}", parent.get_name(), n + 1),
+                value: *value,
+                hash: split_id(asset_id, n),
+                owner: parent.get_owner().clone(),
+                last_transaction: parent.get_last_transaction(),
+                outputs: Vec::default(),
+                spent: false,
+                bonded: false,
+                unlock: None,
+            })
+            .collect()
+    }
 
-        payload
+    /// asset created by merging `asset_ids`, coinage of the
+    /// merged asset is that of the most recently used asset
+    fn merged_asset(&self, asset_ids: &[String], owner: &str) -> Asset {
+        let assets: Vec<&Asset> = asset_ids
+            .iter()
+            .filter_map(|asset_id| self.get_asset(asset_id))
+            .collect();
+        Asset {
+            name: assets
+                .iter()
+                .map(|asset| asset.get_name())
+                .collect::<Vec<&str>>()
+                .join(" + "),
+            value: assets.iter().map(|asset| asset.get_value()).sum(),
+            hash: merge_id(asset_ids),
+            owner: Some(owner.into()),
+            last_transaction: assets
+                .iter()
+                .map(|asset| asset.get_last_transaction())
+                .max()
+                .unwrap_or_default(),
+            outputs: Vec::default(),
+            spent: false,
+            bonded: false,
+            unlock: None,
+        }
     }
 
+    /// checks if `owner` can split, merge or burn asset `asset_id` as
+    /// described by `kind` and that values are conserved: split values
+    /// must add up to the asset's value, merged value must be the sum of
+    /// the merged assets' values and burned value must be the asset's value
+    pub fn check_change(&self, owner: &str, asset_id: &str, kind: &TxKind) -> TxResult<()> {
+        let owned = |asset_id: &str| -> TxResult<&Asset> {
+            let asset = self.get_asset(asset_id).ok_or(TxError::UnknownAsset)?;
+            if asset.is_spent() || asset.get_owner().as_deref() != Some(owner) {
+                Err(TxError::NotAssetOwner)
+            } else {
+                Ok(asset)
+            }
+        };
+        let asset = owned(asset_id)?;
+
+        let conserved = match kind {
+            TxKind::Split { values } => {
+                if values.len() < 2 || values.contains(&0) {
+                    return Err(TxError::InvalidAssetChange);
+                }
+                let sum = values
+                    .iter()
+                    .try_fold(0usize, |sum, value| sum.checked_add(*value))
+                    .ok_or(TxError::ValueOverflow)?;
+                sum == asset.get_value()
+            }
+            TxKind::Merge { assets, value } => {
+                let mut asset_ids: Vec<&str> = assets.iter().map(|id| id.as_str()).collect();
+                asset_ids.push(asset_id);
+                asset_ids.sort_unstable();
+                asset_ids.dedup();
+                if assets.is_empty() || asset_ids.len() != assets.len() + 1 {
+                    return Err(TxError::InvalidAssetChange);
+                }
+                let mut sum = asset.get_value();
+                for asset_id in assets.iter() {
+                    sum = sum
+                        .checked_add(owned(asset_id)?.get_value())
+                        .ok_or(TxError::ValueOverflow)?;
+                }
+                sum == *value
+            }
+            TxKind::Burn { value } => asset.get_value() == *value,
+            _ => return Err(TxError::InvalidAssetChange),
+        };
+        if conserved {
+            Ok(())
+        } else {
+            Err(TxError::ValueNotConserved)
+        }
+    }
+
+    /// `owner` asks `price` tokens for asset `asset_id`, replacing
+    /// previous asks
+    pub fn set_ask(&mut self, owner: &str, asset_id: &str, price: u64) -> TxResult<()> {
+        let asset = self.get_asset(asset_id).ok_or(TxError::UnknownAsset)?;
+        if asset.is_spent() || asset.get_owner().as_deref() != Some(owner) {
+            return Err(TxError::NotAssetOwner);
+        }
+        self.asks.insert(asset_id.into(), price);
+        Ok(())
+    }
+
+    /// accepts an offer of `price` tokens for asset `asset_id` if it
+    /// covers the asked price. The ask is withdrawn once it's accepted
+    pub fn take_ask(&mut self, asset_id: &str, price: u64) -> TxResult<()> {
+        match self.asks.get(asset_id) {
+            Some(ask) if price >= *ask => {
+                self.asks.remove(asset_id);
+                Ok(())
+            }
+            Some(_) => Err(TxError::PriceTooLow),
+            None => Err(TxError::NotForSale),
+        }
+    }
+
+    /// unspent assets of `peer_id` that are locked in block `block_id`,
+    /// sorted by hash. These are burned when the peer is slashed
+    pub fn slashable(&self, peer_id: &str, block_id: usize) -> Vec<String> {
+        let mut assets: Vec<String> = self
+            .get_peer_assets(peer_id)
+            .iter()
+            .filter(|asset| asset.is_locked(block_id))
+            .map(|asset| asset.get_hash().to_owned())
+            .collect();
+        assets.sort_unstable();
+        assets
+    }
+
+    /// checks if a transaction of kind `kind` respects stake locks in
+    /// block `block_id`: assets that are spent must be unlocked, bonded
+    /// assets must be unlocked and owned by `owner`, unbonded assets must
+    /// be bonded and stay locked for at least `period` blocks and slashed
+    /// assets must be exactly the assets locked by the offending validator
+    pub fn check_stake(
+        &self,
+        owner: &str,
+        asset_id: &str,
+        kind: &TxKind,
+        block_id: usize,
+        period: usize,
+    ) -> TxResult<()> {
+        let unlocked = |asset_id: &str| -> TxResult<()> {
+            match self.get_asset(asset_id) {
+                Some(asset) if asset.is_locked(block_id) => Err(TxError::AssetLocked),
+                _ => Ok(()),
+            }
+        };
+        let owned = |asset_id: &str| -> TxResult<&Asset> {
+            let asset = self.get_asset(asset_id).ok_or(TxError::UnknownAsset)?;
+            if asset.is_spent() || asset.get_owner().as_deref() != Some(owner) {
+                Err(TxError::NotAssetOwner)
+            } else {
+                Ok(asset)
+            }
+        };
+
+        match kind {
+            TxKind::Merge { assets, .. } => {
+                unlocked(asset_id)?;
+                for asset_id in assets.iter() {
+                    unlocked(asset_id)?;
+                }
+                Ok(())
+            }
+            kind if kind.spends_asset() => unlocked(asset_id),
+            TxKind::Bond => {
+                owned(asset_id)?;
+                unlocked(asset_id)
+            }
+            TxKind::Unbond { unlock } => {
+                if !owned(asset_id)?.is_bonded() {
+                    Err(TxError::NotBonded)
+                } else if *unlock < block_id.saturating_add(period) {
+                    Err(TxError::UnbondingTooShort)
+                } else {
+                    Ok(())
+                }
+            }
+            TxKind::Slash { evidence, assets } => {
+                if !evidence.is_valid() {
+                    return Err(TxError::InvalidEvidence);
+                }
+                let slashable = self.slashable(evidence.offender().unwrap(), block_id);
+                if slashable.is_empty() {
+                    Err(TxError::NothingToSlash)
+                } else if &slashable != assets {
+                    Err(TxError::InvalidSlash)
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// value of assets staked by every peer, which weighs its finality
+    /// votes. Like [AssetLedger::default_stake], peers that bonded assets
+    /// only stake their bonded assets
+    pub fn voting_stake(&self) -> HashMap<String, usize> {
+        self.index
+            .by_owner
+            .keys()
+            .map(|owner| {
+                let mut assets = self.get_peer_assets(owner);
+                if assets.iter().any(|asset| asset.is_bonded()) {
+                    assets.retain(|asset| asset.is_bonded());
+                }
+                let stake = assets.iter().map(|asset| asset.get_value()).sum();
+                (owner.clone(), stake)
+            })
+            .collect()
+    }
+
+    /// total value of unspent assets, saturates when minting is
+    /// unlimited
+    pub fn supply(&self) -> usize {
+        self.assets
+            .iter()
+            .filter(|asset| !asset.is_spent())
+            .fold(0, |supply, asset| supply.saturating_add(asset.get_value()))
+    }
+
+    /// applies changes to assets recorded in `block`: ownership changes,
+    /// splits, merges, burns and mints
+    pub fn apply_block(&mut self, block: &Block) {
+        if let TxKind::Slash { assets, .. } = block.get_kind() {
+            for asset_id in assets.iter() {
+                self.set_spent(asset_id, true);
+            }
+            return;
+        }
+        let (asset_id, owner) = match (block.get_asset_id(), block.get_rx()) {
+            (Some(asset_id), Some(owner)) => (asset_id.clone(), owner.clone()),
+            _ => return,
+        };
+        match block.get_kind() {
+            kind if kind.moves_asset() => self.change_owner(&asset_id, &owner),
+            TxKind::Split { values } => {
+                for asset in self.split_assets(&asset_id, values) {
+                    self.push_asset(asset);
+                }
+                self.set_spent(&asset_id, true);
+            }
+            TxKind::Merge { assets, .. } => {
+                let mut asset_ids = vec![asset_id];
+                asset_ids.extend(assets.iter().cloned());
+                self.push_asset(self.merged_asset(&asset_ids, &owner));
+                for asset_id in asset_ids.iter() {
+                    self.set_spent(asset_id, true);
+                }
+            }
+            TxKind::Burn { .. } => self.set_spent(&asset_id, true),
+            TxKind::MintAsset { name, value } => self.push_asset(Asset {
+                name: name.clone(),
+                value: *value,
+                hash: asset_id,
+                owner: Some(owner),
+                last_transaction: 0,
+                outputs: Vec::default(),
+                spent: false,
+                bonded: false,
+                unlock: None,
+            }),
+            TxKind::Bond => self.set_bond(&asset_id, true, None),
+            TxKind::Unbond { unlock } => self.set_bond(&asset_id, false, Some(*unlock)),
+            _ => (),
+        }
+    }
+
+    /// undoes changes to assets recorded in `block`, blocks must be
+    /// undone latest first
+    pub fn undo_block(&mut self, block: &Block) {
+        if let TxKind::Slash { assets, .. } = block.get_kind() {
+            for asset_id in assets.iter() {
+                self.set_spent(asset_id, false);
+            }
+            return;
+        }
+        let (asset_id, owner) = match (block.get_asset_id(), block.get_tx()) {
+            (Some(asset_id), Some(owner)) => (asset_id.clone(), owner.clone()),
+            _ => return,
+        };
+        match block.get_kind() {
+            kind if kind.moves_asset() => self.undo_owner_change(&asset_id, &owner),
+            TxKind::Split { values } => {
+                let created: Vec<String> =
+                    (0..values.len()).map(|n| split_id(&asset_id, n)).collect();
+                self.remove_assets(&created);
+                self.set_spent(&asset_id, false);
+            }
+            TxKind::Merge { assets, .. } => {
+                let mut asset_ids = vec![asset_id];
+                asset_ids.extend(assets.iter().cloned());
+                self.remove_assets(&[merge_id(&asset_ids)]);
+                for asset_id in asset_ids.iter() {
+                    self.set_spent(asset_id, false);
+                }
+            }
+            TxKind::Burn { .. } => self.set_spent(&asset_id, false),
+            TxKind::MintAsset { .. } => self.remove_assets(&[asset_id]),
+            TxKind::Bond => self.set_bond(&asset_id, false, None),
+            TxKind::Unbond { .. } => self.set_bond(&asset_id, true, None),
+            _ => (),
+        }
+    }
+
+    /// changes owner of asset `asset_id`, unknown assets are ignored
+    pub fn change_owner(&mut self, asset_id: &str, new_owner: &str) {
+        if let Some(pos) = self.index.by_hash.get(asset_id).copied() {
+            self.set_owner_at(pos, new_owner);
+        }
+    }
+
+    /// undoes the last ownership change of asset `asset_id`, making
+    /// `prev_owner` the owner again. On UTXO ledgers the last output is
+    /// dropped and the output it spent becomes unspent
+    pub fn undo_owner_change(&mut self, asset_id: &str, prev_owner: &str) {
+        if let Some(pos) = self.index.by_hash.get(asset_id).copied() {
+            if self.model == LedgerModel::Utxo {
+                self.assets[pos].outputs.pop();
+            }
+            self.move_owner_at(pos, prev_owner);
+        }
+    }
+
+    /// checks if `owner` can spend output `input` of asset `asset_id`.
+    /// Always succeeds on account ledgers, ownership is checked with
+    /// [check_ownership](crate::utils::check_ownership) there
+    pub fn check_spend(&self, asset_id: &str, input: Option<&str>, owner: &str) -> TxResult<()> {
+        if self.model == LedgerModel::Account {
+            return Ok(());
+        }
+        let asset = self.get_asset(asset_id).ok_or(TxError::UnknownAsset)?;
+        let input = input.ok_or(TxError::MissingInput)?;
+        if asset.is_spent() {
+            return Err(TxError::OutputSpent);
+        }
+        if asset.get_output().map(|output| output.as_str()) == Some(input) {
+            if asset.get_owner().as_deref() == Some(owner) {
+                Ok(())
+            } else {
+                Err(TxError::NotOutputOwner)
+            }
+        } else if asset.get_outputs().iter().any(|output| output == input) {
+            Err(TxError::OutputSpent)
+        } else {
+            Err(TxError::UnknownOutput)
+        }
+    }
+
+    /// sets last transaction of asset `asset_id`, unknown assets are ignored
+    pub fn set_last_transaction(&mut self, asset_id: &str, tx: usize) {
+        if let Some(pos) = self.index.by_hash.get(asset_id) {
+            self.assets[*pos].set_last_transaction(tx);
+        }
+    }
+
+    fn get_peer_assets(&self, peer_id: &str) -> Vec<Asset> {
+        self.index
+            .by_owner
+            .get(peer_id)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .map(|pos| self.assets[*pos].clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// bonded assets of this peer, all of its assets if it didn't bond any.
+    /// Assets that aren't bonded are locked once they are staked, see
+    /// [AssetLedger::lock_stake]
     fn default_stake(&self) -> Vec<String> {
-        let assets_for_me = self.get_peer_assets(&self.peer_id);
+        let mut assets_for_me = self.get_peer_assets(&self.peer_id);
+        if assets_for_me.iter().any(|asset| asset.is_bonded()) {
+            assets_for_me.retain(|asset| asset.is_bonded());
+        }
 
         let mut stake_id = Vec::new();
         assets_for_me.iter().for_each(|asset| {
@@ -185,33 +915,13 @@ impl AssetLedger {
 
     // get the current number of peers assigned
     fn peers_currently_assigned(&self) -> usize {
-        let mut peers_currently_assigned: Vec<&str> = Vec::new();
-        self.assets.iter().for_each(|asset| {
-            if let Some(owner) = asset.get_owner() {
-                let peer_check = peers_currently_assigned.iter().find(|peer_id| {
-                    if **peer_id == owner {
-                        true
-                    } else {
-                        false
-                    }
-                });
-
-                if peer_check.is_none() {
-                    peers_currently_assigned.push(owner);
-                }
-            };
-        });
-        peers_currently_assigned.len()
+        self.index.by_owner.len()
     }
 
     /// generates a bunch of fake assets
     pub fn generate(peer_id: &str) -> AssetLedger {
         debug!("Gerating assets");
-        let mut ledger = AssetLedger {
-            assets: Vec::new(),
-            stake: Vec::new(),
-            peer_id: peer_id.into(),
-        };
+        let mut ledger = AssetLedger::new(peer_id);
 
         ledger.assets.push(Asset::new("les Escaldes", 100));
         ledger.assets.push(Asset::new("Andorra la Vella", 100));
@@ -228,6 +938,7 @@ impl AssetLedger {
         ledger.assets.push(Asset::new("Ajman", 100));
         ledger.assets.push(Asset::new("Adh Dhayd", 100));
         ledger.assets.push(Asset::new("Abu Dhabi", 100));
+        ledger.reindex();
         ledger
     }
 
@@ -244,7 +955,6 @@ impl AssetLedger {
         // the peer has (https://en.wikipedia.org/wiki/Proof_of_stake#Coin_age-based_selection)
         //let mut counter: Vec<(usize, (usize, &str))> = Vec::new();
 
-        let mut counter: Vec<(usize, &str)> = Vec::new();
         // problem statement:
         // - all assets shuold be accounted for
         // - coinage must be compared
@@ -263,40 +973,19 @@ impl AssetLedger {
             .unwrap()
             .get_last_transaction();
 
-        // calculate coinage
-        self.assets.iter().for_each(|asset| {
-            // it's possible that the minted assets are not yet assigned
-            if let Some(owner) = asset.get_owner() {
-                // unwrap is okay jere because get_owner() and get_coinage()
-                // are in sync when retruning None and it's checked for
-                let asset_last_transaction = asset.get_last_transaction();
-                let coinage = latest_transaction - asset_last_transaction;
-
-                // flag to check if an asset has been counted
-                let mut counted_flag = false;
-
-                // checking if owner already exists in counter
-                // datastructure
-                for (coinage_sum, peer_id) in counter.iter_mut() {
-                    if peer_id == owner {
-                        *coinage_sum += coinage;
-                        counted_flag = true;
-                        break;
-                    }
-                }
-                if !counted_flag {
-                    counter.push((coinage, owner));
-                }
-            }
+        // calculate coinage of every owner, minted assets that are not
+        // yet assigned aren't in the owner index
+        let counter = self.index.by_owner.iter().map(|(owner, positions)| {
+            let coinage_sum: usize = positions
+                .iter()
+                .map(|pos| latest_transaction - self.assets[*pos].get_last_transaction())
+                .sum();
+            (coinage_sum, owner.as_str())
         });
 
         // return the minimum value(when coimage_sum minimum, coinage maximum)
         // present in the counter datastructure.
-        if let Some((_, peer_id)) = counter.iter().max() {
-            Some(peer_id.to_string())
-        } else {
-            None
-        }
+        counter.max().map(|(_, peer_id)| peer_id.to_string())
     }
 }
 
@@ -345,6 +1034,32 @@ pub struct DumpLedger;
 #[rtype(result = "()")]
 pub struct ReplaceLedger(pub Vec<Asset>);
 
+/// Adds assets that aren't in the ledger yet and takes over assets
+/// that were distributed since([InitNetwork]). Assets that already
+/// have an owner are kept as they are
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct MergeLedger(pub Vec<Asset>);
+
+/// `owner` asks `price` tokens for asset `asset_id`, see
+/// [AssetLedger::set_ask]
+#[derive(Message)]
+#[rtype(result = "TxResult<()>")]
+pub struct SetAsk {
+    pub owner: String,
+    pub asset_id: String,
+    pub price: u64,
+}
+
+/// Accepts an offer of `price` tokens for asset `asset_id`, see
+/// [AssetLedger::take_ask]
+#[derive(Message)]
+#[rtype(result = "TxResult<()>")]
+pub struct TakeAsk {
+    pub asset_id: String,
+    pub price: u64,
+}
+
 /// Get asset info of `GetAssetInfo.0`
 #[derive(Message)]
 #[rtype(result = "Option<String>")]
@@ -377,6 +1092,102 @@ pub struct SetLastTransation {
     pub asset_id: String,
 }
 
+/// Get info of assets `GetAssetsInfo.0`, assets that aren't in the
+/// ledger are skipped
+#[derive(Message)]
+#[rtype(result = "Vec<Asset>")]
+pub struct GetAssetsInfo(pub Vec<String>);
+
+/// Changes owners of several assets, applied in order
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChangeAssetOwners(pub Vec<ChangeAssetOwner>);
+
+/// Sets last transaction of several assets
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetLastTransactions(pub Vec<SetLastTransation>);
+
+/// Locks staked assets until block `unlock`, see [AssetLedger::lock_stake]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct LockStake {
+    pub assets: Vec<String>,
+    pub unlock: usize,
+}
+
+/// Undoes ownership changes of several assets, applied in order.
+/// `new_owner` is the owner before the change, see
+/// [AssetLedger::undo_owner_change]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UndoAssetOwners(pub Vec<ChangeAssetOwner>);
+
+/// Checks if `owner` can split, merge or burn asset `asset_id` as
+/// described by `kind`, see [AssetLedger::check_change]
+#[derive(Message, Builder)]
+#[rtype(result = "TxResult<()>")]
+pub struct CheckChange {
+    pub owner: String,
+    pub asset_id: String,
+    pub kind: TxKind,
+}
+
+/// Checks if a transaction respects stake locks in block `block_id`,
+/// see [AssetLedger::check_stake]
+#[derive(Message, Builder)]
+#[rtype(result = "TxResult<()>")]
+pub struct CheckStake {
+    pub owner: String,
+    pub asset_id: String,
+    pub kind: TxKind,
+    pub block_id: usize,
+    /// minimum number of blocks an unbonded asset stays locked
+    pub period: usize,
+}
+
+/// Get assets of a peer that are burned if it's slashed in block
+/// `block_id`, see [AssetLedger::slashable]
+#[derive(Message)]
+#[rtype(result = "Vec<String>")]
+pub struct GetSlashable {
+    pub peer_id: String,
+    pub block_id: usize,
+}
+
+/// Get value of assets staked by every peer, see
+/// [AssetLedger::voting_stake]
+#[derive(Message)]
+#[rtype(result = "HashMap<String, usize>")]
+pub struct GetVotingStake;
+
+/// Applies asset changes recorded in several blocks, in order,
+/// see [AssetLedger::apply_block]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ApplyBlocks(pub Vec<Block>);
+
+/// Undoes asset changes recorded in several blocks, blocks must be
+/// latest first, see [AssetLedger::undo_block]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UndoBlocks(pub Vec<Block>);
+
+/// Get total value of unspent assets, see [AssetLedger::supply]
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct GetSupply;
+
+/// Checks if `owner` can spend output `input` of asset `asset_id`,
+/// see [AssetLedger::check_spend]
+#[derive(Message, Builder)]
+#[rtype(result = "TxResult<()>")]
+pub struct CheckSpend {
+    pub asset_id: String,
+    pub input: Option<String>,
+    pub owner: String,
+}
+
 impl From<PayloadGetStake> for GetStake {
     fn from(msg: PayloadGetStake) -> Self {
         GetStake(msg.block_id)
@@ -400,18 +1211,14 @@ impl Handler<InitNetwork> for AssetLedger {
         let current_transaction = self.peers_currently_assigned() + 1;
 
         for i in 0..length {
-            // unwrap is okay here as I'm only `get_mut()`ing over the
-            // the length of `self.assets`
-            let asset = self.assets.get_mut(i).unwrap();
-
             // number of assets that need to be modified are
             // controlled by this:
             if assets_per_peer > 0 {
                 // assets that already have an owner shouldn't be effected
-                if asset.get_owner().is_none() {
-                    asset.set_owner(&msg.peer_id);
+                if self.assets[i].get_owner().is_none() {
+                    self.set_owner_at(i, &msg.peer_id);
                     // initializing coinage to 0(ready for use)
-                    asset.set_last_transaction(current_transaction);
+                    self.assets[i].set_last_transaction(current_transaction);
                     assets_per_peer -= 1;
                 }
             }
@@ -432,17 +1239,7 @@ impl Handler<GetAssetInfo> for AssetLedger {
     type Result = MessageResult<GetAssetInfo>;
 
     fn handle(&mut self, msg: GetAssetInfo, _ctx: &mut Self::Context) -> Self::Result {
-        let mut iter = self.assets.iter();
-        loop {
-            if let Some(val) = iter.next() {
-                if val.get_hash() == msg.0 {
-                    return MessageResult(Some(val.clone()));
-                }
-            } else {
-                break;
-            }
-        }
-        MessageResult(None)
+        MessageResult(self.get_asset(&msg.0).cloned())
     }
 }
 
@@ -450,122 +1247,263 @@ impl Handler<ChangeAssetOwner> for AssetLedger {
     type Result = ();
 
     fn handle(&mut self, msg: ChangeAssetOwner, _ctx: &mut Self::Context) -> Self::Result {
-        let mut target: Option<usize> = None;
-        for (index, asset) in self.assets.iter().enumerate() {
-            if asset.get_hash() == msg.asset_id {
-                target = Some(index);
-                break;
+        self.change_owner(&msg.asset_id, &msg.new_owner);
+    }
+}
+
+impl Handler<ReplaceLedger> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReplaceLedger, _ctx: &mut Self::Context) -> Self::Result {
+        self.assets = msg.0;
+        self.reindex();
+        debug!("Replaced AssetLedger for peer: {}", &self.peer_id);
+    }
+}
+
+impl Handler<MergeLedger> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: MergeLedger, _ctx: &mut Self::Context) -> Self::Result {
+        for asset in msg.0.into_iter() {
+            // unowned assets aren't in the owner index, so the index is
+            // updated in place
+            match self.index.by_hash.get(asset.get_hash()).copied() {
+                None => {
+                    let pos = self.assets.len();
+                    self.index.by_hash.insert(asset.get_hash().into(), pos);
+                    self.assets.push(asset);
+                    self.index_owner(pos);
+                }
+                Some(pos) if self.assets[pos].get_owner().is_none() => {
+                    self.assets[pos] = asset;
+                    self.index_owner(pos);
+                }
+                _ => (),
             }
         }
+        debug!("Merged AssetLedger for peer: {}", &self.peer_id);
+    }
+}
 
-        if let Some(index) = target {
-            self.assets
-                .get_mut(index)
-                .unwrap()
-                .set_owner(&msg.new_owner);
-        };
+impl Handler<SetAsk> for AssetLedger {
+    type Result = MessageResult<SetAsk>;
+
+    fn handle(&mut self, msg: SetAsk, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.set_ask(&msg.owner, &msg.asset_id, msg.price))
+    }
+}
+
+impl Handler<TakeAsk> for AssetLedger {
+    type Result = MessageResult<TakeAsk>;
+
+    fn handle(&mut self, msg: TakeAsk, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.take_ask(&msg.asset_id, msg.price))
+    }
+}
+
+impl Handler<ChooseValidator> for AssetLedger {
+    type Result = MessageResult<ChooseValidator>;
+
+    fn handle(&mut self, _msg: ChooseValidator, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.choose_validator())
+    }
+}
+
+impl Handler<GetPeerAssets> for AssetLedger {
+    type Result = MessageResult<GetPeerAssets>;
+
+    fn handle(&mut self, msg: GetPeerAssets, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_peer_assets(&msg.0))
+    }
+}
+
+impl Handler<SetStake> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetStake, _ctx: &mut Self::Context) -> Self::Result {
+        // checks if the asset hashes received are
+        // indeed owned by the peer ID received
+        // and filters assets accordingly
+        // Ideally, when a match isn't found
+        // an error must be returned
+        let peer_id = msg.peer_id;
+        let correct_stake: Vec<String> = msg
+            .stake
+            .into_iter()
+            .filter(|stake| {
+                self.get_asset(stake)
+                    .filter(|asset| !asset.is_spent())
+                    .and_then(|asset| asset.get_owner().as_ref())
+                    .map(|owner| owner == &peer_id)
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        let stake = StakeBuilder::default()
+            .block_id(msg.block_id)
+            .stake(correct_stake)
+            .build()
+            .unwrap();
+
+        self.stake.push(stake);
+    }
+}
+
+impl Handler<GetStake> for AssetLedger {
+    type Result = MessageResult<GetStake>;
+
+    fn handle(&mut self, msg: GetStake, _ctx: &mut Self::Context) -> Self::Result {
+        let stake = self
+            .stake
+            .iter()
+            .find(|stake| if stake.block_id == msg.0 { true } else { false });
+        if stake.is_some() {
+            MessageResult(stake.unwrap().to_owned())
+        } else {
+            debug!("GetStake is empty, generating default stake");
+            let stake = StakeBuilder::default()
+                .block_id(msg.0)
+                .stake(self.default_stake())
+                .build()
+                .unwrap();
+
+            self.stake.push(stake.clone());
+            MessageResult(stake)
+        }
+    }
+}
+
+impl Handler<SetLastTransation> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetLastTransation, _ctx: &mut Self::Context) -> Self::Result {
+        self.set_last_transaction(&msg.asset_id, msg.tx);
+    }
+}
+
+impl Handler<GetAssetsInfo> for AssetLedger {
+    type Result = MessageResult<GetAssetsInfo>;
+
+    fn handle(&mut self, msg: GetAssetsInfo, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            msg.0
+                .iter()
+                .filter_map(|asset_id| self.get_asset(asset_id).cloned())
+                .collect(),
+        )
+    }
+}
+
+impl Handler<ChangeAssetOwners> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChangeAssetOwners, _ctx: &mut Self::Context) -> Self::Result {
+        for change in msg.0.iter() {
+            self.change_owner(&change.asset_id, &change.new_owner);
+        }
+    }
+}
+
+impl Handler<SetLastTransactions> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetLastTransactions, _ctx: &mut Self::Context) -> Self::Result {
+        for change in msg.0.iter() {
+            self.set_last_transaction(&change.asset_id, change.tx);
+        }
+    }
+}
+
+impl Handler<LockStake> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: LockStake, _ctx: &mut Self::Context) -> Self::Result {
+        self.lock_stake(&msg.assets, msg.unlock);
+    }
+}
+
+impl Handler<UndoAssetOwners> for AssetLedger {
+    type Result = ();
+
+    fn handle(&mut self, msg: UndoAssetOwners, _ctx: &mut Self::Context) -> Self::Result {
+        for change in msg.0.iter() {
+            self.undo_owner_change(&change.asset_id, &change.new_owner);
+        }
+    }
+}
+
+impl Handler<CheckChange> for AssetLedger {
+    type Result = MessageResult<CheckChange>;
+
+    fn handle(&mut self, msg: CheckChange, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.check_change(&msg.owner, &msg.asset_id, &msg.kind))
     }
 }
 
-impl Handler<ReplaceLedger> for AssetLedger {
-    type Result = ();
-
-    fn handle(&mut self, msg: ReplaceLedger, _ctx: &mut Self::Context) -> Self::Result {
-        self.assets = msg.0;
-        debug!("Replaced AssetLedger for peer: {}", &self.peer_id);
+impl Handler<CheckStake> for AssetLedger {
+    type Result = MessageResult<CheckStake>;
+
+    fn handle(&mut self, msg: CheckStake, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.check_stake(
+            &msg.owner,
+            &msg.asset_id,
+            &msg.kind,
+            msg.block_id,
+            msg.period,
+        ))
     }
 }
 
-impl Handler<ChooseValidator> for AssetLedger {
-    type Result = MessageResult<ChooseValidator>;
+impl Handler<GetSlashable> for AssetLedger {
+    type Result = MessageResult<GetSlashable>;
 
-    fn handle(&mut self, _msg: ChooseValidator, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(self.choose_validator())
+    fn handle(&mut self, msg: GetSlashable, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.slashable(&msg.peer_id, msg.block_id))
     }
 }
 
-impl Handler<GetPeerAssets> for AssetLedger {
-    type Result = MessageResult<GetPeerAssets>;
+impl Handler<GetVotingStake> for AssetLedger {
+    type Result = MessageResult<GetVotingStake>;
 
-    fn handle(&mut self, msg: GetPeerAssets, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(self.get_peer_assets(&msg.0))
+    fn handle(&mut self, _msg: GetVotingStake, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.voting_stake())
     }
 }
 
-impl Handler<SetStake> for AssetLedger {
+impl Handler<ApplyBlocks> for AssetLedger {
     type Result = ();
 
-    fn handle(&mut self, msg: SetStake, _ctx: &mut Self::Context) -> Self::Result {
-        let assets = self.get_peer_assets(&msg.peer_id);
-
-        let mut correct_stake: Vec<String> = Vec::new();
-
-        // checks if the asset hashes received are
-        // indeed owned by the peer ID received
-        // and filters assets accordingly
-        // Ideally, when a match isn't found
-        // an error must be returned
-        for stake in msg.stake.iter() {
-            let hash = assets.iter().find(|asset| {
-                if asset.get_hash() == stake {
-                    true
-                } else {
-                    false
-                }
-            });
-            if hash.is_some() {
-                correct_stake.push(hash.unwrap().get_hash().to_owned())
-            }
+    fn handle(&mut self, msg: ApplyBlocks, _ctx: &mut Self::Context) -> Self::Result {
+        for block in msg.0.iter() {
+            self.apply_block(block);
         }
+    }
+}
 
-        let stake = StakeBuilder::default()
-            .block_id(msg.block_id)
-            .stake(correct_stake)
-            .build()
-            .unwrap();
+impl Handler<UndoBlocks> for AssetLedger {
+    type Result = ();
 
-        self.stake.push(stake);
+    fn handle(&mut self, msg: UndoBlocks, _ctx: &mut Self::Context) -> Self::Result {
+        for block in msg.0.iter() {
+            self.undo_block(block);
+        }
     }
 }
 
-impl Handler<GetStake> for AssetLedger {
-    type Result = MessageResult<GetStake>;
-
-    fn handle(&mut self, msg: GetStake, _ctx: &mut Self::Context) -> Self::Result {
-        let stake = self
-            .stake
-            .iter()
-            .find(|stake| if stake.block_id == msg.0 { true } else { false });
-        if stake.is_some() {
-            MessageResult(stake.unwrap().to_owned())
-        } else {
-            debug!("GetStake is empty, generating default stake");
-            let stake = StakeBuilder::default()
-                .block_id(msg.0)
-                .stake(self.default_stake())
-                .build()
-                .unwrap();
+impl Handler<GetSupply> for AssetLedger {
+    type Result = MessageResult<GetSupply>;
 
-            self.stake.push(stake.clone());
-            MessageResult(stake)
-        }
+    fn handle(&mut self, _msg: GetSupply, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.supply())
     }
 }
 
-impl Handler<SetLastTransation> for AssetLedger {
-    type Result = ();
+impl Handler<CheckSpend> for AssetLedger {
+    type Result = MessageResult<CheckSpend>;
 
-    fn handle(&mut self, msg: SetLastTransation, _ctx: &mut Self::Context) -> Self::Result {
-        let mut iter = self.assets.iter_mut();
-        loop {
-            if let Some(val) = iter.next() {
-                if val.get_hash() == msg.asset_id {
-                    val.set_last_transaction(msg.tx);
-                }
-            } else {
-                break;
-            }
-        }
+    fn handle(&mut self, msg: CheckSpend, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.check_spend(&msg.asset_id, msg.input.as_deref(), &msg.owner))
     }
 }
 
@@ -683,6 +1621,54 @@ mod tests {
             .iter()
             .zip(new_dump.iter())
             .for_each(|(a, b)| assert_eq!(a, b, "AssetLedger replace check"));
+
+        let mut distributed = asset_ledger.assets.get(3).unwrap().clone();
+        distributed.set_owner("other");
+        let mut owned = asset_ledger.assets.get(2).unwrap().clone();
+        owned.set_owner("other");
+        let other = vec![Asset::new("Fujairah", 100), distributed, owned];
+        fork_asset_ledger_addr
+            .send(MergeLedger(other.clone()))
+            .await
+            .unwrap();
+        let merged = fork_asset_ledger_addr.send(DumpLedger).await.unwrap();
+        assert_eq!(merged.len(), new_dump.len() + 1, "new assets are added");
+        assert_eq!(
+            fork_asset_ledger_addr
+                .send(GetAssetInfo(hash.into()))
+                .await
+                .unwrap()
+                .unwrap()
+                .get_owner(),
+            &Some(peer_id.into()),
+            "owned assets are kept"
+        );
+        assert_eq!(
+            fork_asset_ledger_addr
+                .send(GetAssetInfo(
+                    asset_ledger.assets.get(3).unwrap().get_hash().into()
+                ))
+                .await
+                .unwrap()
+                .unwrap()
+                .get_owner(),
+            &Some("other".into()),
+            "distributed assets are taken over"
+        );
+        let others = fork_asset_ledger_addr
+            .send(GetPeerAssets("other".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            others,
+            vec![other[1].clone()],
+            "owner index is updated on merge"
+        );
+        assert!(fork_asset_ledger_addr
+            .send(GetAssetInfo(other[0].get_hash().into()))
+            .await
+            .unwrap()
+            .is_some());
     }
 
     #[actix_rt::test]
@@ -850,6 +1836,7 @@ mod tests {
                 }
             }
         }
+        assets.reindex();
     }
 
     #[test]
@@ -869,6 +1856,29 @@ mod tests {
         assert_eq!(stake, assets.default_stake())
     }
 
+    #[test]
+    fn lock_stake_works() {
+        let mut ledger = AssetLedger::generate("me");
+        assign_assets(&mut ledger, "me", 3);
+        let stake = ledger.default_stake();
+        assert!(ledger.slashable("me", 5).is_empty(), "nothing locked yet");
+
+        ledger.lock_stake(&stake, 15);
+        assert_eq!(ledger.slashable("me", 5).len(), stake.len());
+        assert_eq!(
+            ledger.check_stake("me", &stake[0], &TxKind::Asset, 5, 10),
+            Err(TxError::AssetLocked),
+            "staked assets can't be sold"
+        );
+        ledger.lock_stake(&stake, 10);
+        assert_eq!(
+            ledger.get_asset(&stake[0]).unwrap().get_unlock(),
+            Some(15),
+            "locks aren't shortened"
+        );
+        assert!(ledger.slashable("me", 15).is_empty(), "locks expire");
+    }
+
     #[actix_rt::test]
     async fn set_last_tx_wors() {
         let peer_id = "Me";
@@ -901,4 +1911,528 @@ mod tests {
             "AssetLedger GetAssetInfo test"
         );
     }
+
+    #[test]
+    fn asset_index_works() {
+        let peers: Vec<String> = (0..40).map(|i| format!("peer-{}", i)).collect();
+        let assets = (0..20_000)
+            .map(|i| Asset::new(&format!("asset-{}", i), 100))
+            .collect();
+        let mut ledger = AssetLedger::from_assets("me", assets);
+        assert_eq!(ledger.peers_currently_assigned(), 0);
+
+        let hashes: Vec<String> = ledger
+            .assets
+            .iter()
+            .map(|asset| asset.get_hash().to_owned())
+            .collect();
+        for (i, hash) in hashes.iter().enumerate() {
+            ledger.change_owner(hash, &peers[i % peers.len()]);
+        }
+        assert_eq!(ledger.peers_currently_assigned(), 40);
+        assert_eq!(ledger.get_peer_assets("peer-3").len(), 500);
+
+        // moving every asset of a peer drops it from the owner index
+        for asset in ledger.get_peer_assets("peer-3").iter() {
+            ledger.change_owner(asset.get_hash(), "peer-4");
+        }
+        assert_eq!(ledger.peers_currently_assigned(), 39);
+        assert_eq!(ledger.get_peer_assets("peer-4").len(), 1000);
+        assert!(ledger.get_peer_assets("peer-3").is_empty());
+
+        ledger.set_last_transaction(&hashes[7], 9);
+        assert_eq!(
+            ledger.get_asset(&hashes[7]).unwrap().get_last_transaction(),
+            9
+        );
+        assert!(ledger.get_asset("nonexistent").is_none());
+        ledger.change_owner("nonexistent", "peer-0");
+        assert_eq!(ledger.assets.len(), 20_000, "unknown assets ignored");
+
+        // peer-4 holds the most coinage
+        assert_eq!(ledger.choose_validator(), Some("peer-4".into()));
+
+        let mut reindexed = ledger.clone();
+        reindexed.reindex();
+        assert_eq!(
+            reindexed.get_peer_assets("peer-4"),
+            ledger.get_peer_assets("peer-4"),
+            "reindex works"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn batch_messages_work() {
+        let asset_ledger = AssetLedger::generate("me");
+        let hashes: Vec<String> = asset_ledger.assets[..3]
+            .iter()
+            .map(|asset| asset.get_hash().to_owned())
+            .collect();
+        let asset_addr = asset_ledger.start();
+
+        let changes = hashes
+            .iter()
+            .map(|hash| {
+                ChangeAssetOwnerBuilder::default()
+                    .asset_id(hash.into())
+                    .new_owner("you".into())
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        asset_addr.send(ChangeAssetOwners(changes)).await.unwrap();
+        let changes = hashes
+            .iter()
+            .map(|hash| {
+                SetLastTransationBuilder::default()
+                    .tx(7)
+                    .asset_id(hash.into())
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        asset_addr.send(SetLastTransactions(changes)).await.unwrap();
+
+        let mut query = hashes.clone();
+        query.push("nonexistent".into());
+        let assets = asset_addr.send(GetAssetsInfo(query)).await.unwrap();
+        assert_eq!(assets.len(), 3, "unknown assets skipped");
+        for (asset, hash) in assets.iter().zip(hashes.iter()) {
+            assert_eq!(asset.get_hash(), hash);
+            assert_eq!(asset.get_owner().as_deref(), Some("you"));
+            assert_eq!(asset.get_last_transaction(), 7);
+        }
+        assert_eq!(
+            asset_addr.send(GetPeerAssets("you".into())).await.unwrap(),
+            assets,
+            "owner index updated"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn utxo_works() {
+        let mut ledger = AssetLedger::generate("me");
+        ledger.model = LedgerModel::Utxo;
+        let hash = ledger.assets[0].get_hash().to_owned();
+        let asset_addr = ledger.start();
+
+        let init_msg = InitNetworkBuilder::default()
+            .network_size(1)
+            .peer_id("me".into())
+            .build()
+            .unwrap();
+        asset_addr.send(init_msg).await.unwrap();
+        let asset = asset_addr
+            .send(GetAssetInfo(hash.clone()))
+            .await
+            .unwrap()
+            .unwrap();
+        let first = asset.get_output().unwrap().to_owned();
+        assert_eq!(first, output_id(&hash, "me"), "initial output created");
+
+        let check = |input: &str, owner: &str| CheckSpend {
+            asset_id: hash.clone(),
+            input: Some(input.into()),
+            owner: owner.into(),
+        };
+        assert_eq!(asset_addr.send(check(&first, "me")).await.unwrap(), Ok(()));
+        assert_eq!(
+            asset_addr.send(check(&first, "you")).await.unwrap(),
+            Err(TxError::NotOutputOwner)
+        );
+
+        // me -> you -> me, the first output stays spent
+        for owner in ["you", "me"].iter() {
+            let msg = ChangeAssetOwnerBuilder::default()
+                .asset_id(hash.clone())
+                .new_owner((*owner).into())
+                .build()
+                .unwrap();
+            asset_addr.send(msg).await.unwrap();
+        }
+        let asset = asset_addr
+            .send(GetAssetInfo(hash.clone()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(asset.get_outputs().len(), 3);
+        assert_eq!(asset.get_owner().as_deref(), Some("me"));
+        assert_eq!(
+            asset_addr.send(check(&first, "me")).await.unwrap(),
+            Err(TxError::OutputSpent),
+            "double-spend rejected"
+        );
+        assert_eq!(
+            asset_addr.send(check("nonexistent", "me")).await.unwrap(),
+            Err(TxError::UnknownOutput)
+        );
+        let missing = CheckSpend {
+            asset_id: hash.clone(),
+            input: None,
+            owner: "me".into(),
+        };
+        assert_eq!(
+            asset_addr.send(missing).await.unwrap(),
+            Err(TxError::MissingInput)
+        );
+
+        let undo = ChangeAssetOwnerBuilder::default()
+            .asset_id(hash.clone())
+            .new_owner("you".into())
+            .build()
+            .unwrap();
+        asset_addr.send(UndoAssetOwners(vec![undo])).await.unwrap();
+        let asset = asset_addr
+            .send(GetAssetInfo(hash.clone()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(asset.get_owner().as_deref(), Some("you"));
+        assert_eq!(asset.get_output(), asset.get_outputs().get(1), "undo works");
+        assert_eq!(
+            asset_addr
+                .send(check(asset.get_output().unwrap(), "you"))
+                .await
+                .unwrap(),
+            Ok(())
+        );
+
+        // account ledgers don't track outputs
+        let account = AssetLedger::generate("me");
+        assert!(account.assets[0].get_outputs().is_empty());
+        assert_eq!(account.check_spend(&hash, None, "you"), Ok(()));
+    }
+
+    #[test]
+    fn split_merge_burn_works() {
+        use crate::block::BlockBuilder;
+
+        let mut ledger = AssetLedger::generate("me");
+        let hashes: Vec<String> = ledger.assets[..3]
+            .iter()
+            .map(|asset| asset.get_hash().to_owned())
+            .collect();
+        for hash in hashes.iter() {
+            ledger.change_owner(hash, "me");
+        }
+        let block = |asset_id: &str, kind: TxKind| {
+            BlockBuilder::default()
+                .set_tx("me")
+                .set_rx("me")
+                .set_validator("validator")
+                .set_asset_id(asset_id)
+                .set_kind(kind)
+                .set_prev(&Block::genesis())
+                .build()
+        };
+
+        let split = TxKind::Split {
+            values: vec![30, 70],
+        };
+        assert_eq!(ledger.check_change("me", &hashes[0], &split), Ok(()));
+        assert_eq!(
+            ledger.check_change("you", &hashes[0], &split),
+            Err(TxError::NotAssetOwner)
+        );
+        let inflated = TxKind::Split {
+            values: vec![30, 80],
+        };
+        assert_eq!(
+            ledger.check_change("me", &hashes[0], &inflated),
+            Err(TxError::ValueNotConserved),
+            "split can't create value"
+        );
+        let overflow = TxKind::Split {
+            values: vec![usize::MAX, 101],
+        };
+        assert_eq!(
+            ledger.check_change("me", &hashes[0], &overflow),
+            Err(TxError::ValueOverflow),
+            "split values can't wrap around"
+        );
+        let single = TxKind::Split { values: vec![100] };
+        assert_eq!(
+            ledger.check_change("me", &hashes[0], &single),
+            Err(TxError::InvalidAssetChange)
+        );
+
+        let split = block(&hashes[0], split);
+        ledger.apply_block(&split);
+        assert!(ledger.get_asset(&hashes[0]).unwrap().is_spent());
+        let child = ledger.get_asset(&split_id(&hashes[0], 1)).unwrap();
+        assert_eq!(child.get_value(), 70);
+        assert_eq!(child.get_owner().as_deref(), Some("me"));
+        assert_eq!(
+            ledger.get_peer_assets("me").len(),
+            4,
+            "spent assets unindexed"
+        );
+        assert_eq!(
+            ledger.check_change("me", &hashes[0], &TxKind::Burn { value: 100 }),
+            Err(TxError::NotAssetOwner),
+            "spent assets can't be reused"
+        );
+
+        let merged = vec![hashes[2].clone(), split_id(&hashes[0], 0)];
+        let merge = TxKind::Merge {
+            assets: merged.clone(),
+            value: 230,
+        };
+        assert_eq!(ledger.check_change("me", &hashes[1], &merge), Ok(()));
+        let duplicate = TxKind::Merge {
+            assets: vec![hashes[1].clone()],
+            value: 200,
+        };
+        assert_eq!(
+            ledger.check_change("me", &hashes[1], &duplicate),
+            Err(TxError::InvalidAssetChange)
+        );
+        let merge = block(&hashes[1], merge);
+        ledger.apply_block(&merge);
+        let mut asset_ids = vec![hashes[1].clone()];
+        asset_ids.extend(merged);
+        assert_eq!(
+            ledger.get_asset(&merge_id(&asset_ids)).unwrap().get_value(),
+            230
+        );
+        assert_eq!(ledger.get_peer_assets("me").len(), 2);
+
+        let burn = block(&split_id(&hashes[0], 1), TxKind::Burn { value: 70 });
+        ledger.apply_block(&burn);
+        assert_eq!(ledger.get_peer_assets("me").len(), 1);
+
+        // undo latest first
+        for block in [burn, merge, split].iter() {
+            ledger.undo_block(block);
+        }
+        assert_eq!(ledger.assets.len(), 15, "created assets removed");
+        assert_eq!(
+            ledger.get_peer_assets("me").len(),
+            3,
+            "spent assets restored"
+        );
+        assert!(!ledger.get_asset(&hashes[0]).unwrap().is_spent());
+    }
+
+    #[test]
+    fn asks_work() {
+        let mut ledger = AssetLedger::generate("me");
+        let asset_id = ledger.assets[0].get_hash().to_owned();
+        ledger.change_owner(&asset_id, "me");
+
+        assert_eq!(ledger.take_ask(&asset_id, 10), Err(TxError::NotForSale));
+        assert_eq!(
+            ledger.set_ask("you", &asset_id, 10),
+            Err(TxError::NotAssetOwner),
+            "only owners ask for prices"
+        );
+        assert_eq!(ledger.set_ask("me", "nope", 10), Err(TxError::UnknownAsset));
+        ledger.set_ask("me", &asset_id, 10).unwrap();
+        assert_eq!(ledger.take_ask(&asset_id, 9), Err(TxError::PriceTooLow));
+        ledger.take_ask(&asset_id, 10).unwrap();
+        assert_eq!(
+            ledger.take_ask(&asset_id, 10),
+            Err(TxError::NotForSale),
+            "asks are withdrawn once accepted"
+        );
+    }
+
+    #[test]
+    fn supply_policy_works() {
+        use crate::block::BlockBuilder;
+
+        let policy = SupplyPolicy {
+            cap: Some(1600),
+            issuance: Some(10),
+        };
+        assert_eq!(policy.check(1500, 0, 10, 100), Ok(()));
+        assert_eq!(
+            policy.check(1500, 0, 10, 101),
+            Err(TxError::SupplyCapExceeded)
+        );
+        assert_eq!(
+            policy.check(1000, 50, 10, 60),
+            Err(TxError::IssuanceExceeded),
+            "no more than 10 per block"
+        );
+        assert_eq!(
+            SupplyPolicy::default().check(usize::MAX - 1, 0, 0, 1),
+            Ok(()),
+            "no limits by default"
+        );
+        assert_eq!(
+            policy.check(usize::MAX, 0, 10, 1),
+            Err(TxError::SupplyCapExceeded),
+            "overflowing supply exceeds the cap"
+        );
+        let issuance = SupplyPolicy {
+            cap: None,
+            issuance: Some(usize::MAX),
+        };
+        assert_eq!(
+            issuance.check(0, usize::MAX, usize::MAX, 1),
+            Err(TxError::IssuanceExceeded),
+            "overflowing issuance exceeds the schedule"
+        );
+        assert_eq!(issuance.check(0, 0, 2, usize::MAX), Ok(()));
+
+        let mut ledger = AssetLedger::generate("me");
+        let supply = ledger.supply();
+        let prev = Block::genesis();
+        let asset_id = mint_id(prev.get_hash(), "gold", 50);
+        let mint = BlockBuilder::default()
+            .set_tx("auditor")
+            .set_rx("me")
+            .set_validator("auditor")
+            .set_asset_id(&asset_id)
+            .set_kind(TxKind::MintAsset {
+                name: "gold".into(),
+                value: 50,
+            })
+            .set_prev(&prev)
+            .build();
+        ledger.apply_block(&mint);
+        assert_eq!(ledger.supply(), supply + 50);
+        let asset = ledger.get_asset(&asset_id).unwrap();
+        assert_eq!(asset.get_name(), "gold");
+        assert_eq!(asset.get_owner().as_deref(), Some("me"));
+
+        ledger.undo_block(&mint);
+        assert_eq!(ledger.supply(), supply);
+        assert!(
+            ledger.get_asset(&asset_id).is_none(),
+            "minted asset removed"
+        );
+    }
+
+    #[test]
+    fn bond_and_slash_works() {
+        use crate::block::{BlockBuilder, Evidence};
+
+        let mut ledger = AssetLedger::generate("me");
+        let hashes: Vec<String> = ledger.assets[..2]
+            .iter()
+            .map(|asset| asset.get_hash().to_owned())
+            .collect();
+        for hash in hashes.iter() {
+            ledger.change_owner(hash, "me");
+        }
+        let block = |asset_id: &str, kind: TxKind| {
+            BlockBuilder::default()
+                .set_tx("me")
+                .set_rx("me")
+                .set_validator("validator")
+                .set_asset_id(asset_id)
+                .set_kind(kind)
+                .set_prev(&Block::genesis())
+                .build()
+        };
+
+        assert_eq!(
+            ledger.check_stake("you", &hashes[0], &TxKind::Bond, 5, 10),
+            Err(TxError::NotAssetOwner)
+        );
+        assert_eq!(
+            ledger.check_stake("me", &hashes[0], &TxKind::Unbond { unlock: 15 }, 5, 10),
+            Err(TxError::NotBonded)
+        );
+        let bond = block(&hashes[0], TxKind::Bond);
+        ledger.apply_block(&bond);
+        assert!(ledger.get_asset(&hashes[0]).unwrap().is_bonded());
+        assert_eq!(
+            ledger.default_stake(),
+            vec![hashes[0].clone()],
+            "bonded assets are staked"
+        );
+        assert_eq!(
+            ledger.voting_stake().get("me"),
+            Some(&ledger.get_asset(&hashes[0]).unwrap().get_value()),
+            "only bonded assets weigh votes"
+        );
+        assert_eq!(
+            ledger.check_stake("me", &hashes[0], &TxKind::Asset, 5, 10),
+            Err(TxError::AssetLocked),
+            "bonded assets can't be sold"
+        );
+        let merge = TxKind::Merge {
+            assets: vec![hashes[0].clone()],
+            value: 200,
+        };
+        assert_eq!(
+            ledger.check_stake("me", &hashes[1], &merge, 5, 10),
+            Err(TxError::AssetLocked)
+        );
+
+        assert_eq!(
+            ledger.check_stake("me", &hashes[0], &TxKind::Unbond { unlock: 14 }, 5, 10),
+            Err(TxError::UnbondingTooShort)
+        );
+        assert_eq!(
+            ledger.check_stake(
+                "me",
+                &hashes[0],
+                &TxKind::Unbond { unlock: 14 },
+                5,
+                usize::MAX
+            ),
+            Err(TxError::UnbondingTooShort),
+            "unbonding period saturates"
+        );
+        let unbond = block(&hashes[0], TxKind::Unbond { unlock: 15 });
+        ledger.apply_block(&unbond);
+        let asset = ledger.get_asset(&hashes[0]).unwrap();
+        assert!(!asset.is_bonded());
+        assert!(asset.is_locked(14), "locked during unbonding period");
+        assert!(!asset.is_locked(15));
+        assert_eq!(
+            ledger.check_stake("me", &hashes[0], &TxKind::Asset, 15, 10),
+            Ok(())
+        );
+
+        ledger.undo_block(&unbond);
+        assert!(ledger.get_asset(&hashes[0]).unwrap().is_bonded());
+
+        // "me" validates conflicting blocks
+        let prev = Block::genesis();
+        let conflicting = |rx: &str| {
+            BlockBuilder::default()
+                .set_tx("you")
+                .set_rx(rx)
+                .set_asset_id("asset")
+                .set_validator("me")
+                .set_prev(&prev)
+                .build()
+        };
+        let evidence = Evidence::new(conflicting("normal"), conflicting("victim")).unwrap();
+        assert_eq!(ledger.slashable("me", 5), vec![hashes[0].clone()]);
+        let slash = |assets: Vec<String>| TxKind::Slash {
+            evidence: Box::new(evidence.clone()),
+            assets,
+        };
+        assert_eq!(
+            ledger.check_stake("you", "", &slash(hashes.clone()), 5, 10),
+            Err(TxError::InvalidSlash),
+            "only locked assets are slashed"
+        );
+        assert_eq!(
+            ledger.check_stake("you", "", &slash(vec![hashes[0].clone()]), 5, 10),
+            Ok(())
+        );
+
+        let slash = BlockBuilder::default()
+            .set_tx("you")
+            .set_rx("me")
+            .set_validator("validator")
+            .set_kind(slash(vec![hashes[0].clone()]))
+            .set_prev(&prev)
+            .build();
+        ledger.apply_block(&slash);
+        assert!(
+            ledger.get_asset(&hashes[0]).unwrap().is_spent(),
+            "bond burned"
+        );
+        assert!(ledger.slashable("me", 5).is_empty());
+        ledger.undo_block(&slash);
+        assert!(!ledger.get_asset(&hashes[0]).unwrap().is_spent());
+    }
 }
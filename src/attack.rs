@@ -0,0 +1,284 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! State that an attacking peer maintains while carrying out attacks.
+//! The easiest way to interact with attack state is with the [Attack] actor.
+//!
+//! # [Attack] actor supports the following messages:
+//! - [SetEclipse]: Set(or clear) the peer that is being eclipsed
+//! - [GetEclipse]: Get the peer that is being eclipsed
+//! - [AddSybils]: Add fake identities controlled by this peer
+//! - [GetSybils]: Get fake identities controlled by this peer
+//! - [CaptureTx]: Record a transaction that passed through this peer
+//! - [GetCapturedTxs]: Get transactions that passed through this peer
+//! - [SetSelfish]: Start(or stop) withholding blocks
+//! - [GetSelfish]: Check if this peer is withholding blocks
+
+use actix::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::payload::{Peer, ValidateTx};
+
+/// Attack state of a peer
+#[derive(Deserialize, Default, Serialize, Clone, Debug)]
+pub struct Attack {
+    /// ID of the peer that is being eclipsed
+    pub eclipse: Option<String>,
+    /// IDs of fake identities controlled by this peer
+    pub sybils: Vec<String>,
+    /// transactions that passed through this peer, can be replayed
+    pub captured: Vec<ValidateTx>,
+    /// when set, blocks are mined privately on the tampered chain
+    /// and released strategically
+    pub selfish: bool,
+}
+
+impl Actor for Attack {
+    type Context = Context<Self>;
+}
+
+/// Set peer that should be eclipsed, `None` stops the attack
+#[derive(Deserialize, Serialize, Message)]
+#[rtype(result = "()")]
+pub struct SetEclipse {
+    pub victim: Option<String>,
+}
+
+/// Get peer that is being eclipsed
+#[derive(Message)]
+#[rtype(result = "Option<String>")]
+pub struct GetEclipse;
+
+/// Add fake identities
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AddSybils(pub Vec<String>);
+
+/// Get fake identities
+#[derive(Message)]
+#[rtype(result = "Vec<String>")]
+pub struct GetSybils;
+
+/// Record a transaction
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CaptureTx(pub ValidateTx);
+
+/// Get recorded transactions
+#[derive(Message)]
+#[rtype(result = "Vec<ValidateTx>")]
+pub struct GetCapturedTxs;
+
+/// Start or stop withholding blocks
+#[derive(Deserialize, Serialize, Message)]
+#[rtype(result = "()")]
+pub struct SetSelfish {
+    pub enabled: bool,
+}
+
+/// Check if blocks are being withheld
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct GetSelfish;
+
+impl Attack {
+    /// poisons a peer ledger for `victim`: every peer, other than the
+    /// victim itself, is made to resolve to `attacker_ip`. A decoy peer is
+    /// added so that the poisoned ledger is longer than the victim's
+    /// current ledger, which makes the victim replace its ledger when
+    /// synchronising
+    pub fn poison(peers: &[Peer], victim: &str, attacker_id: &str, attacker_ip: &str) -> Vec<Peer> {
+        let mut poisoned: Vec<Peer> = peers
+            .iter()
+            .map(|peer| {
+                if peer.id == victim {
+                    peer.clone()
+                } else {
+                    Peer {
+                        id: peer.id.clone(),
+                        ip: attacker_ip.into(),
+                    }
+                }
+            })
+            .collect();
+
+        poisoned.push(Peer {
+            id: format!("eclipse.{}", attacker_id),
+            ip: attacker_ip.into(),
+        });
+        poisoned
+    }
+}
+
+impl Handler<SetEclipse> for Attack {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetEclipse, _ctx: &mut Self::Context) -> Self::Result {
+        self.eclipse = msg.victim;
+    }
+}
+
+impl Handler<GetEclipse> for Attack {
+    type Result = MessageResult<GetEclipse>;
+
+    fn handle(&mut self, _msg: GetEclipse, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.eclipse.clone())
+    }
+}
+
+impl Handler<AddSybils> for Attack {
+    type Result = ();
+
+    fn handle(&mut self, msg: AddSybils, _ctx: &mut Self::Context) -> Self::Result {
+        self.sybils.extend(msg.0);
+    }
+}
+
+impl Handler<GetSybils> for Attack {
+    type Result = MessageResult<GetSybils>;
+
+    fn handle(&mut self, _msg: GetSybils, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.sybils.clone())
+    }
+}
+
+impl Handler<CaptureTx> for Attack {
+    type Result = ();
+
+    fn handle(&mut self, msg: CaptureTx, _ctx: &mut Self::Context) -> Self::Result {
+        // replayed transactions may pass through this peer again
+        if !self.captured.contains(&msg.0) {
+            self.captured.push(msg.0);
+        }
+    }
+}
+
+impl Handler<GetCapturedTxs> for Attack {
+    type Result = MessageResult<GetCapturedTxs>;
+
+    fn handle(&mut self, _msg: GetCapturedTxs, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.captured.clone())
+    }
+}
+
+impl Handler<SetSelfish> for Attack {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetSelfish, _ctx: &mut Self::Context) -> Self::Result {
+        self.selfish = msg.enabled;
+    }
+}
+
+impl Handler<GetSelfish> for Attack {
+    type Result = MessageResult<GetSelfish>;
+
+    fn handle(&mut self, _msg: GetSelfish, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.selfish)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poison_works() {
+        let peers = vec![
+            Peer {
+                id: "attacker.batsense.net".into(),
+                ip: "localhost:7001".into(),
+            },
+            Peer {
+                id: "victim.batsense.net".into(),
+                ip: "localhost:7002".into(),
+            },
+            Peer {
+                id: "normal.batsense.net".into(),
+                ip: "localhost:7003".into(),
+            },
+        ];
+
+        let poisoned = Attack::poison(
+            &peers,
+            "victim.batsense.net",
+            "attacker.batsense.net",
+            "localhost:7001",
+        );
+
+        assert!(poisoned.len() > peers.len(), "poisoned ledger is longer");
+        poisoned.iter().for_each(|peer| {
+            if peer.id == "victim.batsense.net" {
+                assert_eq!(peer.ip, "localhost:7002", "victim untouched");
+            } else {
+                assert_eq!(peer.ip, "localhost:7001", "peer resolves to attacker");
+            }
+        });
+    }
+
+    #[actix_rt::test]
+    async fn attack_actor_works() {
+        use crate::payload::{TxBuilder, ValidateTxBuilder};
+
+        let attack_addr = Attack::default().start();
+        assert_eq!(attack_addr.send(GetEclipse).await.unwrap(), None);
+
+        attack_addr
+            .send(SetEclipse {
+                victim: Some("victim.batsense.net".into()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            attack_addr.send(GetEclipse).await.unwrap(),
+            Some("victim.batsense.net".into()),
+            "set eclipse works"
+        );
+
+        attack_addr.send(SetEclipse { victim: None }).await.unwrap();
+        assert_eq!(attack_addr.send(GetEclipse).await.unwrap(), None);
+
+        let sybils = vec!["sybil-0".to_string(), "sybil-1".to_string()];
+        attack_addr.send(AddSybils(sybils.clone())).await.unwrap();
+        assert_eq!(
+            attack_addr.send(GetSybils).await.unwrap(),
+            sybils,
+            "sybils work"
+        );
+
+        let tx = ValidateTxBuilder::default()
+            .tx(TxBuilder::default()
+                .asset_id("asset".into())
+                .buyer_peer_id("attacker.batsense.net".into())
+                .build()
+                .unwrap())
+            .seller_peer_id("victim.batsense.net".into())
+            .build()
+            .unwrap();
+        attack_addr.send(CaptureTx(tx.clone())).await.unwrap();
+        attack_addr.send(CaptureTx(tx)).await.unwrap();
+        let captured = attack_addr.send(GetCapturedTxs).await.unwrap();
+        assert_eq!(captured.len(), 1, "capture tx works");
+        assert_eq!(captured[0].seller_peer_id, "victim.batsense.net");
+
+        assert!(!attack_addr.send(GetSelfish).await.unwrap());
+        attack_addr
+            .send(SetSelfish { enabled: true })
+            .await
+            .unwrap();
+        assert!(attack_addr.send(GetSelfish).await.unwrap(), "selfish works");
+    }
+}
@@ -46,6 +46,199 @@
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
+/// Type of transaction recorded in a [Block]. Token amounts are in
+/// units of the native token
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TxKind {
+    /// asset changes hands(`tx` to `rx`) without payment
+    #[default]
+    Asset,
+    /// asset is sold(`tx` to `rx`) for `price` tokens, paid by the
+    /// buyer(`rx`) along with a `fee` for the validator
+    Sale { price: u64, fee: u64 },
+    /// `amount` tokens are sent from `tx` to `rx`, `tx` pays a `fee`
+    /// to the validator
+    Transfer { amount: u64, fee: u64 },
+    /// `amount` tokens are minted to `rx` by the auditor(`tx`)
+    Mint { amount: u64 },
+    /// asset is split into assets worth `values`, which must add up
+    /// to the asset's value. Owner is both `tx` and `rx`
+    Split { values: Vec<usize> },
+    /// asset and `assets` are merged into an asset worth `value`,
+    /// which must be the sum of their values. Owner is both `tx` and `rx`
+    Merge { assets: Vec<String>, value: usize },
+    /// asset worth `value` is destroyed. Owner is both `tx` and `rx`
+    Burn { value: usize },
+    /// asset `name` worth `value` is minted to `rx` by the auditor(`tx`)
+    MintAsset { name: String, value: usize },
+    /// asset is bonded as stake and can't be transacted until it's
+    /// unbonded. Owner is both `tx` and `rx`
+    Bond,
+    /// bonded asset stops counting as stake but stays locked until
+    /// block `unlock`. Owner is both `tx` and `rx`
+    Unbond { unlock: usize },
+    /// `assets` locked by the validator(`rx`) that signed the conflicting
+    /// blocks of `evidence` are burned, `tx` submits the evidence
+    Slash {
+        evidence: Box<Evidence>,
+        assets: Vec<String>,
+    },
+}
+
+impl TxKind {
+    /// checks if the transaction moves an asset
+    pub fn moves_asset(&self) -> bool {
+        matches!(self, TxKind::Asset | TxKind::Sale { .. })
+    }
+
+    /// checks if the transaction uses up an asset of the sender, either
+    /// by moving it or by splitting, merging or burning it
+    pub fn spends_asset(&self) -> bool {
+        self.moves_asset() || self.changes_asset()
+    }
+
+    /// checks if the block carries an asset ID: assets that are spent,
+    /// minted, bonded or unbonded
+    pub fn has_asset(&self) -> bool {
+        self.spends_asset() || self.bonds_asset() || matches!(self, TxKind::MintAsset { .. })
+    }
+
+    /// checks if the transaction bonds or unbonds an asset
+    pub fn bonds_asset(&self) -> bool {
+        matches!(self, TxKind::Bond | TxKind::Unbond { .. })
+    }
+
+    /// checks if the transaction is recorded in asset ledgers with
+    /// [apply_block](crate::asset::AssetLedger::apply_block), every
+    /// transaction that affects assets except the ones that move them
+    pub fn is_applied(&self) -> bool {
+        (self.has_asset() && !self.moves_asset()) || matches!(self, TxKind::Slash { .. })
+    }
+
+    /// checks if the transaction splits, merges or burns an asset
+    pub fn changes_asset(&self) -> bool {
+        matches!(
+            self,
+            TxKind::Split { .. } | TxKind::Merge { .. } | TxKind::Burn { .. }
+        )
+    }
+
+    /// checks if the transaction is validated through consensus,
+    /// mints are created by the auditor
+    pub fn is_validated(&self) -> bool {
+        !matches!(self, TxKind::Mint { .. } | TxKind::MintAsset { .. })
+    }
+
+    /// peer that pays for the transaction, tokens minted
+    /// out of thin air have no payer
+    pub fn payer<'a>(&self, tx: &'a str, rx: &'a str) -> Option<&'a str> {
+        match self {
+            TxKind::Sale { .. } => Some(rx),
+            TxKind::Transfer { .. } => Some(tx),
+            _ => None,
+        }
+    }
+
+    /// tokens spent by the payer, including fees. `None` if the
+    /// amounts overflow
+    pub fn cost(&self) -> Option<u64> {
+        match self {
+            TxKind::Sale { price, fee } => price.checked_add(*fee),
+            TxKind::Transfer { amount, fee } => amount.checked_add(*fee),
+            _ => Some(0),
+        }
+    }
+
+    /// token movements, `(from, to, amount)`. `from` is `None` for
+    /// minted tokens
+    pub fn token_transfers<'a>(
+        &self,
+        tx: &'a str,
+        rx: &'a str,
+        validator: &'a str,
+    ) -> Vec<(Option<&'a str>, &'a str, u64)> {
+        match *self {
+            TxKind::Sale { price, fee } => vec![(Some(rx), tx, price), (Some(rx), validator, fee)],
+            TxKind::Transfer { amount, fee } => {
+                vec![(Some(tx), rx, amount), (Some(tx), validator, fee)]
+            }
+            TxKind::Mint { amount } => vec![(None, rx, amount)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// part of the block hash, empty for plain asset transactions so
+    /// that their hashes don't change
+    fn hash_input(&self) -> String {
+        match self {
+            TxKind::Asset => String::default(),
+            // serializing a enum of integers, strings and blocks can't fail
+            _ => serde_json::to_string(self).unwrap(),
+        }
+    }
+}
+
+/// Proof that a validator signed two conflicting blocks: blocks on top
+/// of the same block, created by the same validator, with different
+/// hashes. Blocks are ordered by hash so that the same conflict always
+/// makes the same evidence
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Evidence {
+    pub first: Block,
+    pub second: Block,
+}
+
+impl Evidence {
+    /// evidence of conflicting blocks `a` and `b`, `None` if they
+    /// don't conflict
+    pub fn new(a: Block, b: Block) -> Option<Self> {
+        let (first, second) = if a.get_hash() < b.get_hash() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let evidence = Evidence { first, second };
+        if evidence.is_valid() {
+            Some(evidence)
+        } else {
+            None
+        }
+    }
+
+    /// checks if blocks are intact, ordered and conflicting and name
+    /// a validator
+    pub fn is_valid(&self) -> bool {
+        let (first, second) = (&self.first, &self.second);
+        self.offender().is_some()
+            && !first.is_genesis()
+            && !second.is_genesis()
+            && first.hash() == first.get_hash()
+            && second.hash() == second.get_hash()
+            && first.get_hash() < second.get_hash()
+            && first.get_prev() == second.get_prev()
+            && first.get_validator() == second.get_validator()
+    }
+
+    /// validator that signed the conflicting blocks, `None` if the
+    /// blocks don't name a validator
+    pub fn offender(&self) -> Option<&String> {
+        self.first
+            .get_validator()
+            .filter(|validator| !validator.is_empty())
+    }
+
+    /// ID of the evidence, derived from the hashes of the blocks
+    pub fn id(&self) -> String {
+        use crate::utils::hasher;
+        hasher(&format!(
+            "{}{}",
+            self.first.get_hash(),
+            self.second.get_hash()
+        ))
+    }
+}
+
 /// Builder struct for [Block]
 // custom  builder is required because
 // Option<T> is needed for genesis
@@ -61,6 +254,16 @@ pub struct BlockBuilder {
     asset_id: String,
     /// validator's ID
     validator: String,
+    /// type of transaction
+    kind: TxKind,
+    /// output spent by the transaction, UTXO ledgers only
+    input: String,
+    /// VRF proof of the validator, see [crate::vrf]
+    proof: String,
+    /// number of transactions sent by the sender before this one
+    nonce: Option<usize>,
+    /// ID of the chain that the transaction is meant for
+    chain_id: Option<String>,
 }
 
 impl BlockBuilder {
@@ -94,18 +297,71 @@ impl BlockBuilder {
         self
     }
 
+    /// set type of transaction, defaults to [TxKind::Asset]
+    pub fn set_kind(&mut self, kind: TxKind) -> &mut Self {
+        self.kind = kind;
+        self
+    }
+
+    /// set output spent by the transaction
+    pub fn set_input(&mut self, input: &str) -> &mut Self {
+        self.input = input.into();
+        self
+    }
+
+    /// set VRF proof that the validator won the lottery for the block
+    pub fn set_proof(&mut self, proof: &str) -> &mut Self {
+        self.proof = proof.into();
+        self
+    }
+
+    /// set sender's nonce, used for replay protection
+    pub fn set_nonce(&mut self, nonce: Option<usize>) -> &mut Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// set ID of the chain that the transaction is meant for, used for
+    /// replay protection
+    pub fn set_chain_id(&mut self, chain_id: Option<&str>) -> &mut Self {
+        self.chain_id = chain_id.map(String::from);
+        self
+    }
+
+    /// hash of every field but the VRF proof, lottery winners sign it
+    /// with their proof, see [crate::vrf]
+    pub fn content_hash(&self) -> String {
+        use crate::utils::*;
+        hasher(&format!(
+            "{}{}{}{}{}{}{}{}",
+            self.prev,
+            self.rx,
+            self.tx,
+            if self.kind.has_asset() {
+                self.asset_id.as_str()
+            } else {
+                ""
+            },
+            self.validator,
+            self.kind.hash_input(),
+            self.input,
+            replay_hash_input(self.nonce, self.chain_id.as_deref())
+        ))
+    }
+
     fn hash(&self) -> String {
         use crate::utils::*;
-        hasher(&format!("{}{}{}", self.prev, self.rx, self.tx))
+        hasher(&format!("{}{}", self.content_hash(), self.proof))
     }
 
-    /// Build block, this method must be called at the very end
+    /// Build block, this method must be called at the very end.
+    /// Asset ID is only required by transactions that spend or mint assets
     pub fn build(&mut self) -> Block {
         use crate::utils::*;
         if self.prev.is_empty()
             || self.rx.is_empty()
             || self.tx.is_empty()
-            || self.asset_id.is_empty()
+            || (self.kind.has_asset() && self.asset_id.is_empty())
         {
             panic!("Can't create block, one or more fields are empty");
         } else {
@@ -118,13 +374,30 @@ impl BlockBuilder {
                 validator: Some(self.validator.to_owned()),
                 timesamp: get_current_time(),
                 serial_no: None,
-                asset_id: Some(self.asset_id.to_owned()),
+                asset_id: if self.kind.has_asset() {
+                    Some(self.asset_id.to_owned())
+                } else {
+                    None
+                },
+                kind: self.kind.clone(),
+                input: if self.input.is_empty() {
+                    None
+                } else {
+                    Some(self.input.to_owned())
+                },
+                proof: if self.proof.is_empty() {
+                    None
+                } else {
+                    Some(self.proof.to_owned())
+                },
+                nonce: self.nonce,
+                chain_id: self.chain_id.clone(),
             }
         }
     }
 }
 
-#[derive(Display, Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Display, Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
 #[display(fmt = "{}", hash)]
 /// Smallest data-sctructure that can go into [Chain](crate::chain::Chain).
 ///
@@ -141,6 +414,35 @@ pub struct Block {
     validator: Option<String>,
     serial_no: Option<usize>,
     asset_id: Option<String>,
+    #[serde(default)]
+    kind: TxKind,
+    #[serde(default)]
+    input: Option<String>,
+    /// VRF proof of the validator, only set when validators are chosen
+    /// by lottery
+    #[serde(default)]
+    proof: Option<String>,
+    /// sender's nonce, only set when the transaction carries replay
+    /// protection
+    #[serde(default)]
+    nonce: Option<usize>,
+    /// ID of the chain that the transaction is meant for, only set when
+    /// the transaction carries replay protection
+    #[serde(default)]
+    chain_id: Option<String>,
+}
+
+/// part of the block hash, empty for transactions without replay
+/// protection so that their hashes don't change
+fn replay_hash_input(nonce: Option<usize>, chain_id: Option<&str>) -> String {
+    match (nonce, chain_id) {
+        (None, None) => String::default(),
+        (nonce, chain_id) => format!(
+            ":{}:{}",
+            nonce.map(|nonce| nonce.to_string()).unwrap_or_default(),
+            chain_id.unwrap_or_default()
+        ),
+    }
 }
 
 impl Block {
@@ -175,6 +477,11 @@ impl Block {
             validator: None,
             serial_no: Some(0),
             asset_id: None,
+            kind: TxKind::default(),
+            input: None,
+            proof: None,
+            nonce: None,
+            chain_id: None,
         }
     }
 
@@ -196,14 +503,30 @@ impl Block {
             return self.get_hash().into();
         } else {
             hasher(&format!(
-                "{}{}{}",
-                self.prev.as_ref().unwrap(),
-                self.rx.as_ref().unwrap(),
-                self.tx.as_ref().unwrap()
+                "{}{}",
+                self.content_hash(),
+                self.proof.as_deref().unwrap_or_default()
             ))
         }
     }
 
+    /// hash of every field but the VRF proof, see
+    /// [BlockBuilder::content_hash]
+    pub fn content_hash(&self) -> String {
+        use crate::utils::*;
+        hasher(&format!(
+            "{}{}{}{}{}{}{}{}",
+            self.prev.as_deref().unwrap_or_default(),
+            self.rx.as_deref().unwrap_or_default(),
+            self.tx.as_deref().unwrap_or_default(),
+            self.asset_id.as_deref().unwrap_or_default(),
+            self.validator.as_deref().unwrap_or_default(),
+            self.kind.hash_input(),
+            self.input.as_deref().unwrap_or_default(),
+            replay_hash_input(self.nonce, self.chain_id.as_deref())
+        ))
+    }
+
     /// get hash of previous block
     pub fn get_prev(&self) -> Option<&String> {
         self.prev.as_ref()
@@ -219,6 +542,11 @@ impl Block {
         &self.hash
     }
 
+    /// get time at which block was created
+    pub fn get_timesamp(&self) -> &str {
+        &self.timesamp
+    }
+
     /// get serial numbr of block
     pub fn get_serial_no(&self) -> Option<usize> {
         self.serial_no
@@ -245,6 +573,46 @@ impl Block {
     pub fn get_tx(&self) -> Option<&String> {
         self.tx.as_ref()
     }
+
+    /// get type of transaction that lead to the creation of this block
+    pub fn get_kind(&self) -> &TxKind {
+        &self.kind
+    }
+
+    /// get output spent by the transaction, only set on UTXO ledgers
+    pub fn get_input(&self) -> Option<&String> {
+        self.input.as_ref()
+    }
+
+    /// get VRF proof of the validator, only set when validators are
+    /// chosen by lottery
+    pub fn get_proof(&self) -> Option<&String> {
+        self.proof.as_ref()
+    }
+
+    /// get sender's nonce, only set when the transaction carries
+    /// replay protection
+    pub fn get_nonce(&self) -> Option<usize> {
+        self.nonce
+    }
+
+    /// get ID of the chain that the transaction is meant for, only set
+    /// when the transaction carries replay protection
+    pub fn get_chain_id(&self) -> Option<&String> {
+        self.chain_id.as_ref()
+    }
+
+    /// token movements of the block's transaction, see
+    /// [TxKind::token_transfers]
+    pub fn token_transfers(&self) -> Vec<(Option<&str>, &str, u64)> {
+        match (self.get_tx(), self.get_rx()) {
+            (Some(tx), Some(rx)) => {
+                let validator = self.get_validator().map(|v| v.as_str()).unwrap_or("");
+                self.kind.token_transfers(tx, rx, validator)
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -286,6 +654,193 @@ mod tests {
         );
     }
 
+    #[test]
+    fn tx_kind_works() {
+        let prev = Block::genesis();
+        let asset = BlockBuilder::default()
+            .set_tx("Me")
+            .set_rx("You")
+            .set_prev(&prev)
+            .set_asset_id("asset")
+            .build();
+        assert_eq!(asset.get_kind(), &TxKind::Asset, "asset sale is default");
+        assert!(asset.token_transfers().is_empty());
+        assert_eq!(asset.get_input(), None, "input only set on UTXO ledgers");
+
+        let spend = BlockBuilder::default()
+            .set_tx("Me")
+            .set_rx("You")
+            .set_prev(&prev)
+            .set_asset_id("asset")
+            .set_input("output")
+            .build();
+        assert_eq!(spend.get_input().unwrap(), "output");
+        assert_ne!(spend.get_hash(), asset.get_hash(), "input is hashed");
+        assert_eq!(spend.hash(), spend.get_hash());
+
+        let mut builder = BlockBuilder::default();
+        builder
+            .set_tx("Me")
+            .set_rx("You")
+            .set_prev(&prev)
+            .set_asset_id("asset")
+            .set_proof("proof");
+        let proven = builder.build();
+        assert_eq!(proven.get_proof().unwrap(), "proof");
+        assert_ne!(proven.get_hash(), asset.get_hash(), "proof is hashed");
+        assert_eq!(proven.hash(), proven.get_hash());
+        assert_eq!(builder.content_hash(), proven.content_hash());
+        assert_eq!(
+            proven.content_hash(),
+            asset.content_hash(),
+            "proof isn't part of the contents"
+        );
+        let other_asset = BlockBuilder::default()
+            .set_tx("Me")
+            .set_rx("You")
+            .set_prev(&prev)
+            .set_asset_id("other asset")
+            .build();
+        assert_ne!(
+            other_asset.content_hash(),
+            asset.content_hash(),
+            "asset ID is part of the contents"
+        );
+
+        let protected = BlockBuilder::default()
+            .set_tx("Me")
+            .set_rx("You")
+            .set_prev(&prev)
+            .set_asset_id("asset")
+            .set_nonce(Some(0))
+            .set_chain_id(Some(prev.get_hash()))
+            .build();
+        assert_eq!(protected.get_nonce(), Some(0));
+        assert_eq!(protected.get_chain_id().unwrap(), prev.get_hash());
+        assert_ne!(protected.get_hash(), asset.get_hash(), "nonce is hashed");
+        assert_eq!(protected.hash(), protected.get_hash());
+        let mut replayed = protected.clone();
+        replayed.nonce = Some(1);
+        assert_ne!(
+            replayed.hash(),
+            replayed.get_hash(),
+            "nonce can't be changed"
+        );
+
+        let kind = TxKind::Transfer { amount: 10, fee: 1 };
+        let transfer = BlockBuilder::default()
+            .set_tx("Me")
+            .set_rx("You")
+            .set_validator("Validator")
+            .set_prev(&prev)
+            .set_kind(kind.clone())
+            .build();
+        assert_eq!(transfer.get_asset_id(), None, "transfers don't need assets");
+        assert_ne!(transfer.get_hash(), asset.get_hash(), "kind is hashed");
+        assert_eq!(transfer.hash(), transfer.get_hash());
+        assert_eq!(
+            transfer.token_transfers(),
+            vec![(Some("Me"), "You", 10), (Some("Me"), "Validator", 1)]
+        );
+        assert_eq!(kind.cost(), Some(11));
+        let overflow = TxKind::Transfer {
+            amount: u64::MAX,
+            fee: 1,
+        };
+        assert_eq!(overflow.cost(), None, "overflowing costs are rejected");
+        assert_eq!(kind.payer("Me", "You"), Some("Me"));
+
+        let sale = TxKind::Sale { price: 5, fee: 2 };
+        assert_eq!(sale.payer("Me", "You"), Some("You"), "buyer pays");
+        assert!(sale.moves_asset());
+        let mint = TxKind::Mint { amount: 3 };
+        assert_eq!(mint.payer("Auditor", "You"), None);
+        assert!(!mint.is_validated());
+
+        let json = serde_json::to_string(&transfer).unwrap();
+        let decoded: Block = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.get_kind(), &kind);
+        let legacy = json.replace(r#","kind":{"type":"transfer","amount":10,"fee":1}"#, "");
+        let decoded: Block = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(decoded.get_kind(), &TxKind::Asset, "kind defaults to asset");
+    }
+
+    #[test]
+    fn evidence_works() {
+        let prev = Block::genesis();
+        let block = |rx: &str, validator: &str| {
+            BlockBuilder::default()
+                .set_tx("attacker")
+                .set_rx(rx)
+                .set_asset_id("asset")
+                .set_validator(validator)
+                .set_prev(&prev)
+                .build()
+        };
+        let (normal, victim) = (block("normal", "attacker"), block("victim", "attacker"));
+
+        let evidence = Evidence::new(victim.clone(), normal.clone()).unwrap();
+        assert!(evidence.first.get_hash() < evidence.second.get_hash());
+        assert_eq!(
+            Evidence::new(normal.clone(), victim.clone()).unwrap().id(),
+            evidence.id(),
+            "same conflict makes the same evidence"
+        );
+        assert_eq!(evidence.offender().unwrap(), "attacker");
+
+        assert!(
+            Evidence::new(normal.clone(), normal.clone()).is_none(),
+            "identical blocks don't conflict"
+        );
+        assert!(
+            Evidence::new(normal.clone(), block("victim", "normal")).is_none(),
+            "blocks of different validators don't conflict"
+        );
+        let next = BlockBuilder::default()
+            .set_tx("attacker")
+            .set_rx("victim")
+            .set_asset_id("asset")
+            .set_validator("attacker")
+            .set_prev(&normal)
+            .build();
+        assert!(
+            Evidence::new(normal.clone(), next).is_none(),
+            "blocks at different heights don't conflict"
+        );
+
+        let mut tampered = evidence.clone();
+        tampered.second.hash = tampered.first.get_hash().into();
+        assert!(!tampered.is_valid(), "blocks must be intact");
+
+        let mut forged = evidence.clone();
+        forged.first.validator = Some("normal".into());
+        forged.second.validator = Some("normal".into());
+        assert!(!forged.is_valid(), "validator is hashed");
+
+        let mut anonymous = evidence.clone();
+        anonymous.first.validator = None;
+        anonymous.second.validator = None;
+        assert_eq!(anonymous.offender(), None);
+        assert!(!anonymous.is_valid(), "blocks must name a validator");
+        assert!(
+            Evidence::new(block("normal", ""), block("victim", "")).is_none(),
+            "blocks must name a validator"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn sale_panic_works() {
+        let prev = Block::genesis();
+
+        let _ = BlockBuilder::default()
+            .set_rx("You")
+            .set_tx("Me")
+            .set_prev(&prev)
+            .set_kind(TxKind::Sale { price: 1, fee: 0 })
+            .build();
+    }
+
     #[test]
     #[should_panic]
     fn block_panic_works() {
@@ -34,19 +34,183 @@
 //! - [DumpLedger]: dumps the entire ledger
 //! - [ReplaceChain]: replaces a [Vec<Block>] inside the [Chain] data-structure, useful
 //! when synchronising ledgers
+//! - [GetNonce]: get number of transactions sent by a peer
+//! - [GetChainId]: get chain identifier(hash of genesis block)
+//! - [Reorg]: replaces the chain with a longer chain(longest chain rule)
+//! - [GetAssetHistory]: get every transfer of an asset
+//! - [GetBlock]: get a block by hash
+//! - [GetBlockBySerial]: get a block by serial number
+//! - [QueryBlocks]: get a page of blocks filtered by serial range and participants
+//! - [GetBalance]: get token balance of a peer
+//! - [GetBalances]: get token balances of all peers
+//! - [GetMinted]: get total value of assets minted at runtime
+//! - [HasEvidence]: check if a validator was slashed for evidence
+//! - [Finalize]: mark a block and its ancestors as final
+//! - [GetFinalized]: get the last final block
+//! - [RemoveTip]: remove the last block, unless it is final
+//!
+//! Lookups by hash, serial number, asset and participant are served from
+//! indexes that the [Chain] maintains as blocks are added. Token balances
+//! are computed from the transactions in the chain the same way, so they
+//! follow reorgs and forks.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use actix::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::block::Block;
+use crate::block::{Block, TxKind};
 use crate::error::*;
 
+/// number of blocks in a page when [BlockQuery::limit] isn't set
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+/// maximum number of blocks in a page
+pub const MAX_PAGE_SIZE: usize = 1000;
+
 /// Ledger data-structure for the blockchain
 ///
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Chain {
     name: String,
     blocks: Vec<Block>,
+    /// position of the last final block, reorgs can't rewrite it
+    #[serde(default)]
+    finalized: usize,
+    #[serde(skip)]
+    index: ChainIndex,
+}
+
+/// Positions of blocks in [Chain], keyed by the fields that blocks
+/// are looked up with
+#[derive(Clone, Debug, Default)]
+struct ChainIndex {
+    by_hash: HashMap<String, usize>,
+    by_serial: BTreeMap<usize, usize>,
+    by_asset: HashMap<String, Vec<usize>>,
+    by_tx: HashMap<String, Vec<usize>>,
+    by_rx: HashMap<String, Vec<usize>>,
+    by_validator: HashMap<String, Vec<usize>>,
+    balances: HashMap<String, u64>,
+    /// total value of assets minted at runtime
+    minted: usize,
+    /// IDs of evidence that validators were slashed for
+    evidence: HashSet<String>,
+}
+
+impl ChainIndex {
+    /// index block at position `pos`
+    fn insert(&mut self, pos: usize, block: &Block) {
+        fn push(index: &mut HashMap<String, Vec<usize>>, key: Option<&String>, pos: usize) {
+            if let Some(key) = key {
+                index.entry(key.clone()).or_default().push(pos);
+            }
+        }
+
+        self.by_hash.insert(block.get_hash().into(), pos);
+        if let Some(serial_no) = block.get_serial_no() {
+            self.by_serial.insert(serial_no, pos);
+        }
+        push(&mut self.by_asset, block.get_asset_id(), pos);
+        push(&mut self.by_tx, block.get_tx(), pos);
+        push(&mut self.by_rx, block.get_rx(), pos);
+        push(&mut self.by_validator, block.get_validator(), pos);
+
+        // blocks are validated before they are added, so balances
+        // can't go below zero here and overflows are rejected by
+        // [ChainIndex::check_tokens]
+        for (from, to, amount) in block.token_transfers() {
+            if let Some(from) = from {
+                let balance = self.balances.entry(from.into()).or_default();
+                *balance = balance.saturating_sub(amount);
+            }
+            let balance = self.balances.entry(to.into()).or_default();
+            *balance = balance.saturating_add(amount);
+        }
+
+        match block.get_kind() {
+            TxKind::MintAsset { value, .. } => self.minted = self.minted.saturating_add(*value),
+            TxKind::Slash { evidence, .. } => {
+                self.evidence.insert(evidence.id());
+            }
+            _ => (),
+        }
+    }
+
+    /// checks if the token movements of `block` can be represented:
+    /// the tokens spent and the balances of the recipients can't overflow
+    fn check_tokens(&self, block: &Block) -> ChainResult<()> {
+        block.get_kind().cost().ok_or(ChainError::TokenOverflow)?;
+        let mut received: HashMap<&str, u64> = HashMap::new();
+        for (_, to, amount) in block.token_transfers() {
+            let balance = received
+                .entry(to)
+                .or_insert_with(|| self.balances.get(to).copied().unwrap_or(0));
+            *balance = balance
+                .checked_add(amount)
+                .ok_or(ChainError::TokenOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// index `blocks`, checking the token movements of every block
+    fn try_build(blocks: &[Block]) -> ChainResult<Self> {
+        let mut index = ChainIndex::default();
+        for (pos, block) in blocks.iter().enumerate() {
+            index.check_tokens(block)?;
+            index.insert(pos, block);
+        }
+        Ok(index)
+    }
+
+    fn build(blocks: &[Block]) -> Self {
+        let mut index = ChainIndex::default();
+        for (pos, block) in blocks.iter().enumerate() {
+            index.insert(pos, block);
+        }
+        index
+    }
+}
+
+/// Block query, eg: `?from=4&to=10&tx=victim.batsense.net&limit=5`.
+/// All filters are optional and are combined
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct BlockQuery {
+    /// lowest serial number, inclusive
+    pub from: Option<usize>,
+    /// highest serial number, inclusive
+    pub to: Option<usize>,
+    /// seller
+    pub tx: Option<String>,
+    /// buyer
+    pub rx: Option<String>,
+    pub validator: Option<String>,
+    /// number of matching blocks to skip
+    #[serde(default)]
+    pub offset: usize,
+    /// number of blocks in the page, defaults to [DEFAULT_PAGE_SIZE]
+    /// and can't exceed [MAX_PAGE_SIZE]
+    pub limit: Option<usize>,
+}
+
+/// Page of blocks that matched a [BlockQuery], oldest first
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BlockPage {
+    /// number of blocks that matched the query
+    pub total: usize,
+    pub offset: usize,
+    pub blocks: Vec<Block>,
+}
+
+/// Transfer of an asset, rebuilt from the block that recorded it
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Transfer {
+    pub serial_no: Option<usize>,
+    /// hash of the block
+    pub block: String,
+    pub from: String,
+    pub to: String,
+    pub validator: String,
+    pub timesamp: String,
 }
 
 /// # [Chain] supports the followings messages:
@@ -55,6 +219,18 @@ pub struct Chain {
 /// - [DumpLedger]: dumps the entire ledger
 /// - [ReplaceChain]: replaces a [Vec<Block>] inside the [Chain] data-structure, useful
 /// when synchronising ledgers
+/// - [GetNonce]: get number of transactions sent by a peer
+/// - [GetChainId]: get chain identifier(hash of genesis block)
+/// - [Reorg]: replaces the chain with a longer chain(longest chain rule)
+/// - [GetAssetHistory]: get every transfer of an asset
+/// - [GetBlock]: get a block by hash
+/// - [GetBlockBySerial]: get a block by serial number
+/// - [QueryBlocks]: get a page of blocks filtered by serial range and participants
+/// - [GetBalance]: get token balance of a peer
+/// - [GetBalances]: get token balances of all peers
+/// - [GetMinted]: get total value of assets minted at runtime
+/// - [Finalize]: mark a block and its ancestors as final
+/// - [GetFinalized]: get the last final block
 
 impl Chain {
     /// create new blockchain
@@ -63,10 +239,22 @@ impl Chain {
         let blocks = vec![genesis];
         Chain {
             name: name.into(),
+            index: ChainIndex::build(&blocks),
             blocks,
+            finalized: 0,
         }
     }
 
+    /// rebuilds indexes, required after blocks are replaced
+    fn reindex(&mut self) {
+        self.index = ChainIndex::build(&self.blocks);
+    }
+
+    /// get all blocks in the chain
+    pub fn blocks(&self) -> Vec<Block> {
+        self.blocks.clone()
+    }
+
     /// get the last block in the chain
     pub fn get_last_block(&self) -> &Block {
         // unwrap is okay here because chain initiation guarentees
@@ -96,7 +284,9 @@ impl Chain {
                 last_serial_no + 1
             };
 
+            self.index.check_tokens(&block)?;
             block.set_serial_no(serial_no);
+            self.index.insert(self.blocks.len(), &block);
             self.blocks.push(block);
             return Ok(serial_no);
         }
@@ -104,6 +294,7 @@ impl Chain {
 
     /// checks if a blockchain is valid by comparing the hash of the previous
     /// element with the block.prev of the next element in the blockchain
+    /// and checking that token movements don't overflow
     pub fn is_valid(chain: &Vec<Block>) -> ChainResult<()> {
         let mut iter = chain.iter().peekable();
         loop {
@@ -119,14 +310,242 @@ impl Chain {
                 break;
             }
         }
+        ChainIndex::try_build(chain)?;
+        Ok(())
+    }
+
+    /// get number of transactions sent by `peer_id`, this is the
+    /// nonce that the peer's next transaction must carry
+    pub fn get_nonce(&self, peer_id: &str) -> usize {
+        self.index.by_tx.get(peer_id).map(Vec::len).unwrap_or(0)
+    }
+
+    /// every transfer of asset `asset_id`, oldest first
+    pub fn asset_history(&self, asset_id: &str) -> Vec<Transfer> {
+        self.index
+            .by_asset
+            .get(asset_id)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+            .iter()
+            .map(|pos| &self.blocks[*pos])
+            .map(|block| Transfer {
+                serial_no: block.get_serial_no(),
+                block: block.get_hash().into(),
+                from: block.get_tx().cloned().unwrap_or_default(),
+                to: block.get_rx().cloned().unwrap_or_default(),
+                validator: block.get_validator().cloned().unwrap_or_default(),
+                timesamp: block.get_timesamp().into(),
+            })
+            .collect()
+    }
+
+    /// get token balance of `peer_id`
+    pub fn get_balance(&self, peer_id: &str) -> u64 {
+        self.index.balances.get(peer_id).copied().unwrap_or(0)
+    }
+
+    /// get total value of assets minted at runtime
+    pub fn minted(&self) -> usize {
+        self.index.minted
+    }
+
+    /// checks if a validator was slashed for evidence `evidence_id`
+    pub fn has_evidence(&self, evidence_id: &str) -> bool {
+        self.index.evidence.contains(evidence_id)
+    }
+
+    /// get token balances of all peers that have ever held tokens
+    pub fn balances(&self) -> BTreeMap<String, u64> {
+        self.index
+            .balances
+            .iter()
+            .map(|(peer_id, balance)| (peer_id.clone(), *balance))
+            .collect()
+    }
+
+    /// get block by hash
+    pub fn get_block(&self, hash: &str) -> Option<&Block> {
+        self.index.by_hash.get(hash).map(|pos| &self.blocks[*pos])
+    }
+
+    /// get block by serial number
+    pub fn get_block_by_serial(&self, serial_no: usize) -> Option<&Block> {
+        self.index
+            .by_serial
+            .get(&serial_no)
+            .map(|pos| &self.blocks[*pos])
+    }
+
+    /// blocks that match `query`, paginated.
+    /// QueryError::InvalidRange is returned when `query.from` is
+    /// greater than `query.to`
+    pub fn query(&self, query: &BlockQuery) -> QueryResult<BlockPage> {
+        let from = query.from.unwrap_or(0);
+        let to = query.to.unwrap_or(usize::MAX);
+        if from > to {
+            return Err(QueryError::InvalidRange);
+        }
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let participants = [
+            (&query.tx, &self.index.by_tx),
+            (&query.rx, &self.index.by_rx),
+            (&query.validator, &self.index.by_validator),
+        ];
+        let mut candidates: Vec<&[usize]> = Vec::new();
+        for (filter, index) in participants.iter() {
+            if let Some(peer_id) = filter {
+                match index.get(peer_id.as_str()) {
+                    Some(positions) => candidates.push(positions),
+                    None => candidates.push(&[]),
+                }
+            }
+        }
+
+        let matches = |block: &Block| {
+            let is = |filter: &Option<String>, field: Option<&String>| {
+                filter.is_none() || filter.as_ref() == field
+            };
+            is(&query.tx, block.get_tx())
+                && is(&query.rx, block.get_rx())
+                && is(&query.validator, block.get_validator())
+        };
+
+        // the shortest participant index is scanned, other filters
+        // are checked on the blocks it points to
+        let positions: Vec<usize> = match candidates.into_iter().min_by_key(|c| c.len()) {
+            Some(positions) => positions
+                .iter()
+                .copied()
+                .filter(|pos| {
+                    let block = &self.blocks[*pos];
+                    let serial_no = block.get_serial_no().unwrap_or(0);
+                    serial_no >= from && serial_no <= to && matches(block)
+                })
+                .collect(),
+            None => self
+                .index
+                .by_serial
+                .range(from..=to)
+                .map(|(_, pos)| *pos)
+                .collect(),
+        };
+
+        Ok(BlockPage {
+            total: positions.len(),
+            offset: query.offset,
+            blocks: positions
+                .iter()
+                .skip(query.offset)
+                .take(limit)
+                .map(|pos| self.blocks[*pos].clone())
+                .collect(),
+        })
+    }
+
+    /// get chain identifier, which is the hash of the genesis block
+    pub fn get_chain_id(&self) -> &str {
+        // unwrap is okay here because chain initiation guarentees
+        // genesis block creation
+        self.blocks.first().unwrap().get_hash()
+    }
+
+    /// get index of the first block where `chain` diverges from
+    /// `current`
+    pub fn fork_point(current: &[Block], chain: &[Block]) -> usize {
+        current
+            .iter()
+            .zip(chain.iter())
+            .take_while(|(current, other)| current.get_hash() == other.get_hash())
+            .count()
+    }
+
+    /// checks if `chain` rewrites blocks of `current` that are more than
+    /// `checkpoint` blocks deep
+    pub fn check_checkpoint(
+        current: &[Block],
+        chain: &[Block],
+        checkpoint: Option<usize>,
+    ) -> ChainResult<()> {
+        if let Some(depth) = checkpoint {
+            let finalized = current.len().saturating_sub(depth);
+            if Chain::fork_point(current, chain) < finalized {
+                return Err(ChainError::CheckpointViolation);
+            }
+        }
         Ok(())
     }
 
+    /// replaces the current chain with `chain` if it is valid, longer
+    /// than the current chain and shares the same genesis block.
+    /// When `checkpoint` is set, blocks that are `checkpoint` blocks deeper
+    /// than the tip are final and can't be rewritten.
+    /// Returns the fork point and the blocks that were discarded
+    pub fn reorg(
+        &mut self,
+        chain: Vec<Block>,
+        checkpoint: Option<usize>,
+    ) -> ChainResult<(usize, Vec<Block>)> {
+        Chain::is_valid(&chain)?;
+        if chain.len() <= self.blocks.len() {
+            return Err(ChainError::ShorterChain);
+        }
+
+        let fork_point = Chain::fork_point(&self.blocks, &chain);
+        if fork_point == 0 {
+            return Err(ChainError::InvalidBlockChain);
+        }
+        if fork_point <= self.finalized {
+            return Err(ChainError::FinalityViolation);
+        }
+        Chain::check_checkpoint(&self.blocks, &chain, checkpoint)?;
+
+        let discarded = self.blocks.split_off(fork_point);
+        self.blocks = chain;
+        self.reindex();
+        Ok((fork_point, discarded))
+    }
+
+    /// replaces the current chain with `chain` if it is valid. Once a
+    /// block is final, `chain` must contain it
     pub fn replace_chain(&mut self, chain: Vec<Block>) -> ChainResult<()> {
         Chain::is_valid(&chain)?;
+        if self.finalized > 0 && Chain::fork_point(&self.blocks, &chain) <= self.finalized {
+            return Err(ChainError::FinalityViolation);
+        }
         self.blocks = chain;
+        self.reindex();
         Ok(())
     }
+
+    /// marks block `hash` and its ancestors as final, returns false if
+    /// the block isn't in the chain or is already final
+    pub fn finalize(&mut self, hash: &str) -> bool {
+        match self.index.by_hash.get(hash) {
+            Some(pos) if *pos > self.finalized => {
+                self.finalized = *pos;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// get the last final block, the genesis block when no block is final
+    pub fn get_finalized(&self) -> &Block {
+        &self.blocks[self.finalized]
+    }
+
+    /// removes and returns the last block, so that a competing block can
+    /// take its place. Final blocks and the genesis block can't be removed
+    pub fn remove_tip(&mut self) -> ChainResult<Block> {
+        if self.blocks.len() <= self.finalized + 1 {
+            return Err(ChainError::FinalityViolation);
+        }
+        let tip = self.blocks.pop().unwrap();
+        self.reindex();
+        Ok(tip)
+    }
 }
 
 impl Actor for Chain {
@@ -158,6 +577,81 @@ pub struct ReplaceChain(pub Vec<Block>);
 #[rtype(result = "Vec<Block>")]
 pub struct DumpLedger;
 
+/// Get number of transactions sent by a peer
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct GetNonce(pub String);
+
+/// Get chain identifier
+#[derive(Message)]
+#[rtype(result = "String")]
+pub struct GetChainId;
+
+/// Replace chain following the longest chain rule, returns the
+/// fork point and discarded blocks.
+/// `checkpoint` is the depth beyond which blocks can't be rewritten
+#[derive(Message)]
+#[rtype(result = "ChainResult<(usize, Vec<Block>)>")]
+pub struct Reorg {
+    pub chain: Vec<Block>,
+    pub checkpoint: Option<usize>,
+}
+
+/// Get every transfer of an asset
+#[derive(Message)]
+#[rtype(result = "Vec<Transfer>")]
+pub struct GetAssetHistory(pub String);
+
+/// Get block by hash
+#[derive(Message)]
+#[rtype(result = "Option<Block>")]
+pub struct GetBlock(pub String);
+
+/// Get block by serial number
+#[derive(Message)]
+#[rtype(result = "Option<Block>")]
+pub struct GetBlockBySerial(pub usize);
+
+/// Get a page of blocks that match a [BlockQuery]
+#[derive(Message)]
+#[rtype(result = "QueryResult<BlockPage>")]
+pub struct QueryBlocks(pub BlockQuery);
+
+/// Get token balance of a peer
+#[derive(Message)]
+#[rtype(result = "u64")]
+pub struct GetBalance(pub String);
+
+/// Get token balances of all peers
+#[derive(Message)]
+#[rtype(result = "BTreeMap<String, u64>")]
+pub struct GetBalances;
+
+/// Get total value of assets minted at runtime
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct GetMinted;
+
+/// Check if a validator was slashed for evidence, by evidence ID
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct HasEvidence(pub String);
+
+/// Mark a block and its ancestors as final, see [Chain::finalize]
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct Finalize(pub String);
+
+/// Remove the last block, see [Chain::remove_tip]
+#[derive(Message)]
+#[rtype(result = "ChainResult<Block>")]
+pub struct RemoveTip;
+
+/// Get the last final block
+#[derive(Message)]
+#[rtype(result = "Block")]
+pub struct GetFinalized;
+
 impl Handler<AddBlock> for Chain {
     type Result = MessageResult<AddBlock>;
 
@@ -190,6 +684,118 @@ impl Handler<DumpLedger> for Chain {
     }
 }
 
+impl Handler<GetNonce> for Chain {
+    type Result = MessageResult<GetNonce>;
+
+    fn handle(&mut self, msg: GetNonce, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_nonce(&msg.0))
+    }
+}
+
+impl Handler<GetChainId> for Chain {
+    type Result = MessageResult<GetChainId>;
+
+    fn handle(&mut self, _msg: GetChainId, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_chain_id().into())
+    }
+}
+
+impl Handler<Reorg> for Chain {
+    type Result = MessageResult<Reorg>;
+
+    fn handle(&mut self, msg: Reorg, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.reorg(msg.chain, msg.checkpoint))
+    }
+}
+
+impl Handler<GetAssetHistory> for Chain {
+    type Result = MessageResult<GetAssetHistory>;
+
+    fn handle(&mut self, msg: GetAssetHistory, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.asset_history(&msg.0))
+    }
+}
+
+impl Handler<GetBlock> for Chain {
+    type Result = MessageResult<GetBlock>;
+
+    fn handle(&mut self, msg: GetBlock, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_block(&msg.0).cloned())
+    }
+}
+
+impl Handler<GetBlockBySerial> for Chain {
+    type Result = MessageResult<GetBlockBySerial>;
+
+    fn handle(&mut self, msg: GetBlockBySerial, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_block_by_serial(msg.0).cloned())
+    }
+}
+
+impl Handler<QueryBlocks> for Chain {
+    type Result = MessageResult<QueryBlocks>;
+
+    fn handle(&mut self, msg: QueryBlocks, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.query(&msg.0))
+    }
+}
+
+impl Handler<GetBalance> for Chain {
+    type Result = MessageResult<GetBalance>;
+
+    fn handle(&mut self, msg: GetBalance, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_balance(&msg.0))
+    }
+}
+
+impl Handler<GetMinted> for Chain {
+    type Result = MessageResult<GetMinted>;
+
+    fn handle(&mut self, _msg: GetMinted, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.minted())
+    }
+}
+
+impl Handler<HasEvidence> for Chain {
+    type Result = MessageResult<HasEvidence>;
+
+    fn handle(&mut self, msg: HasEvidence, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.has_evidence(&msg.0))
+    }
+}
+
+impl Handler<Finalize> for Chain {
+    type Result = MessageResult<Finalize>;
+
+    fn handle(&mut self, msg: Finalize, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.finalize(&msg.0))
+    }
+}
+
+impl Handler<RemoveTip> for Chain {
+    type Result = MessageResult<RemoveTip>;
+
+    fn handle(&mut self, _msg: RemoveTip, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.remove_tip())
+    }
+}
+
+impl Handler<GetFinalized> for Chain {
+    type Result = MessageResult<GetFinalized>;
+
+    fn handle(&mut self, _msg: GetFinalized, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_finalized().to_owned())
+    }
+}
+
+impl Handler<GetBalances> for Chain {
+    type Result = MessageResult<GetBalances>;
+
+    fn handle(&mut self, _msg: GetBalances, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.balances())
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -246,6 +852,165 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chain_query_works() {
+        let mut chain = Chain::new("test chain");
+        let network_size = 3;
+        let sales = [
+            ("attacker", "victim", "attacker"),
+            ("victim", "normal", "normal"),
+            ("attacker", "normal", "attacker"),
+            ("normal", "attacker", "victim"),
+        ];
+        for (tx, rx, validator) in sales.iter() {
+            let block = BlockBuilder::default()
+                .set_tx(tx)
+                .set_rx(rx)
+                .set_validator(validator)
+                .set_asset_id("asset")
+                .set_prev(chain.get_last_block())
+                .build();
+            chain.add_block(block, network_size).unwrap();
+        }
+
+        let tip = chain.get_last_block().clone();
+        assert_eq!(
+            chain.get_block(tip.get_hash()).unwrap().get_hash(),
+            tip.get_hash(),
+            "get by hash"
+        );
+        assert_eq!(
+            chain.get_block_by_serial(7).unwrap().get_hash(),
+            tip.get_hash(),
+            "get by serial"
+        );
+        assert!(chain.get_block("nonexistent").is_none());
+        assert!(chain.get_block_by_serial(1).is_none(), "serials are offset");
+        assert_eq!(chain.get_nonce("attacker"), 2);
+        assert_eq!(chain.asset_history("asset").len(), 4);
+
+        let all = chain.query(&BlockQuery::default()).unwrap();
+        assert_eq!(all.total, 5, "genesis included");
+
+        let range = BlockQuery {
+            from: Some(5),
+            to: Some(6),
+            ..Default::default()
+        };
+        let page = chain.query(&range).unwrap();
+        let serials: Vec<_> = page.blocks.iter().map(|b| b.get_serial_no()).collect();
+        assert_eq!(serials, vec![Some(5), Some(6)], "range query works");
+
+        let attacker = BlockQuery {
+            tx: Some("attacker".into()),
+            validator: Some("attacker".into()),
+            ..Default::default()
+        };
+        assert_eq!(chain.query(&attacker).unwrap().total, 2, "filters combine");
+        let attacker_to_normal = BlockQuery {
+            rx: Some("normal".into()),
+            ..attacker.clone()
+        };
+        let page = chain.query(&attacker_to_normal).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.blocks[0].get_serial_no(), Some(6));
+        let unknown = BlockQuery {
+            validator: Some("auditor".into()),
+            ..Default::default()
+        };
+        assert_eq!(chain.query(&unknown).unwrap().total, 0);
+
+        let paginated = BlockQuery {
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = chain.query(&paginated).unwrap();
+        assert_eq!((page.total, page.blocks.len()), (5, 2), "pagination works");
+        assert_eq!(page.blocks[0].get_serial_no(), Some(4));
+
+        let invalid = BlockQuery {
+            from: Some(6),
+            to: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(chain.query(&invalid).unwrap_err(), QueryError::InvalidRange);
+
+        // indexes are rebuilt when blocks are replaced
+        let mut blocks = chain.blocks();
+        blocks.truncate(2);
+        chain.replace_chain(blocks).unwrap();
+        assert!(chain.get_block(tip.get_hash()).is_none(), "reindex works");
+        assert_eq!(chain.get_nonce("attacker"), 1);
+    }
+
+    #[test]
+    fn balances_work() {
+        let mut chain = Chain::new("test chain");
+        let mut add = |tx: &str, rx: &str, kind: TxKind| {
+            let block = BlockBuilder::default()
+                .set_tx(tx)
+                .set_rx(rx)
+                .set_validator("validator")
+                .set_asset_id("asset")
+                .set_kind(kind)
+                .set_prev(chain.get_last_block())
+                .build();
+            chain.add_block(block, 3).unwrap();
+        };
+        add("auditor", "victim", TxKind::Mint { amount: 100 });
+        add("victim", "normal", TxKind::Transfer { amount: 30, fee: 2 });
+        add("attacker", "normal", TxKind::Sale { price: 20, fee: 1 });
+        add("attacker", "victim", TxKind::Asset);
+        let mint = TxKind::MintAsset {
+            name: "Dubai".into(),
+            value: 50,
+        };
+        add("auditor", "victim", mint);
+
+        assert_eq!(chain.minted(), 50, "minted assets are counted");
+        assert_eq!(chain.get_balance("victim"), 68);
+        assert_eq!(chain.get_balance("normal"), 9, "buyer pays price and fee");
+        assert_eq!(chain.get_balance("attacker"), 20, "seller is paid");
+        assert_eq!(chain.get_balance("validator"), 3, "fees go to validator");
+        assert_eq!(chain.get_balance("auditor"), 0, "minting is free");
+        assert_eq!(chain.balances().len(), 4);
+
+        // balances follow the chain
+        let mut blocks = chain.blocks();
+        blocks.truncate(3);
+        chain.replace_chain(blocks).unwrap();
+        assert_eq!(chain.get_balance("victim"), 68);
+        assert_eq!(chain.get_balance("attacker"), 0, "sale undone");
+        assert_eq!(chain.minted(), 0);
+
+        // token amounts that overflow are rejected
+        for kind in [
+            TxKind::Mint { amount: u64::MAX },
+            TxKind::Transfer {
+                amount: u64::MAX,
+                fee: 1,
+            },
+        ]
+        .iter()
+        {
+            let block = BlockBuilder::default()
+                .set_tx("auditor")
+                .set_rx("victim")
+                .set_validator("validator")
+                .set_kind(kind.clone())
+                .set_prev(chain.get_last_block())
+                .build();
+            assert_eq!(
+                chain.add_block(block.clone(), 3),
+                Err(ChainError::TokenOverflow)
+            );
+            let mut blocks = chain.blocks();
+            blocks.push(block);
+            assert_eq!(Chain::is_valid(&blocks), Err(ChainError::TokenOverflow));
+        }
+    }
+
     #[actix_rt::test]
     async fn chain_actor_works() {
         use crate::asset::AssetLedger;
@@ -288,6 +1053,26 @@ mod tests {
             "add_block works"
         );
 
+        // checks if asset history works
+        let history = chain_addr
+            .send(GetAssetHistory(asset.get_hash().into()))
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1, "asset history works");
+        assert_eq!(history[0].block, block.get_hash());
+        assert_eq!(history[0].from, "Me");
+        assert_eq!(history[0].to, "You");
+        assert_eq!(history[0].serial_no, Some(network_size + 1));
+
+        // checks if nonce and chain ID work
+        assert_eq!(chain_addr.send(GetNonce("Me".into())).await.unwrap(), 1);
+        assert_eq!(chain_addr.send(GetNonce("You".into())).await.unwrap(), 0);
+        assert_eq!(
+            chain_addr.send(GetChainId).await.unwrap(),
+            prev.get_hash(),
+            "chain ID is genesis hash"
+        );
+
         // checks if invalid block, where block.get_prev() != chain.get_last_block().get_hash()
         // can be added to chain
         assert_eq!(
@@ -389,4 +1174,146 @@ mod tests {
             "Invalild blockchain replace test"
         );
     }
+
+    fn extend(chain: &mut Chain, tx: &str, rx: &str, length: usize) {
+        for _ in 0..length {
+            let block = BlockBuilder::default()
+                .set_tx(tx)
+                .set_rx(rx)
+                .set_prev(chain.get_last_block())
+                .set_asset_id("asset")
+                .build();
+            chain.add_block(block, 3).unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn reorg_works() {
+        let mut chain = Chain::new("test chain");
+        extend(&mut chain, "Me", "You", 3);
+
+        // alternative history from the first block after genesis
+        let mut alternative = chain.clone();
+        alternative.blocks.truncate(2);
+        extend(&mut alternative, "Me", "Me", 3);
+
+        assert_eq!(Chain::fork_point(&chain.blocks, &alternative.blocks), 2);
+        assert_eq!(
+            chain.clone().reorg(chain.blocks.clone(), None).err(),
+            Some(ChainError::ShorterChain),
+            "chain must be longer"
+        );
+        assert_eq!(
+            chain.clone().reorg(Chain::new("other").blocks, None).err(),
+            Some(ChainError::ShorterChain)
+        );
+
+        let mut other = Chain::new("other");
+        extend(&mut other, "Me", "You", 5);
+        assert_eq!(
+            chain.clone().reorg(other.blocks, None).err(),
+            Some(ChainError::InvalidBlockChain),
+            "chains must share genesis"
+        );
+
+        assert_eq!(
+            chain
+                .clone()
+                .reorg(alternative.blocks.clone(), Some(1))
+                .err(),
+            Some(ChainError::CheckpointViolation),
+            "checkpointed blocks can't be rewritten"
+        );
+
+        let chain_addr = chain.clone().start();
+        let (fork_point, discarded) = chain_addr
+            .send(Reorg {
+                chain: alternative.blocks.clone(),
+                checkpoint: Some(3),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fork_point, 2);
+        assert_eq!(discarded.len(), 2, "blocks after fork point discarded");
+        assert_eq!(
+            chain_addr.send(GetLastBlock).await.unwrap().get_hash(),
+            alternative.get_last_block().get_hash(),
+            "reorg works"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn finality_works() {
+        let mut chain = Chain::new("test chain");
+        extend(&mut chain, "Me", "You", 3);
+        let mut alternative = chain.clone();
+        alternative.blocks.truncate(2);
+        extend(&mut alternative, "Me", "Me", 3);
+
+        let chain_addr = chain.clone().start();
+        assert!(chain_addr.send(GetFinalized).await.unwrap().is_genesis());
+        assert!(!chain_addr.send(Finalize("unknown".into())).await.unwrap());
+        let final_block = chain.blocks[2].clone();
+        assert!(chain_addr
+            .send(Finalize(final_block.get_hash().into()))
+            .await
+            .unwrap());
+        assert!(
+            !chain_addr
+                .send(Finalize(chain.blocks[1].get_hash().into()))
+                .await
+                .unwrap(),
+            "ancestors of final blocks are final"
+        );
+        assert_eq!(chain_addr.send(GetFinalized).await.unwrap(), final_block);
+
+        assert_eq!(
+            chain_addr
+                .send(Reorg {
+                    chain: alternative.blocks.clone(),
+                    checkpoint: None,
+                })
+                .await
+                .unwrap()
+                .err(),
+            Some(ChainError::FinalityViolation),
+            "final blocks can't be rewritten"
+        );
+        assert_eq!(
+            chain_addr
+                .send(ReplaceChain(alternative.blocks.clone()))
+                .await
+                .unwrap(),
+            Err(ChainError::FinalityViolation)
+        );
+
+        extend(&mut chain, "Me", "Me", 2);
+        chain_addr
+            .send(Reorg {
+                chain: chain.blocks.clone(),
+                checkpoint: None,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            chain_addr.send(GetFinalized).await.unwrap(),
+            final_block,
+            "chains that extend final blocks are adopted"
+        );
+
+        for block in chain.blocks[3..].iter().rev() {
+            assert_eq!(
+                chain_addr.send(RemoveTip).await.unwrap().as_ref(),
+                Ok(block)
+            );
+        }
+        assert_eq!(chain_addr.send(GetLastBlock).await.unwrap(), final_block);
+        assert_eq!(
+            chain_addr.send(RemoveTip).await.unwrap(),
+            Err(ChainError::FinalityViolation),
+            "final blocks can't be removed"
+        );
+    }
 }
@@ -17,29 +17,43 @@
 //! Client wrapper for p2p communication
 
 use actix_web::client::Client as awc;
+use actix_web::web;
 use serde::{Deserialize, Serialize};
 
-use crate::asset::{Asset, ReplaceLedger, Stake};
-use crate::block::Block;
+use crate::asset::{Asset, MergeLedger, Stake};
+use crate::block::{Block, Evidence};
 use crate::config::Config;
-use crate::discovery::AddPeer;
-use crate::payload::{Peer, Status, Tx, ValidateTx};
+use crate::discovery::{AddPeer, EnrollPolicy};
+use crate::finality::Vote;
+use crate::logs::Action;
+use crate::payload::{Enrollment, Offer, Peer, Status, Tx, ValidateTx};
+use crate::pbft::PbftMessage;
 use crate::utils::*;
+use crate::vrf::PublicKey;
 //use crate::logs::SellAsset;
 
 // NOTE these URLs are subject to change
 // if tests are failing, come check the URLs
 // here
 pub const PEER_ENROLL: &str = "/peers/enroll";
+pub const PEER_ENROLL_POLICY: &str = "/peers/enroll/policy";
 pub const PEER_DISCOVER_ALL: &str = "/peers/all";
+pub const PEER_SELF: &str = "/peers/self";
 pub const GET_ALL_ASSETS: &str = "/assets/all";
 pub const SELL_ASSET: &str = "/assets/sell";
+pub const OFFER: &str = "/assets/offer";
 pub const GET_STAKE: &str = "/stake";
 pub const SET_ATTACK: &str = "/attack";
 pub const GET_CHAIN: &str = "/chain/all";
 pub const ADD_BLOCK: &str = "/chain/add";
+pub const REPLACE_CHAIN: &str = "/chain/replace";
 pub const SEND_VALIDATOR_TX: &str = "/block/validate";
 pub const STATE: &str = "/state";
+pub const EVIDENCE: &str = "/evidence";
+pub const VOTE: &str = "/finality/vote";
+pub const PBFT: &str = "/pbft";
+pub const PBFT_SENT: &str = "/pbft/sent";
+pub const LOTTERY_KEY: &str = "/lottery/key";
 
 /// Client wrapper for p2p communication
 #[derive(Clone, Default)]
@@ -61,13 +75,56 @@ impl Client {
             id: config.peer_id.clone(),
             ip: config.public_ip.clone(),
         };
+        if !self.enroll(config, peer).await {
+            config.info("Enrollment rejected by auditor");
+        }
+    }
+
+    /// gets enrollment policy from auditor node
+    pub async fn get_enroll_policy(&self, config: &Config) -> EnrollPolicy {
+        let addr = Client::make_uri(&config.auditor_node, PEER_ENROLL_POLICY);
+        loop {
+            if let Ok(mut val) = self.client.get(&addr).send().await {
+                if let Ok(policy) = val.json().await {
+                    return policy;
+                }
+            }
+        }
+    }
+
+    /// enrolls `peer` with the auditor node, solving proof of work
+    /// if the auditor requires it. Returns `false` when the auditor
+    /// rejects enrollment
+    pub async fn enroll(&self, config: &Config, peer: Peer) -> bool {
+        let policy = self.get_enroll_policy(config).await;
+        let nonce = match policy.pow_difficulty {
+            Some(difficulty) => {
+                config.debug(&format!(
+                    "Solving enrollment proof of work for {}",
+                    &peer.id
+                ));
+                let pow_peer = peer.clone();
+                match web::block(move || EnrollPolicy::solve_pow(&pow_peer, difficulty)).await {
+                    Ok(nonce) => nonce,
+                    Err(e) => {
+                        config.info(&format!("Can't solve enrollment proof of work: {}", e));
+                        return false;
+                    }
+                }
+            }
+            None => 0,
+        };
+
+        let payload = Enrollment { peer, nonce };
         let addr = Client::make_uri(&config.auditor_node, PEER_ENROLL);
-        self.client
+        let resp = self
+            .client
             .post(addr)
             .header("content-type", "application/json")
-            .send_json(&peer)
+            .send_json(&payload)
             .await
             .unwrap();
+        resp.status().is_success()
     }
 
     /// set attack
@@ -86,10 +143,20 @@ impl Client {
 
         let payload = PayloadGetStake {
             block_id: peer.block_id,
+            peer_id: Some(peer.peer_id.clone()),
         };
 
         let peer_addr = get_peer(&config, &peer.peer_id).await;
         let addr = Client::make_uri(&peer_addr.ip, GET_STAKE);
+
+        // unreachable peers can't stake
+        if self.inject_faults(config, &peer.peer_id, GET_STAKE).await == 0 {
+            return Stake {
+                block_id: peer.block_id,
+                stake: Vec::default(),
+            };
+        }
+
         loop {
             if let Ok(mut val) = self
                 .client
@@ -109,6 +176,43 @@ impl Client {
         format!("http://{}{}", address, path)
     }
 
+    /// make URI for ledger requests, includes requesting peer's ID
+    fn make_requester_uri(config: &Config, address: &str, path: &str) -> String {
+        format!(
+            "{}?requester={}",
+            Client::make_uri(address, path),
+            config.peer_id
+        )
+    }
+
+    /// applies faults configured on this peer to a message bound for
+    /// `route` on peer `peer_id`. Waits for injected latency and returns
+    /// the number of copies of the message that should be sent, 0 when
+    /// the message is dropped
+    async fn inject_faults(&self, config: &Config, peer_id: &str, route: &str) -> usize {
+        use crate::faults::{Inspect, Verdict};
+        use actix::clock::delay_for;
+        use std::time::Duration;
+
+        let msg = Inspect {
+            peer_id: peer_id.into(),
+            route: route.into(),
+        };
+        match config.fault_addr.send(msg).await.unwrap() {
+            Verdict::Drop => {
+                config.debug(&format!("Dropping message to {} on {}", peer_id, route));
+                0
+            }
+            Verdict::Deliver { delay, copies } => {
+                if delay > 0 {
+                    config.debug(&format!("Delaying message to {} by {}ms", peer_id, delay));
+                    delay_for(Duration::from_millis(delay)).await;
+                }
+                copies
+            }
+        }
+    }
+
     /// gets list of peers from auditor, should be called periodically
     pub async fn peer_discovery(&self, config: &Config) {
         // gets peers from Auditor and replaces peers
@@ -121,6 +225,9 @@ impl Client {
                 if let Ok(val) = peers {
                     for peer in val.iter() {
                         config.network_addr.send(AddPeer(peer.to_owned())).await;
+                        config
+                            .log_event(Action::PeerConnected(peer.to_owned()))
+                            .await;
                     }
                     break;
                 }
@@ -130,20 +237,16 @@ impl Client {
 
     /// gets asset ledger from auditor node, should be called periodically
     pub async fn get_all_assets(&self, config: &Config) {
-        // gets assets from Auditor and replaces assets
-        // in local AssetsLedger
-        let addr = Client::make_uri(&config.auditor_node, GET_ALL_ASSETS);
+        // gets assets from Auditor and merges them into
+        // local AssetsLedger
+        let addr = Client::make_requester_uri(config, &config.auditor_node, GET_ALL_ASSETS);
         loop {
             if let Ok(mut val) = self.client.get(&addr).send().await {
                 config.debug("Asset request success");
                 let peers: Result<Vec<Asset>, _> = val.json().await;
                 if let Ok(val) = peers {
                     config.debug("Asset deserialization success");
-                    config
-                        .get_asset_ledger()
-                        .await
-                        .send(ReplaceLedger(val))
-                        .await;
+                    config.get_asset_ledger().await.send(MergeLedger(val)).await;
                     break;
                 }
             }
@@ -151,18 +254,28 @@ impl Client {
     }
 
     /// send Tx request to validator
-    pub async fn send_tx_to_validator(&self, validator: &Peer, payload: &ValidateTx) {
+    pub async fn send_tx_to_validator(
+        &self,
+        config: &Config,
+        validator: &Peer,
+        payload: &ValidateTx,
+    ) {
         let addr = Client::make_uri(&validator.ip, SEND_VALIDATOR_TX);
 
-        loop {
-            if let Ok(_) = self
-                .client
-                .post(&addr)
-                .header("content-type", "application/json")
-                .send_json(&payload)
-                .await
-            {
-                break;
+        for _ in 0..self
+            .inject_faults(config, &validator.id, SEND_VALIDATOR_TX)
+            .await
+        {
+            loop {
+                if let Ok(_) = self
+                    .client
+                    .post(&addr)
+                    .header("content-type", "application/json")
+                    .send_json(&payload)
+                    .await
+                {
+                    break;
+                }
             }
         }
     }
@@ -172,22 +285,68 @@ impl Client {
         let seller = get_peer(&config, seller_id).await;
         let addr = Client::make_uri(&seller.ip, SELL_ASSET);
 
+        for _ in 0..self.inject_faults(config, seller_id, SELL_ASSET).await {
+            loop {
+                if let Ok(_) = self
+                    .client
+                    .post(&addr)
+                    .header("content-type", "application/json")
+                    .send_json(&payload)
+                    .await
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// send offer to buy an asset to its owner, returns true if
+    /// the owner accepted it
+    pub async fn send_offer(&self, config: &Config, owner: &Peer, payload: &Offer) -> bool {
+        let addr = Client::make_uri(&owner.ip, OFFER);
+        if self.inject_faults(config, &owner.id, OFFER).await == 0 {
+            return false;
+        }
         loop {
-            if let Ok(_) = self
+            if let Ok(resp) = self
                 .client
                 .post(&addr)
                 .header("content-type", "application/json")
                 .send_json(&payload)
                 .await
             {
-                break;
+                return resp.status().is_success();
+            }
+        }
+    }
+
+    /// get VRF public key of a peer
+    pub async fn get_lottery_key(&self, peer: &Peer) -> PublicKey {
+        let addr = Client::make_uri(&peer.ip, LOTTERY_KEY);
+        loop {
+            if let Ok(mut val) = self.client.get(&addr).send().await {
+                if let Ok(key) = val.json().await {
+                    return key;
+                }
+            }
+        }
+    }
+
+    /// get ID and address of the peer at `address`
+    pub async fn get_identity(&self, address: &str) -> Peer {
+        let addr = Client::make_uri(address, PEER_SELF);
+        loop {
+            if let Ok(mut val) = self.client.get(&addr).send().await {
+                if let Ok(peer) = val.json().await {
+                    return peer;
+                }
             }
         }
     }
 
     /// Get chain dump
     pub async fn get_chain(&self, config: &Config, peer_ip: &str) -> Vec<Block> {
-        let addr = Client::make_uri(&peer_ip, GET_CHAIN);
+        let addr = Client::make_requester_uri(config, &peer_ip, GET_CHAIN);
 
         loop {
             if let Ok(mut val) = self.client.get(&addr).send().await {
@@ -216,23 +375,38 @@ impl Client {
         }
     }
 
-    /// gets asset ledger from auditor node, should be called periodically
-    pub async fn get_peer_assets(&self, config: &Config, peer: &Peer) {
-        // gets assets from Auditor and replaces assets
-        // in local AssetsLedger
+    /// gets list of peers from a peer, returns `None` when
+    /// peer is unreachable
+    pub async fn peer_gossip(&self, config: &Config, peer: &Peer) -> Option<Vec<Peer>> {
+        let addr = Client::make_requester_uri(config, &peer.ip, PEER_DISCOVER_ALL);
+        if self
+            .inject_faults(config, &peer.id, PEER_DISCOVER_ALL)
+            .await
+            == 0
+        {
+            return None;
+        }
 
-        let addr = Client::make_uri(&peer.ip, GET_ALL_ASSETS);
+        if let Ok(mut val) = self.client.get(&addr).send().await {
+            if let Ok(peers) = val.json().await {
+                return Some(peers);
+            }
+        }
+        None
+    }
+
+    /// gets asset ledger from a peer and adds assets that aren't in the
+    /// local ledger, like assets distributed to newly enrolled peers.
+    /// Ownership of known assets only changes through blocks
+    pub async fn get_peer_assets(&self, config: &Config, peer: &Peer) {
+        let addr = Client::make_requester_uri(config, &peer.ip, GET_ALL_ASSETS);
         loop {
             if let Ok(mut val) = self.client.get(&addr).send().await {
                 config.debug("Asset request success");
                 let peers: Result<Vec<Asset>, _> = val.json().await;
                 if let Ok(val) = peers {
                     config.debug("Asset deserialization success");
-                    config
-                        .get_asset_ledger()
-                        .await
-                        .send(ReplaceLedger(val))
-                        .await;
+                    config.get_asset_ledger().await.send(MergeLedger(val)).await;
                     break;
                 }
             }
@@ -243,15 +417,126 @@ impl Client {
     pub async fn send_block_to_peer(&self, config: &Config, peer: &Peer, payload: &Block) {
         let peer_addr = get_peer(&config, &peer.id).await;
         let addr = Client::make_uri(&peer_addr.ip, ADD_BLOCK);
+        for _ in 0..self.inject_faults(config, &peer.id, ADD_BLOCK).await {
+            loop {
+                if let Ok(_) = self
+                    .client
+                    .post(&addr)
+                    .header("content-type", "application/json")
+                    .send_json(&payload)
+                    .await
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// send chain to peer, peer replaces its chain if the
+    /// chain is longer
+    pub async fn send_chain_to_peer(&self, config: &Config, peer: &Peer, payload: &[Block]) {
+        let addr = Client::make_uri(&peer.ip, REPLACE_CHAIN);
+        for _ in 0..self.inject_faults(config, &peer.id, REPLACE_CHAIN).await {
+            loop {
+                if self
+                    .client
+                    .post(&addr)
+                    .header("content-type", "application/json")
+                    .send_json(&payload)
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// send equivocation evidence to peer
+    pub async fn send_evidence(&self, config: &Config, peer: &Peer, payload: &Evidence) {
+        let addr = Client::make_uri(&peer.ip, EVIDENCE);
+        for _ in 0..self.inject_faults(config, &peer.id, EVIDENCE).await {
+            loop {
+                if self
+                    .client
+                    .post(&addr)
+                    .header("content-type", "application/json")
+                    .send_json(&payload)
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// send finality vote to peer
+    pub async fn send_vote(&self, config: &Config, peer: &Peer, payload: &Vote) {
+        let addr = Client::make_uri(&peer.ip, VOTE);
+        for _ in 0..self.inject_faults(config, &peer.id, VOTE).await {
+            loop {
+                if self
+                    .client
+                    .post(&addr)
+                    .header("content-type", "application/json")
+                    .send_json(&payload)
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// get vote of a peer for serial number `serial_no`, `None` when
+    /// the peer is unreachable or didn't vote
+    pub async fn get_vote(&self, config: &Config, peer: &Peer, serial_no: usize) -> Option<Vote> {
+        let addr = Client::make_uri(&peer.ip, &format!("{}/{}", VOTE, serial_no));
+        if self.inject_faults(config, &peer.id, VOTE).await == 0 {
+            return None;
+        }
         loop {
-            if let Ok(_) = self
-                .client
-                .post(&addr)
-                .header("content-type", "application/json")
-                .send_json(&payload)
-                .await
-            {
-                break;
+            if let Ok(mut val) = self.client.get(&addr).send().await {
+                if let Ok(vote) = val.json().await {
+                    return vote;
+                }
+            }
+        }
+    }
+
+    /// send PBFT message to replica
+    pub async fn send_pbft(&self, config: &Config, peer: &Peer, payload: &PbftMessage) {
+        let addr = Client::make_uri(&peer.ip, PBFT);
+        for _ in 0..self.inject_faults(config, &peer.id, PBFT).await {
+            loop {
+                if self
+                    .client
+                    .post(&addr)
+                    .header("content-type", "application/json")
+                    .send_json(&payload)
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// check if replica `peer` sent PBFT message `payload`, `false` when
+    /// the replica is unreachable
+    pub async fn has_sent_pbft(&self, config: &Config, peer: &Peer, payload: &PbftMessage) -> bool {
+        let addr = Client::make_uri(&peer.ip, &format!("{}/{}", PBFT_SENT, payload.digest()));
+        if self.inject_faults(config, &peer.id, PBFT_SENT).await == 0 {
+            return false;
+        }
+        loop {
+            if let Ok(mut val) = self.client.get(&addr).send().await {
+                if let Ok(sent) = val.json().await {
+                    return sent;
+                }
             }
         }
     }
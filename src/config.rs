@@ -16,10 +16,19 @@
 */
 use actix::prelude::*;
 use log::{debug, info};
+use serde::{Deserialize, Serialize};
 
-use crate::asset::AssetLedger;
+use crate::asset::{AssetLedger, LedgerModel, SupplyPolicy, DEFAULT_UNBONDING_PERIOD};
+use crate::attack::Attack;
 use crate::chain::Chain;
-use crate::discovery::Network;
+use crate::detector::AlertLog;
+use crate::discovery::{EnrollPolicy, Network, MAX_POW_DIFFICULTY};
+use crate::evidence::EvidenceLog;
+use crate::faults::FaultInjector;
+use crate::finality::Finality;
+use crate::logs::{Action, EventLog, LogEvent};
+use crate::pbft::{Pbft, DEFAULT_TIMEOUT};
+use crate::vrf::Lottery;
 use crate::Client;
 
 #[derive(Clone)]
@@ -33,11 +42,29 @@ pub struct Config {
     pub tampered_chain_addr: Addr<Chain>,
     pub tampered_asset_addr: Addr<AssetLedger>,
     pub network_addr: Addr<Network>,
+    pub fault_addr: Addr<FaultInjector>,
+    pub attack_addr: Addr<Attack>,
+    pub alert_addr: Addr<AlertLog>,
+    pub evidence_addr: Addr<EvidenceLog>,
+    pub finality_addr: Addr<Finality>,
+    pub pbft_addr: Addr<Pbft>,
+    pub lottery_addr: Addr<Lottery>,
+    pub event_addr: Addr<EventLog>,
+    pub enroll_policy: EnrollPolicy,
+    pub supply_policy: SupplyPolicy,
+    /// number of blocks an unbonded asset stays locked
+    pub unbonding_period: usize,
+    pub consensus: Consensus,
+    /// milliseconds PBFT replicas wait for a request to be executed
+    /// before changing view
+    pub pbft_timeout: u64,
+    pub replay_protection: bool,
+    pub checkpoint: Option<usize>,
     pub init_network_size: usize,
     pub remote_server: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub enum Mode {
     Auditor,
     /// set Attacker = true when
@@ -54,6 +81,19 @@ pub enum Mode {
     Normal,
 }
 
+/// Protocol peers use to agree on blocks
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Consensus {
+    /// validator chosen by coinage of stake, see
+    /// [AssetLedger::choose_validator](crate::asset::AssetLedger::choose_validator)
+    #[default]
+    Stake,
+    /// peers and auditor order transactions with PBFT, see [crate::pbft]
+    Pbft,
+    /// validator chosen by a stake weighted VRF lottery, see [crate::vrf]
+    Vrf,
+}
+
 impl Config {
     #[cfg(not(tarpaulin_include))]
     pub fn new() -> Self {
@@ -91,6 +131,21 @@ impl Config {
             tampered_asset_addr: self.tampered_asset_addr.clone(),
             chain_addr: self.tampered_chain_addr.clone(),
             network_addr: self.network_addr.clone(),
+            fault_addr: self.fault_addr.clone(),
+            attack_addr: self.attack_addr.clone(),
+            alert_addr: self.alert_addr.clone(),
+            evidence_addr: self.evidence_addr.clone(),
+            finality_addr: self.finality_addr.clone(),
+            pbft_addr: self.pbft_addr.clone(),
+            lottery_addr: self.lottery_addr.clone(),
+            event_addr: self.event_addr.clone(),
+            enroll_policy: self.enroll_policy.clone(),
+            supply_policy: self.supply_policy.clone(),
+            unbonding_period: self.unbonding_period,
+            consensus: self.consensus,
+            pbft_timeout: self.pbft_timeout,
+            replay_protection: self.replay_protection,
+            checkpoint: self.checkpoint,
             init_network_size: self.init_network_size,
             auditor_node: self.auditor_node.clone(),
             public_ip: self.public_ip.clone(),
@@ -104,10 +159,20 @@ impl Config {
         info!("[{}]: {}", &self.peer_id, msg);
     }
 
+    /// record event in journal
+    pub async fn log_event(&self, action: Action) {
+        self.event_addr.send(LogEvent(action)).await.unwrap();
+    }
+
     #[cfg(not(tarpaulin_include))]
     pub async fn bootstrap(&self) {
+        use crate::asset::DumpLedger;
         use crate::chain::ReplaceChain;
-        if self.mode_addr.send(GetMode).await.unwrap() != Mode::Auditor {
+        if self.mode_addr.send(GetMode).await.unwrap() == Mode::Auditor {
+            for asset in self.asset_addr.send(DumpLedger).await.unwrap() {
+                self.log_event(Action::MintingAsset(asset)).await;
+            }
+        } else {
             self.info("Bootstrapping node");
             let client = Client::default();
             self.info("Enrolling peer");
@@ -175,6 +240,69 @@ impl Config {
                     .long("--remote-server")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("enroll_address_limit")
+                    .help("maximum number of peers that can enroll from an address")
+                    .long("--enroll-address-limit")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("enroll_pow")
+                    .help("proof of work difficulty for enrollment, at most 6")
+                    .long("--enroll-pow")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("enroll_stake_gated")
+                    .help("don't give assets to peers enrolling from an address that has stake")
+                    .long("--enroll-stake-gated"),
+            )
+            .arg(
+                Arg::with_name("replay_protection")
+                    .help("reject transactions without valid nonce and chain ID")
+                    .long("--replay-protection"),
+            )
+            .arg(
+                Arg::with_name("checkpoint")
+                    .help("depth beyond which blocks can't be rewritten by reorgs")
+                    .long("--checkpoint")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("utxo")
+                    .help("track asset ownership with unspent outputs and reject spent outputs")
+                    .long("--utxo"),
+            )
+            .arg(
+                Arg::with_name("supply_cap")
+                    .help("maximum total value of assets, enforced when the auditor mints assets")
+                    .long("--supply-cap")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("issuance_rate")
+                    .help("maximum value of assets the auditor can mint per block")
+                    .long("--issuance-rate")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("unbonding_period")
+                    .help("number of blocks an unbonded asset stays locked")
+                    .long("--unbonding-period")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("consensus")
+                    .help("available consensus protocols:\n\tstake\n\tpbft\n\tvrf ")
+                    .long("--consensus")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("pbft_timeout")
+                    .help("milliseconds PBFT replicas wait for a transaction before changing view")
+                    .long("--pbft-timeout")
+                    .takes_value(true),
+            )
             .get_matches();
 
         let peer_id = matches.value_of("peer_id").expect("Set peer ID");
@@ -185,8 +313,23 @@ impl Config {
         let chain_addr = Chain::new("Legit").start();
         let tampered_chain_addr = Chain::new("Tampered").start();
         let network_addr = Network::default().start();
-
-        let tampered_asset_addr = AssetLedger::new("tampered_asset_addr").start();
+        let fault_addr = FaultInjector::default().start();
+        let attack_addr = Attack::default().start();
+        let alert_addr = AlertLog::default().start();
+        let evidence_addr = EvidenceLog::default().start();
+        let finality_addr = Finality::default().start();
+        let pbft_addr = Pbft::default().start();
+        let lottery_addr = Lottery::default().start();
+        let event_addr = EventLog::default().start();
+
+        let model = if matches.is_present("utxo") {
+            LedgerModel::Utxo
+        } else {
+            LedgerModel::Account
+        };
+        let mut tampered_asset_ledger = AssetLedger::new("tampered_asset_addr");
+        tampered_asset_ledger.model = model;
+        let tampered_asset_addr = tampered_asset_ledger.start();
 
         let init_network_size: usize = matches
             .value_of("network_size")
@@ -196,6 +339,61 @@ impl Config {
 
         let remote_server: Option<String> = matches.value_of("remote_server").map(String::from);
 
+        let enroll_policy = EnrollPolicy {
+            address_limit: matches
+                .value_of("enroll_address_limit")
+                .map(|limit| limit.parse().expect("set valid enrollment address limit")),
+            pow_difficulty: matches.value_of("enroll_pow").map(|difficulty| {
+                let difficulty = difficulty.parse().expect("set valid enrollment difficulty");
+                if difficulty > MAX_POW_DIFFICULTY {
+                    panic!(
+                        "enrollment difficulty can't be above {}",
+                        MAX_POW_DIFFICULTY
+                    );
+                }
+                difficulty
+            }),
+            stake_gated: matches.is_present("enroll_stake_gated"),
+        };
+
+        let supply_policy = SupplyPolicy {
+            cap: matches
+                .value_of("supply_cap")
+                .map(|cap| cap.parse().expect("set valid supply cap")),
+            issuance: matches
+                .value_of("issuance_rate")
+                .map(|rate| rate.parse().expect("set valid issuance rate")),
+        };
+
+        let unbonding_period: usize = matches
+            .value_of("unbonding_period")
+            .map(|period| period.parse().expect("set valid unbonding period"))
+            .unwrap_or(DEFAULT_UNBONDING_PERIOD);
+
+        let consensus = match matches
+            .value_of("consensus")
+            .unwrap_or("stake")
+            .trim()
+            .to_lowercase()
+            .as_ref()
+        {
+            "stake" => Consensus::Stake,
+            "pbft" => Consensus::Pbft,
+            "vrf" => Consensus::Vrf,
+            _ => panic!("Enter valid consensus protocol"),
+        };
+
+        let pbft_timeout: u64 = matches
+            .value_of("pbft_timeout")
+            .map(|timeout| timeout.parse().expect("set valid PBFT timeout"))
+            .unwrap_or(DEFAULT_TIMEOUT);
+
+        let replay_protection = matches.is_present("replay_protection");
+
+        let checkpoint: Option<usize> = matches
+            .value_of("checkpoint")
+            .map(|depth| depth.parse().expect("set valid checkpoint depth"));
+
         let auditor_node = matches
             .value_of("auditor")
             .expect("Set auditor node")
@@ -224,6 +422,7 @@ impl Config {
             _ => panic!("Enter valid peer mode"),
         };
 
+        asset_leger.model = model;
         let mode_addr = ModeActor::new(mode).start();
         Config {
             peer_id: peer_id.into(),
@@ -234,6 +433,21 @@ impl Config {
             tampered_asset_addr,
             chain_addr,
             network_addr,
+            fault_addr,
+            attack_addr,
+            alert_addr,
+            evidence_addr,
+            finality_addr,
+            pbft_addr,
+            lottery_addr,
+            event_addr,
+            enroll_policy,
+            supply_policy,
+            unbonding_period,
+            consensus,
+            pbft_timeout,
+            replay_protection,
+            checkpoint,
             init_network_size,
             auditor_node: auditor_node.into(),
             public_ip: public_ip.into(),
@@ -255,8 +469,9 @@ impl Config {
 //    fn handle(&mut self, _msg: Sync, ctx: &mut Self::Context) -> Self::Result {
 impl Config {
     pub async fn sync(&self) {
-        use crate::chain::{DumpLedger as ChainDump, ReplaceChain};
+        use crate::chain::Reorg;
         use crate::discovery::{DumpPeer, ReplacePeerLedger};
+        use crate::utils::reorg_assets;
         use actix::clock::delay_for;
         use std::time::Duration;
         let duration = Duration::from_millis(1000);
@@ -282,8 +497,70 @@ impl Config {
                         .await
                         .unwrap();
                     self.debug("Refreshing asset ledger");
-                    client.get_peer_assets(&self, peer).await;
+                    client.get_all_assets(self).await;
                 }
+
+                // peers also gossip their peer ledgers. Not verifying them
+                // lets an attacker eclipse peers by poisoning responses
+                if let Some(peers_gossip) = client.peer_gossip(self, peer).await {
+                    let current_peers = self.network_addr.send(DumpPeer).await.unwrap();
+                    if current_peers.len() < peers_gossip.len() {
+                        self.debug(&format!("Refreshing peer ledger from {}", &peer.id));
+                        self.network_addr
+                            .send(ReplacePeerLedger(peers_gossip))
+                            .await
+                            .unwrap();
+                        self.debug("Refreshing asset ledger");
+                        client.get_peer_assets(self, peer).await;
+                        self.debug("Refreshing chain");
+                        let chain = client.get_chain(self, &peer.ip).await;
+                        match self
+                            .chain_addr
+                            .send(Reorg {
+                                chain: chain.clone(),
+                                checkpoint: self.checkpoint,
+                            })
+                            .await
+                            .unwrap()
+                        {
+                            Ok((fork_point, discarded)) => {
+                                reorg_assets(&self.asset_addr, &discarded, &chain[fork_point..])
+                                    .await
+                            }
+                            Err(e) => self.debug(&format!("Chain refresh rejected: {}", e)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Config {
+    /// runs the double-spend detector every
+    /// [DETECTOR_INTERVAL](crate::detector::DETECTOR_INTERVAL) milliseconds
+    /// and logs new alerts, only the auditor runs the detector
+    pub async fn detect(&self) {
+        use crate::detector::{detect, UpdateAlerts, DETECTOR_INTERVAL};
+        use crate::utils::state;
+        use actix::clock::delay_for;
+        use std::time::Duration;
+
+        if self.mode_addr.send(GetMode).await.unwrap() != Mode::Auditor {
+            return;
+        }
+
+        let client = Client::default();
+        loop {
+            delay_for(Duration::from_millis(DETECTOR_INTERVAL)).await;
+            let state = state(self, &client).await;
+            let new_alerts = self
+                .alert_addr
+                .send(UpdateAlerts(detect(&state)))
+                .await
+                .unwrap();
+            for alert in new_alerts.iter() {
+                log::warn!("[{}]: ALERT {}", &self.peer_id, alert);
             }
         }
     }
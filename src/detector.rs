@@ -0,0 +1,273 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Double-spend detection. [detect] compares [Status] snapshots
+//! of all peers in the network and raises [Alert]s when they are
+//! inconsistent. Alerts are recorded with the [AlertLog] actor.
+//! The auditor runs the detector every [DETECTOR_INTERVAL]
+//! milliseconds, see [Config::detect](crate::Config::detect).
+//!
+//! # [AlertLog] actor supports the following messages:
+//! - [UpdateAlerts]: Record current alerts, returns alerts that weren't seen before
+//! - [DumpAlerts]: Get all current alerts
+
+use std::collections::BTreeMap;
+
+use actix::prelude::*;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+use crate::payload::Status;
+
+/// time between detector runs on the auditor, in milliseconds
+pub const DETECTOR_INTERVAL: u64 = 5000;
+
+/// A value, like asset owner or block hash, as seen by a peer
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub peer_id: String,
+    pub value: Option<String>,
+}
+
+/// Inconsistency between peers
+#[derive(Deserialize, Display, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Alert {
+    /// peers disagree on the owner of an asset
+    #[display(
+        fmt = "Asset {} has conflicting owners, possible double spend",
+        asset_id
+    )]
+    OwnershipConflict {
+        asset_id: String,
+        owners: Vec<Observation>,
+    },
+    /// peers have different blocks at the same serial number
+    #[display(fmt = "Conflicting blocks at serial number {}", serial_no)]
+    BlockConflict {
+        serial_no: usize,
+        hashes: Vec<Observation>,
+    },
+    /// peers have different last blocks
+    #[display(fmt = "Peers have divergent tips")]
+    DivergentTips { tips: Vec<Observation> },
+}
+
+/// returns `observations` if peers disagree
+fn conflict(observations: Vec<Observation>) -> Option<Vec<Observation>> {
+    let first = &observations.first()?.value;
+    if observations.iter().any(|seen| &seen.value != first) {
+        Some(observations)
+    } else {
+        None
+    }
+}
+
+/// adds `observation` to the observations of its key, only the first
+/// value that a peer has for a key is kept
+fn record(seen: &mut Vec<Observation>, observation: Observation) {
+    if seen.last().map(|last| &last.peer_id) != Some(&observation.peer_id) {
+        seen.push(observation);
+    }
+}
+
+/// compares snapshots of peers and returns inconsistencies. Owners
+/// and blocks are indexed by asset ID and serial number in one pass
+/// over the snapshots
+pub fn detect(states: &[Status]) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+
+    let mut owners: BTreeMap<&str, Vec<Observation>> = BTreeMap::new();
+    let mut hashes: BTreeMap<usize, Vec<Observation>> = BTreeMap::new();
+    for state in states.iter() {
+        for asset in state.asset.iter() {
+            let observation = Observation {
+                peer_id: state.peer_id.clone(),
+                value: asset.get_owner().clone(),
+            };
+            record(owners.entry(asset.get_hash()).or_default(), observation);
+        }
+        for block in state.chain.iter() {
+            if let Some(serial_no) = block.get_serial_no() {
+                let observation = Observation {
+                    peer_id: state.peer_id.clone(),
+                    value: Some(block.get_hash().into()),
+                };
+                record(hashes.entry(serial_no).or_default(), observation);
+            }
+        }
+    }
+
+    for (asset_id, owners) in owners.into_iter() {
+        if let Some(owners) = conflict(owners) {
+            alerts.push(Alert::OwnershipConflict {
+                asset_id: asset_id.into(),
+                owners,
+            });
+        }
+    }
+    for (serial_no, hashes) in hashes.into_iter() {
+        if let Some(hashes) = conflict(hashes) {
+            alerts.push(Alert::BlockConflict { serial_no, hashes });
+        }
+    }
+
+    let tips = states
+        .iter()
+        .map(|state| Observation {
+            peer_id: state.peer_id.clone(),
+            value: state.chain.last().map(|block| block.get_hash().into()),
+        })
+        .collect();
+    if let Some(tips) = conflict(tips) {
+        alerts.push(Alert::DivergentTips { tips });
+    }
+
+    alerts
+}
+
+/// Alerts raised by the detector
+#[derive(Deserialize, Default, Serialize, Clone, Debug)]
+pub struct AlertLog {
+    pub alerts: Vec<Alert>,
+}
+
+impl Actor for AlertLog {
+    type Context = Context<Self>;
+}
+
+/// Record alerts of the latest detector run. Alerts that weren't
+/// raised again are resolved and cleared. Returns alerts that weren't
+/// recorded before
+#[derive(Message)]
+#[rtype(result = "Vec<Alert>")]
+pub struct UpdateAlerts(pub Vec<Alert>);
+
+/// Get all current alerts
+#[derive(Message)]
+#[rtype(result = "Vec<Alert>")]
+pub struct DumpAlerts;
+
+impl Handler<UpdateAlerts> for AlertLog {
+    type Result = MessageResult<UpdateAlerts>;
+
+    fn handle(&mut self, msg: UpdateAlerts, _ctx: &mut Self::Context) -> Self::Result {
+        let new_alerts = msg
+            .0
+            .iter()
+            .filter(|alert| !self.alerts.contains(alert))
+            .cloned()
+            .collect();
+        self.alerts = msg.0;
+        MessageResult(new_alerts)
+    }
+}
+
+impl Handler<DumpAlerts> for AlertLog {
+    type Result = MessageResult<DumpAlerts>;
+
+    fn handle(&mut self, _msg: DumpAlerts, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.alerts.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::AssetLedger;
+    use crate::block::BlockBuilder;
+    use crate::chain::Chain;
+    use crate::payload::StatusBuilder;
+
+    fn status(peer_id: &str, chain: &Chain, assets: &AssetLedger) -> Status {
+        StatusBuilder::default()
+            .peer_id(peer_id.into())
+            .asset(assets.assets.clone())
+            .chain(chain.blocks())
+            .tampered_assets(None)
+            .tampered_chain(None)
+            .build()
+            .unwrap()
+    }
+
+    fn sell(chain: &mut Chain, assets: &mut AssetLedger, tx: &str, rx: &str) {
+        let asset = assets.assets.first_mut().unwrap();
+        let block = BlockBuilder::default()
+            .set_tx(tx)
+            .set_rx(rx)
+            .set_asset_id(asset.get_hash())
+            .set_validator(tx)
+            .set_prev(chain.get_last_block())
+            .build();
+        chain.add_block(block, 3).unwrap();
+        asset.set_owner(rx);
+    }
+
+    #[actix_rt::test]
+    async fn detector_works() {
+        let mut chain = Chain::new("test");
+        let mut assets = AssetLedger::generate("auditor");
+        assets
+            .assets
+            .iter_mut()
+            .for_each(|asset| asset.set_owner("attacker"));
+
+        let consistent = vec![
+            status("victim", &chain, &assets),
+            status("normal", &chain, &assets),
+        ];
+        assert!(detect(&consistent).is_empty(), "consistent peers");
+
+        // attacker sells the same asset to two peers
+        let (mut victim_chain, mut victim_assets) = (chain.clone(), assets.clone());
+        sell(&mut victim_chain, &mut victim_assets, "attacker", "victim");
+        sell(&mut chain, &mut assets, "attacker", "normal");
+        let double_spend = vec![
+            status("victim", &victim_chain, &victim_assets),
+            status("normal", &chain, &assets),
+        ];
+        let alerts = detect(&double_spend);
+        assert_eq!(alerts.len(), 3);
+        assert!(matches!(alerts[0], Alert::OwnershipConflict { .. }));
+        assert!(matches!(
+            alerts[1],
+            Alert::BlockConflict { serial_no: 4, .. }
+        ));
+        assert!(matches!(alerts[2], Alert::DivergentTips { .. }));
+
+        let alert_addr = AlertLog::default().start();
+        let new_alerts = alert_addr.send(UpdateAlerts(alerts.clone())).await.unwrap();
+        assert_eq!(new_alerts, alerts, "new alerts returned");
+        let new_alerts = alert_addr.send(UpdateAlerts(alerts.clone())).await.unwrap();
+        assert!(new_alerts.is_empty(), "alerts aren't repeated");
+        assert_eq!(alert_addr.send(DumpAlerts).await.unwrap(), alerts);
+
+        // victim catches up, only the ownership conflict is left
+        let remaining = alerts[..1].to_vec();
+        let new_alerts = alert_addr
+            .send(UpdateAlerts(remaining.clone()))
+            .await
+            .unwrap();
+        assert!(new_alerts.is_empty());
+        assert_eq!(
+            alert_addr.send(DumpAlerts).await.unwrap(),
+            remaining,
+            "resolved alerts are cleared"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Differences between ledgers. Chains are compared with [diff_chains],
+//! asset ledgers with [diff_assets] and [Status] snapshots with [diff_status].
+//! [diff_worldview] compares every pair of peers in the network.
+
+use serde::{Deserialize, Serialize};
+
+use crate::asset::Asset;
+use crate::block::Block;
+use crate::payload::Status;
+
+/// Difference between two chains
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChainDiff {
+    /// number of blocks that both chains start with
+    pub common_prefix: usize,
+    /// blocks of the left chain after the common prefix
+    pub left: Vec<Block>,
+    /// blocks of the right chain after the common prefix
+    pub right: Vec<Block>,
+}
+
+impl ChainDiff {
+    /// checks if chains are identical
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty() && self.right.is_empty()
+    }
+}
+
+/// Asset whose owner or last transaction differs between two ledgers,
+/// `None` when a ledger doesn't have the asset
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct AssetDiff {
+    pub asset_id: String,
+    pub left: Option<Asset>,
+    pub right: Option<Asset>,
+}
+
+/// Difference between two [Status] snapshots
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct StatusDiff {
+    /// peer ID of the left snapshot
+    pub left: String,
+    /// peer ID of the right snapshot
+    pub right: String,
+    pub chain: ChainDiff,
+    pub assets: Vec<AssetDiff>,
+}
+
+impl StatusDiff {
+    /// checks if snapshots are identical
+    pub fn is_empty(&self) -> bool {
+        self.chain.is_empty() && self.assets.is_empty()
+    }
+}
+
+/// compares chains block by block
+pub fn diff_chains(left: &[Block], right: &[Block]) -> ChainDiff {
+    let common_prefix = left
+        .iter()
+        .zip(right.iter())
+        .take_while(|(left, right)| left.get_hash() == right.get_hash())
+        .count();
+    ChainDiff {
+        common_prefix,
+        left: left[common_prefix..].to_vec(),
+        right: right[common_prefix..].to_vec(),
+    }
+}
+
+/// assets whose owner or last transaction differ
+pub fn diff_assets(left: &[Asset], right: &[Asset]) -> Vec<AssetDiff> {
+    let find = |assets: &[Asset], asset_id: &str| {
+        assets
+            .iter()
+            .find(|asset| asset.get_hash() == asset_id)
+            .cloned()
+    };
+
+    let mut asset_ids: Vec<&str> = left
+        .iter()
+        .chain(right.iter())
+        .map(|asset| asset.get_hash())
+        .collect();
+    asset_ids.sort_unstable();
+    asset_ids.dedup();
+
+    asset_ids
+        .into_iter()
+        .filter_map(|asset_id| {
+            let (left, right) = (find(left, asset_id), find(right, asset_id));
+            let same = match (&left, &right) {
+                (Some(left), Some(right)) => {
+                    left.get_owner() == right.get_owner()
+                        && left.get_last_transaction() == right.get_last_transaction()
+                }
+                _ => false,
+            };
+            if same {
+                None
+            } else {
+                Some(AssetDiff {
+                    asset_id: asset_id.into(),
+                    left,
+                    right,
+                })
+            }
+        })
+        .collect()
+}
+
+/// compares legit ledgers of two snapshots
+pub fn diff_status(left: &Status, right: &Status) -> StatusDiff {
+    StatusDiff {
+        left: left.peer_id.clone(),
+        right: right.peer_id.clone(),
+        chain: diff_chains(&left.chain, &right.chain),
+        assets: diff_assets(&left.asset, &right.asset),
+    }
+}
+
+/// compares tampered ledgers of a snapshot(left: legit, right: tampered),
+/// `None` if the peer doesn't maintain tampered ledgers
+pub fn diff_tampered(state: &Status) -> Option<StatusDiff> {
+    let tampered_chain = state.tampered_chain.as_ref()?;
+    let tampered_assets = state.tampered_assets.as_deref().unwrap_or(&[]);
+    Some(StatusDiff {
+        left: state.peer_id.clone(),
+        right: state.peer_id.clone(),
+        chain: diff_chains(&state.chain, tampered_chain),
+        assets: diff_assets(&state.asset, tampered_assets),
+    })
+}
+
+/// Differences in the network
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct WorldviewDiff {
+    /// every pair of peers
+    pub peers: Vec<StatusDiff>,
+    /// legit ledgers of peers compared with their tampered ledgers
+    pub tampered: Vec<StatusDiff>,
+}
+
+/// compares snapshots of every pair of peers and tampered ledgers
+/// of peers with their legit ledgers
+pub fn diff_worldview(states: &[Status]) -> WorldviewDiff {
+    let peers = states
+        .iter()
+        .enumerate()
+        .flat_map(|(i, left)| {
+            states[i + 1..]
+                .iter()
+                .map(move |right| diff_status(left, right))
+        })
+        .collect();
+    let tampered = states.iter().filter_map(diff_tampered).collect();
+    WorldviewDiff { peers, tampered }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::AssetLedger;
+    use crate::block::BlockBuilder;
+    use crate::chain::Chain;
+
+    fn sell(chain: &mut Chain, assets: &mut AssetLedger, tx: &str, rx: &str) {
+        let asset = assets.assets.first_mut().unwrap();
+        let block = BlockBuilder::default()
+            .set_tx(tx)
+            .set_rx(rx)
+            .set_asset_id(asset.get_hash())
+            .set_validator(tx)
+            .set_prev(chain.get_last_block())
+            .build();
+        chain.add_block(block, 3).unwrap();
+        asset.set_owner(rx);
+        asset.set_last_transaction(chain.blocks().len());
+    }
+
+    #[test]
+    fn diff_works() {
+        let mut chain = Chain::new("test");
+        let mut assets = AssetLedger::generate("attacker");
+        let same = diff_chains(&chain.blocks(), &chain.blocks());
+        assert!(same.is_empty(), "identical chains");
+        assert_eq!(same.common_prefix, chain.blocks().len());
+        assert!(diff_assets(&assets.assets, &assets.assets).is_empty());
+
+        let (mut victim_chain, mut victim_assets) = (chain.clone(), assets.clone());
+        sell(&mut victim_chain, &mut victim_assets, "attacker", "victim");
+        sell(&mut chain, &mut assets, "attacker", "normal");
+        sell(&mut chain, &mut assets, "normal", "auditor");
+
+        let diff = diff_chains(&victim_chain.blocks(), &chain.blocks());
+        assert_eq!(diff.common_prefix, victim_chain.blocks().len() - 1);
+        assert_eq!(diff.left.len(), 1, "divergent blocks of left chain");
+        assert_eq!(diff.right.len(), 2, "divergent blocks of right chain");
+
+        let diff = diff_assets(&victim_assets.assets, &assets.assets);
+        assert_eq!(diff.len(), 1, "only the sold asset differs");
+        assert_eq!(
+            diff[0].left.as_ref().unwrap().get_owner().as_deref(),
+            Some("victim")
+        );
+        assert_eq!(
+            diff[0].right.as_ref().unwrap().get_owner().as_deref(),
+            Some("auditor")
+        );
+
+        let diff = diff_assets(&assets.assets[1..], &assets.assets);
+        assert_eq!(diff.len(), 1, "missing assets differ");
+        assert_eq!(diff[0].left, None);
+    }
+
+    #[test]
+    fn diff_worldview_works() {
+        use crate::payload::StatusBuilder;
+
+        let status = |peer_id: &str, chain: &Chain, tampered: Option<&Chain>| {
+            StatusBuilder::default()
+                .peer_id(peer_id.into())
+                .asset(Vec::new())
+                .chain(chain.blocks())
+                .tampered_assets(None)
+                .tampered_chain(tampered.map(|chain| chain.blocks()))
+                .build()
+                .unwrap()
+        };
+
+        let mut chain = Chain::new("test");
+        let mut assets = AssetLedger::generate("attacker");
+        let mut tampered_chain = chain.clone();
+        sell(
+            &mut tampered_chain,
+            &mut assets.clone(),
+            "attacker",
+            "victim",
+        );
+        let victim_chain = tampered_chain.clone();
+        sell(&mut chain, &mut assets, "attacker", "normal");
+
+        let states = vec![
+            status("attacker", &chain, Some(&tampered_chain)),
+            status("normal", &chain, None),
+            status("victim", &victim_chain, None),
+        ];
+        let diff = diff_worldview(&states);
+        assert_eq!(diff.peers.len(), 3, "every pair compared");
+        assert!(diff.peers[0].is_empty(), "attacker and normal agree");
+        assert_eq!(
+            (diff.peers[1].left.as_str(), diff.peers[1].right.as_str()),
+            ("attacker", "victim")
+        );
+        assert_eq!(diff.peers[1].chain.left.len(), 1);
+        assert_eq!(diff.peers[2].chain.right.len(), 1);
+
+        assert_eq!(diff.tampered.len(), 1, "only attacker has tampered ledgers");
+        let tampered = &diff.tampered[0];
+        assert_eq!(tampered.chain.common_prefix, chain.blocks().len() - 1);
+        assert_eq!(
+            tampered.chain.right[0].get_hash(),
+            victim_chain.get_last_block().get_hash()
+        );
+    }
+}
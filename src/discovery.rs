@@ -24,15 +24,82 @@
 //! - [DumpPeer]: Get a dump of all peers in the network
 //! - [GetPeer]: Get ingo on a specific peer
 //! - [ReplacePeerLedger]: Replace peer ledger
+//! - [RecordEnrollment]: Record a peer enrolled from a host
+//! - [GetEnrollments]: Get number of peers enrolled from a host
+//!
+//! Enrollment with the auditor can be guarded against sybil attacks with
+//! an [EnrollPolicy].
+
+use std::collections::HashMap;
 
 use actix::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::error::{PeerError, PeerResult};
 use crate::payload::Peer;
+use crate::utils::hasher;
+
+/// highest enrollment proof of work difficulty that peers solve,
+/// every level multiplies the expected work by 16
+pub const MAX_POW_DIFFICULTY: usize = 6;
+
+/// Sybil defenses that the auditor applies when enrolling peers.
+/// Peers are identified by the host they enroll from, the address
+/// they declare and its port aren't trusted
+#[derive(Deserialize, Default, Serialize, Clone, Debug, PartialEq)]
+pub struct EnrollPolicy {
+    /// maximum number of peers that can enroll from a host
+    pub address_limit: Option<usize>,
+    /// number of leading zeroes(hex) required in the enrollment
+    /// proof of work hash
+    pub pow_difficulty: Option<usize>,
+    /// peers enrolling from a host that already holds stake don't
+    /// receive assets
+    pub stake_gated: bool,
+}
+
+impl EnrollPolicy {
+    /// fields are prefixed with their length so that different splits
+    /// of the same string don't hash the same
+    fn pow_hash(peer: &Peer, nonce: u64) -> String {
+        hasher(&format!(
+            "{}:{}{}:{}{}",
+            peer.id.len(),
+            peer.id,
+            peer.ip.len(),
+            peer.ip,
+            nonce
+        ))
+    }
+
+    /// checks if `nonce` is a valid proof of work for `peer`
+    pub fn verify_pow(peer: &Peer, nonce: u64, difficulty: usize) -> bool {
+        EnrollPolicy::pow_hash(peer, nonce)
+            .chars()
+            .take(difficulty)
+            .all(|c| c == '0')
+    }
+
+    /// finds a proof of work for `peer`. This is CPU bound, run it with
+    /// [actix_web::web::block] from async code
+    pub fn solve_pow(peer: &Peer, difficulty: usize) -> PeerResult<u64> {
+        if difficulty > MAX_POW_DIFFICULTY {
+            return Err(PeerError::PowTooDifficult);
+        }
+        let mut nonce = 0;
+        while !EnrollPolicy::verify_pow(peer, nonce, difficulty) {
+            nonce += 1;
+        }
+        Ok(nonce)
+    }
+}
 
 #[derive(Deserialize, Default, Serialize, Clone, Debug)]
 pub struct Network {
     peer: Vec<Peer>,
+    /// number of peers enrolled from every host
+    #[serde(skip)]
+    enrollments: HashMap<String, usize>,
 }
 
 impl Actor for Network {
@@ -64,6 +131,16 @@ pub struct GetCurrentSize;
 #[rtype(result = "()")]
 pub struct ReplacePeerLedger(pub Vec<Peer>);
 
+/// Record a peer enrolled from host `RecordEnrollment.0`
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordEnrollment(pub String);
+
+/// Get number of peers enrolled from host `GetEnrollments.0`
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct GetEnrollments(pub String);
+
 impl Network {
     fn get_peer_index(&self, id: &str) -> Option<usize> {
         let mut target: Option<usize> = None;
@@ -125,10 +202,61 @@ impl Handler<ReplacePeerLedger> for Network {
     }
 }
 
+impl Handler<RecordEnrollment> for Network {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordEnrollment, _ctx: &mut Self::Context) -> Self::Result {
+        *self.enrollments.entry(msg.0).or_default() += 1;
+    }
+}
+
+impl Handler<GetEnrollments> for Network {
+    type Result = MessageResult<GetEnrollments>;
+
+    fn handle(&mut self, msg: GetEnrollments, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.enrollments.get(&msg.0).cloned().unwrap_or_default())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn enroll_pow_works() {
+        let peer = Peer {
+            id: "one.example.com".into(),
+            ip: "1.1.1.1:8080".into(),
+        };
+        let difficulty = 2;
+        let nonce = EnrollPolicy::solve_pow(&peer, difficulty).unwrap();
+        assert!(EnrollPolicy::verify_pow(&peer, nonce, difficulty));
+        assert_eq!(
+            EnrollPolicy::solve_pow(&peer, MAX_POW_DIFFICULTY + 1),
+            Err(PeerError::PowTooDifficult),
+            "difficulty is capped"
+        );
+
+        let sybil = Peer {
+            id: "two.example.com".into(),
+            ip: peer.ip.clone(),
+        };
+        assert!(
+            !EnrollPolicy::verify_pow(&sybil, nonce, 64),
+            "proof of work is bound to peer"
+        );
+
+        let split = |id: &str, ip: &str| Peer {
+            id: id.into(),
+            ip: ip.into(),
+        };
+        assert_ne!(
+            EnrollPolicy::pow_hash(&split("one", "1.1.1.1"), 0),
+            EnrollPolicy::pow_hash(&split("one1", ".1.1.1"), 0),
+            "fields are delimited"
+        );
+    }
+
     #[actix_rt::test]
     async fn network_actor_works() {
         let network_addr = Network::default().start();
@@ -169,5 +297,25 @@ mod test {
             1,
             "GetCurrentSize works"
         );
+
+        assert_eq!(
+            network_addr
+                .send(GetEnrollments("1.1.1.1".into()))
+                .await
+                .unwrap(),
+            0
+        );
+        network_addr
+            .send(RecordEnrollment("1.1.1.1".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            network_addr
+                .send(GetEnrollments("1.1.1.1".into()))
+                .await
+                .unwrap(),
+            1,
+            "enrollments are counted by host"
+        );
     }
 }
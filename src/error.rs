@@ -16,6 +16,7 @@
 */
 
 //! Error datatypes
+use actix_web::{http::StatusCode, ResponseError};
 use derive_more::{Display, Error};
 
 /// Errors that can occur when interacting with the blockchain
@@ -34,6 +35,21 @@ pub enum ChainError {
     /// Block inconsistent, block.hash() != chain.get_last_block*().get_hash()
     #[display(fmt = "Block can't be added, previous hash and block data don't match")]
     InconsistentBlockAdition,
+    /// Occurs when a chain that isn't longer than the current chain
+    /// is offered as a replacement
+    #[display(fmt = "Chain can't replace current chain, it isn't longer")]
+    ShorterChain,
+    /// Occurs when a replacement chain rewrites blocks that are
+    /// deeper than the checkpoint depth
+    #[display(fmt = "Chain rewrites checkpointed blocks, possible long-range attack")]
+    CheckpointViolation,
+    /// Occurs when a replacement chain rewrites final blocks
+    #[display(fmt = "Chain rewrites final blocks")]
+    FinalityViolation,
+    /// Occurs when a block spends or credits more tokens than
+    /// balances can hold
+    #[display(fmt = "Block overflows token balances")]
+    TokenOverflow,
 }
 
 /// [Result] datatype for Chain interactions
@@ -46,13 +62,196 @@ pub enum PeerError {
     /// When a non-attacking peer is asked to attack
     #[display(fmt = "Peer is not configured to attack, can't carry out attack")]
     NotAttacker,
+    /// When an attacker that isn't withholding blocks is asked to
+    /// mine or release them
+    #[display(fmt = "Peer isn't withholding blocks, enable selfish mining first")]
+    NotSelfish,
+    /// When a peer creates a block that it wasn't chosen to validate
+    #[display(fmt = "Peer wasn't chosen to validate the block")]
+    NotValidator,
     /// When a non-auditor peer is asked to mint assets
     #[display(fmt = "Peer is not configured to mint assets, can't mint assets")]
     NotAuditor,
+    /// When too many peers enroll from the same address
+    #[display(fmt = "Enrollment limit for this address reached")]
+    AddressLimitReached,
+    /// When enrollment proof of work doesn't meet required difficulty
+    #[display(fmt = "Invalid proof of work, can't enroll peer")]
+    InvalidProofOfWork,
+    /// When enrollment proof of work is harder than
+    /// [MAX_POW_DIFFICULTY](crate::discovery::MAX_POW_DIFFICULTY)
+    #[display(fmt = "Proof of work difficulty is too high, can't enroll peer")]
+    PowTooDifficult,
+    /// When a peer enrolls after the network reached its initial size
+    #[display(fmt = "Network is full, can't enroll peer")]
+    NetworkFull,
+    /// When a peer that doesn't own any assets is asked to stake
+    #[display(fmt = "Peer doesn't own any assets, can't stake")]
+    NoStake,
     /// Blockchian error
     #[display(fmt = "{}", _0)]
     ChainError(ChainError),
+    /// Transaction error
+    #[display(fmt = "{}", _0)]
+    TxError(TxError),
+}
+
+impl ResponseError for PeerError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
 }
 
 /// [Result] datatype for peer interactions
 pub type PeerResult<V> = std::result::Result<V, PeerError>;
+
+/// Errors that can occur when validating transactions
+#[derive(Debug, PartialEq, Display, Clone, Error)]
+#[cfg(not(tarpaulin_include))]
+pub enum TxError {
+    /// When replay protection is enabled and a transaction
+    /// doesn't carry a nonce and chain ID
+    #[display(fmt = "Transaction doesn't carry a nonce and chain ID")]
+    MissingReplayProtection,
+    /// When transaction nonce doesn't match seller's next nonce
+    #[display(fmt = "Transaction nonce is invalid, possible replay")]
+    InvalidNonce,
+    /// When transaction is meant for a different chain
+    #[display(fmt = "Transaction is meant for a different chain")]
+    WrongChain,
+    /// When the payer of a transaction doesn't have enough tokens
+    #[display(fmt = "Insufficient balance to pay for transaction")]
+    InsufficientBalance,
+    /// When the asset of a transaction isn't in the ledger
+    #[display(fmt = "Asset not found")]
+    UnknownAsset,
+    /// When a transaction on a UTXO ledger doesn't spend an output
+    #[display(fmt = "Transaction doesn't spend an output")]
+    MissingInput,
+    /// When a transaction spends an output that was already spent
+    #[display(fmt = "Output already spent, possible double-spend")]
+    OutputSpent,
+    /// When a transaction spends an output that the asset never had
+    #[display(fmt = "Output not found")]
+    UnknownOutput,
+    /// When a transaction spends an output that belongs to someone else
+    #[display(fmt = "Output isn't owned by the seller")]
+    NotOutputOwner,
+    /// When an asset that is split, merged or burned isn't owned by
+    /// the sender or was already used up
+    #[display(fmt = "Asset isn't owned by the sender")]
+    NotAssetOwner,
+    /// When a split doesn't create at least two assets of non-zero value
+    /// or a merge doesn't combine at least two distinct assets
+    #[display(fmt = "Split or merge is malformed")]
+    InvalidAssetChange,
+    /// When values of created or burned assets don't add up to the
+    /// values of spent assets
+    #[display(fmt = "Asset values aren't conserved")]
+    ValueNotConserved,
+    /// When asset values add up to more than can be represented
+    #[display(fmt = "Asset values overflow")]
+    ValueOverflow,
+    /// When a minted asset would take supply beyond the supply cap
+    #[display(fmt = "Minting would exceed the asset supply cap")]
+    SupplyCapExceeded,
+    /// When a minted asset would exceed the issuance schedule
+    #[display(fmt = "Minting would exceed the issuance schedule")]
+    IssuanceExceeded,
+    /// When a minted asset has the ID of an existing asset
+    #[display(fmt = "Asset already exists")]
+    AssetExists,
+    /// When a bonded or unbonding asset is transacted
+    #[display(fmt = "Asset is locked as stake")]
+    AssetLocked,
+    /// When an asset that isn't bonded is unbonded
+    #[display(fmt = "Asset isn't bonded")]
+    NotBonded,
+    /// When an unbonded asset would be unlocked before the
+    /// unbonding period is over
+    #[display(fmt = "Unbonding period is too short")]
+    UnbondingTooShort,
+    /// When blocks of slashing evidence don't conflict or aren't intact
+    #[display(fmt = "Evidence doesn't prove conflicting blocks")]
+    InvalidEvidence,
+    /// When blocks of slashing evidence weren't seen by the peer
+    /// checking it
+    #[display(fmt = "Evidence blocks weren't seen by this peer")]
+    UnseenEvidence,
+    /// When evidence was already used to slash a validator
+    #[display(fmt = "Evidence was already submitted")]
+    EvidenceUsed,
+    /// When slashed assets aren't exactly the assets locked by the
+    /// offending validator
+    #[display(fmt = "Slashed assets don't match the validator's locked assets")]
+    InvalidSlash,
+    /// When the offending validator has no locked assets
+    #[display(fmt = "Validator has no locked assets to slash")]
+    NothingToSlash,
+    /// When an offer is made for an asset that its owner isn't selling
+    #[display(fmt = "Asset isn't for sale")]
+    NotForSale,
+    /// When an offer doesn't cover the price asked by the owner
+    #[display(fmt = "Offer is below the asked price")]
+    PriceTooLow,
+    /// When a seller moves tokens without an ask and an offer
+    #[display(fmt = "Only asset transfers can be made without an offer")]
+    NotAssetTransfer,
+}
+
+impl ResponseError for TxError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// [Result] datatype for transaction validation
+pub type TxResult<V> = std::result::Result<V, TxError>;
+
+/// Errors that can occur when injecting faults
+#[derive(Debug, PartialEq, Display, Clone, Error)]
+#[cfg(not(tarpaulin_include))]
+pub enum FaultError {
+    /// drop and duplication rates are percentages
+    #[display(fmt = "Rate must be a percentage between 0 and 100")]
+    InvalidRate,
+    /// latency and jitter are bounded by [MAX_LATENCY](crate::faults::MAX_LATENCY)
+    #[display(fmt = "Latency and jitter can't be longer than an hour")]
+    InvalidLatency,
+}
+
+impl ResponseError for FaultError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// [Result] datatype for fault injection
+pub type FaultResult<V> = std::result::Result<V, FaultError>;
+
+/// Errors that can occur when querying ledgers
+#[derive(Debug, PartialEq, Display, Clone, Error)]
+#[cfg(not(tarpaulin_include))]
+pub enum QueryError {
+    /// when the requested asset isn't in the ledger
+    #[display(fmt = "Asset not found")]
+    AssetNotFound,
+    /// when the requested block isn't in the chain
+    #[display(fmt = "Block not found")]
+    BlockNotFound,
+    /// when the lower bound of a range is greater than its upper bound
+    #[display(fmt = "Invalid range, from can't be greater than to")]
+    InvalidRange,
+}
+
+impl ResponseError for QueryError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            QueryError::InvalidRange => StatusCode::BAD_REQUEST,
+            _ => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+/// [Result] datatype for ledger queries
+pub type QueryResult<V> = std::result::Result<V, QueryError>;
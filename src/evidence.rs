@@ -0,0 +1,312 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Validator equivocation detection. Peers record blocks they receive
+//! with the [EvidenceLog] actor, which remembers the blocks every
+//! validator created on top of every block. A different block from the
+//! same validator on top of the same block is [Evidence] that the
+//! validator signed conflicting blocks, like the blocks an attacker
+//! sends to different peers to double-spend an asset.
+//!
+//! Blocks are named by their validator but not signed, so peers only
+//! record gossiped evidence and slash validators for blocks they have
+//! received themselves, see [HasSeen]
+//!
+//! Only intact blocks are recorded and blocks are forgotten once they
+//! are final or more than [EVIDENCE_DEPTH] blocks below the tip, see
+//! [PruneSeen]. Received blocks that are part of evidence are kept
+//!
+//! # [EvidenceLog] actor supports the following messages:
+//! - [ObserveBlock]: Record a block, returns evidence if it conflicts
+//!   with a block that was recorded before
+//! - [AddEvidence]: Record evidence gossiped by peers
+//! - [DumpEvidence]: Get all recorded evidence
+//! - [HasSeen]: Check if a block was recorded
+//! - [PruneSeen]: Forget blocks recorded below a height
+
+use std::collections::{BTreeMap, HashMap};
+
+use actix::prelude::*;
+
+use crate::block::{Block, Evidence};
+
+/// number of blocks below the tip for which received blocks are kept
+pub const EVIDENCE_DEPTH: usize = 100;
+
+/// Evidence of equivocation and the blocks it's detected from
+#[derive(Default, Clone, Debug)]
+pub struct EvidenceLog {
+    pub evidence: Vec<Evidence>,
+    /// blocks seen from every validator on top of every block, keyed
+    /// by previous block's hash and validator
+    seen: HashMap<(String, String), Vec<Block>>,
+    /// keys of `seen` by the height of the chain when they were
+    /// first recorded
+    heights: BTreeMap<usize, Vec<(String, String)>>,
+    /// height below which blocks aren't recorded
+    floor: usize,
+}
+
+impl EvidenceLog {
+    /// records `block`, received when the chain was `height` blocks
+    /// high. Returns evidence if the validator of the block created a
+    /// different block on top of the same block before and the conflict
+    /// wasn't recorded yet. Blocks that aren't intact are ignored
+    pub fn observe(&mut self, block: &Block, height: usize) -> Option<Evidence> {
+        if block.is_genesis() || block.hash() != block.get_hash() || height < self.floor {
+            return None;
+        }
+        let key = (
+            block.get_prev()?.to_owned(),
+            block.get_validator()?.to_owned(),
+        );
+        if !self.seen.contains_key(&key) {
+            self.heights.entry(height).or_default().push(key.clone());
+        }
+        let seen = self.seen.entry(key).or_default();
+        if seen.iter().any(|seen| seen.get_hash() == block.get_hash()) {
+            return None;
+        }
+        seen.push(block.clone());
+        let evidence = Evidence::new(seen.first()?.clone(), block.clone())?;
+        if self.add(evidence.clone()) {
+            Some(evidence)
+        } else {
+            None
+        }
+    }
+
+    /// forgets blocks recorded when the chain was less than `floor`
+    /// blocks high, evidence and the blocks in it are kept
+    pub fn prune(&mut self, floor: usize) {
+        if floor <= self.floor {
+            return;
+        }
+        let kept = self.heights.split_off(&floor);
+        for key in self.heights.values().flatten() {
+            if let Some(seen) = self.seen.get_mut(key) {
+                let evidence = &self.evidence;
+                seen.retain(|block| {
+                    evidence.iter().any(|evidence| {
+                        evidence.first.get_hash() == block.get_hash()
+                            || evidence.second.get_hash() == block.get_hash()
+                    })
+                });
+                if seen.is_empty() {
+                    self.seen.remove(key);
+                }
+            }
+        }
+        self.heights = kept;
+        self.floor = floor;
+    }
+
+    /// checks if block `hash` was received by this peer. Blocks that
+    /// are only part of gossiped evidence don't count
+    pub fn has_seen(&self, hash: &str) -> bool {
+        self.seen
+            .values()
+            .flatten()
+            .any(|block| block.get_hash() == hash)
+    }
+
+    /// records `evidence`, returns false if it's invalid or was
+    /// recorded before
+    pub fn add(&mut self, evidence: Evidence) -> bool {
+        if !evidence.is_valid() || self.evidence.iter().any(|e| e.id() == evidence.id()) {
+            false
+        } else {
+            self.evidence.push(evidence);
+            true
+        }
+    }
+}
+
+impl Actor for EvidenceLog {
+    type Context = Context<Self>;
+}
+
+/// Record a block received when the chain was `height` blocks high,
+/// returns evidence if the block conflicts with a block that was
+/// recorded before, see [EvidenceLog::observe]
+#[derive(Message)]
+#[rtype(result = "Option<Evidence>")]
+pub struct ObserveBlock {
+    pub block: Block,
+    pub height: usize,
+}
+
+/// Forget blocks recorded below height `PruneSeen.0`, see
+/// [EvidenceLog::prune]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PruneSeen(pub usize);
+
+/// Record evidence, returns false if it's invalid or was recorded before
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct AddEvidence(pub Evidence);
+
+/// Get all recorded evidence
+#[derive(Message)]
+#[rtype(result = "Vec<Evidence>")]
+pub struct DumpEvidence;
+
+/// Check if block `HasSeen.0` was recorded, see [EvidenceLog::has_seen]
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct HasSeen(pub String);
+
+impl Handler<ObserveBlock> for EvidenceLog {
+    type Result = MessageResult<ObserveBlock>;
+
+    fn handle(&mut self, msg: ObserveBlock, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.observe(&msg.block, msg.height))
+    }
+}
+
+impl Handler<PruneSeen> for EvidenceLog {
+    type Result = ();
+
+    fn handle(&mut self, msg: PruneSeen, _ctx: &mut Self::Context) -> Self::Result {
+        self.prune(msg.0);
+    }
+}
+
+impl Handler<AddEvidence> for EvidenceLog {
+    type Result = MessageResult<AddEvidence>;
+
+    fn handle(&mut self, msg: AddEvidence, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.add(msg.0))
+    }
+}
+
+impl Handler<DumpEvidence> for EvidenceLog {
+    type Result = MessageResult<DumpEvidence>;
+
+    fn handle(&mut self, _msg: DumpEvidence, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.evidence.clone())
+    }
+}
+
+impl Handler<HasSeen> for EvidenceLog {
+    type Result = MessageResult<HasSeen>;
+
+    fn handle(&mut self, msg: HasSeen, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.has_seen(&msg.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::block::BlockBuilder;
+
+    fn block(prev: &Block, rx: &str, validator: &str) -> Block {
+        BlockBuilder::default()
+            .set_tx("attacker")
+            .set_rx(rx)
+            .set_asset_id("asset")
+            .set_validator(validator)
+            .set_prev(prev)
+            .build()
+    }
+
+    #[actix_rt::test]
+    async fn evidence_log_works() {
+        let prev = Block::genesis();
+        let log = EvidenceLog::default().start();
+        let observe = |block: Block| ObserveBlock { block, height: 1 };
+
+        let normal = block(&prev, "normal", "attacker");
+        assert_eq!(log.send(observe(normal.clone())).await.unwrap(), None);
+        assert_eq!(
+            log.send(observe(normal.clone())).await.unwrap(),
+            None,
+            "same block seen twice"
+        );
+        assert_eq!(
+            log.send(observe(block(&normal, "victim", "attacker")))
+                .await
+                .unwrap(),
+            None,
+            "next block doesn't conflict"
+        );
+        assert_eq!(
+            log.send(observe(block(&prev, "victim", "normal")))
+                .await
+                .unwrap(),
+            None,
+            "other validator's block doesn't conflict"
+        );
+
+        let victim = block(&prev, "victim", "attacker");
+        assert!(!log.send(HasSeen(victim.get_hash().into())).await.unwrap());
+        let evidence = log.send(observe(victim.clone())).await.unwrap().unwrap();
+        assert_eq!(evidence, Evidence::new(normal, victim.clone()).unwrap());
+        assert_eq!(
+            log.send(observe(victim)).await.unwrap(),
+            None,
+            "conflicts are recorded once"
+        );
+        assert!(!log.send(AddEvidence(evidence.clone())).await.unwrap());
+        for block in [&evidence.first, &evidence.second].iter() {
+            assert!(log.send(HasSeen(block.get_hash().into())).await.unwrap());
+        }
+
+        let mut forged = evidence.clone();
+        forged.second = forged.first.clone();
+        assert!(!log.send(AddEvidence(forged)).await.unwrap());
+        assert_eq!(log.send(DumpEvidence).await.unwrap(), vec![evidence]);
+    }
+
+    #[test]
+    fn observe_prune_works() {
+        let prev = Block::genesis();
+        let mut log = EvidenceLog::default();
+
+        let mut tampered = serde_json::to_value(block(&prev, "normal", "attacker")).unwrap();
+        tampered["hash"] = "tampered".into();
+        let tampered: Block = serde_json::from_value(tampered).unwrap();
+        assert_eq!(log.observe(&tampered, 1), None);
+        assert!(!log.has_seen(tampered.get_hash()), "broken blocks ignored");
+
+        let normal = block(&prev, "normal", "attacker");
+        let victim = block(&prev, "victim", "attacker");
+        assert_eq!(log.observe(&normal, 1), None);
+        assert!(log.has_seen(normal.get_hash()));
+        log.prune(2);
+        assert!(!log.has_seen(normal.get_hash()), "old blocks forgotten");
+        assert_eq!(log.observe(&victim, 1), None, "blocks below floor ignored");
+        assert!(!log.has_seen(victim.get_hash()));
+
+        assert_eq!(log.observe(&normal, 2), None);
+        assert!(log.observe(&victim, 3).is_some());
+        log.prune(10);
+        assert!(log.has_seen(victim.get_hash()), "evidence is kept");
+        assert!(log.has_seen(normal.get_hash()), "evidence is kept");
+
+        let other = block(&prev, "other", "normal");
+        let gossiped = Evidence::new(other.clone(), block(&prev, "victim", "normal")).unwrap();
+        assert!(log.add(gossiped));
+        assert!(
+            !log.has_seen(other.get_hash()),
+            "blocks only in evidence aren't seen"
+        );
+    }
+}
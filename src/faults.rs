@@ -0,0 +1,382 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Fault injection service for simulating an unreliable network.
+//! Faults are applied to outgoing p2p messages(stake requests,
+//! transactions and block broadcasts) sent by [Client](crate::Client).
+//! The easiest way to interact with the fault injection service
+//! is with the [FaultInjector] actor.
+//!
+//! # [FaultInjector] actor supports the following messages:
+//! - [Partition]: Cut off communication with a set of peers
+//! - [SetLatency]: Delay outgoing messages
+//! - [SetDropRate]: Drop a percentage of outgoing messages
+//! - [SetDuplicateRate]: Duplicate a percentage of outgoing messages
+//! - [BlockRoute]: Drop all outgoing messages to a route
+//! - [ClearFaults]: Remove all faults
+//! - [DumpFaults]: Get faults that are currently active
+//! - [Inspect]: Decide the fate of an outgoing message
+
+use actix::prelude::*;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::error::*;
+
+/// longest latency and jitter that can be set, in milliseconds(an hour)
+pub const MAX_LATENCY: u64 = 60 * 60 * 1000;
+
+/// Faults that are currently active on a peer
+#[derive(Deserialize, Default, Serialize, Clone, Debug, PartialEq)]
+pub struct FaultInjector {
+    /// IDs of peers that this peer can't reach
+    pub partition: Vec<String>,
+    /// delay added to every outgoing message, in milliseconds
+    pub latency: u64,
+    /// maximum random delay added on top of `latency`, in milliseconds
+    pub jitter: u64,
+    /// percentage of outgoing messages that are dropped
+    pub drop_rate: u8,
+    /// percentage of outgoing messages that are delivered twice
+    pub duplicate_rate: u8,
+    /// routes to which outgoing messages are dropped
+    pub blocked_routes: Vec<String>,
+}
+
+impl Actor for FaultInjector {
+    type Context = Context<Self>;
+}
+
+/// Fate of an outgoing message
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    /// message shouldn't be sent
+    Drop,
+    /// message should be sent `copies` times after waiting for
+    /// `delay` milliseconds
+    Deliver { delay: u64, copies: usize },
+}
+
+/// Partition peers, replaces previous partition
+#[derive(Deserialize, Serialize, Message)]
+#[rtype(result = "()")]
+pub struct Partition(pub Vec<String>);
+
+/// Set latency and jitter in milliseconds, each at most [MAX_LATENCY]
+#[derive(Deserialize, Serialize, Message)]
+#[rtype(result = "FaultResult<()>")]
+pub struct SetLatency {
+    pub latency: u64,
+    pub jitter: u64,
+}
+
+/// Set percentage of messages that should be dropped
+#[derive(Deserialize, Serialize, Message)]
+#[rtype(result = "FaultResult<()>")]
+pub struct SetDropRate(pub u8);
+
+/// Set percentage of messages that should be duplicated
+#[derive(Deserialize, Serialize, Message)]
+#[rtype(result = "FaultResult<()>")]
+pub struct SetDuplicateRate(pub u8);
+
+/// Drop all outgoing messages to a route, eg: `/chain/add`
+#[derive(Deserialize, Serialize, Message)]
+#[rtype(result = "()")]
+pub struct BlockRoute(pub String);
+
+/// Remove all faults
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ClearFaults;
+
+/// Dump faults that are currently active
+#[derive(Message)]
+#[rtype(result = "FaultInjector")]
+pub struct DumpFaults;
+
+/// Decide fate of a message that is being sent
+/// to peer `peer_id` on `route`
+#[derive(Message)]
+#[rtype(result = "Verdict")]
+pub struct Inspect {
+    pub peer_id: String,
+    pub route: String,
+}
+
+impl FaultInjector {
+    fn check_latency(msg: &SetLatency) -> FaultResult<()> {
+        if msg.latency > MAX_LATENCY || msg.jitter > MAX_LATENCY {
+            Err(FaultError::InvalidLatency)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_rate(rate: u8) -> FaultResult<()> {
+        if rate > 100 {
+            Err(FaultError::InvalidRate)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// decide fate of a message sent to `peer_id` on `route`
+    pub fn inspect(&self, peer_id: &str, route: &str) -> Verdict {
+        if self.partition.iter().any(|peer| peer == peer_id)
+            || self.blocked_routes.iter().any(|blocked| blocked == route)
+        {
+            return Verdict::Drop;
+        }
+
+        let mut rng = thread_rng();
+        if rng.gen_range(0..100) < self.drop_rate {
+            return Verdict::Drop;
+        }
+
+        let delay = if self.jitter > 0 {
+            self.latency.saturating_add(rng.gen_range(0..=self.jitter))
+        } else {
+            self.latency
+        };
+
+        let copies = if rng.gen_range(0..100) < self.duplicate_rate {
+            2
+        } else {
+            1
+        };
+
+        Verdict::Deliver { delay, copies }
+    }
+}
+
+impl Handler<Partition> for FaultInjector {
+    type Result = ();
+
+    fn handle(&mut self, msg: Partition, _ctx: &mut Self::Context) -> Self::Result {
+        self.partition = msg.0;
+    }
+}
+
+impl Handler<SetLatency> for FaultInjector {
+    type Result = MessageResult<SetLatency>;
+
+    fn handle(&mut self, msg: SetLatency, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(FaultInjector::check_latency(&msg).map(|_| {
+            self.latency = msg.latency;
+            self.jitter = msg.jitter;
+        }))
+    }
+}
+
+impl Handler<SetDropRate> for FaultInjector {
+    type Result = MessageResult<SetDropRate>;
+
+    fn handle(&mut self, msg: SetDropRate, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(FaultInjector::check_rate(msg.0).map(|_| self.drop_rate = msg.0))
+    }
+}
+
+impl Handler<SetDuplicateRate> for FaultInjector {
+    type Result = MessageResult<SetDuplicateRate>;
+
+    fn handle(&mut self, msg: SetDuplicateRate, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(FaultInjector::check_rate(msg.0).map(|_| self.duplicate_rate = msg.0))
+    }
+}
+
+impl Handler<BlockRoute> for FaultInjector {
+    type Result = ();
+
+    fn handle(&mut self, msg: BlockRoute, _ctx: &mut Self::Context) -> Self::Result {
+        if !self.blocked_routes.contains(&msg.0) {
+            self.blocked_routes.push(msg.0);
+        }
+    }
+}
+
+impl Handler<ClearFaults> for FaultInjector {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ClearFaults, _ctx: &mut Self::Context) -> Self::Result {
+        *self = FaultInjector::default();
+    }
+}
+
+impl Handler<DumpFaults> for FaultInjector {
+    type Result = MessageResult<DumpFaults>;
+
+    fn handle(&mut self, _msg: DumpFaults, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.clone())
+    }
+}
+
+impl Handler<Inspect> for FaultInjector {
+    type Result = MessageResult<Inspect>;
+
+    fn handle(&mut self, msg: Inspect, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.inspect(&msg.peer_id, &msg.route))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inspect_msg(peer_id: &str, route: &str) -> Inspect {
+        Inspect {
+            peer_id: peer_id.into(),
+            route: route.into(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn fault_injector_works() {
+        let fault_addr = FaultInjector::default().start();
+
+        // no faults, messages are delivered as is
+        assert_eq!(
+            fault_addr
+                .send(inspect_msg("victim.batsense.net", "/chain/add"))
+                .await
+                .unwrap(),
+            Verdict::Deliver {
+                delay: 0,
+                copies: 1
+            },
+            "no faults works"
+        );
+
+        // partitioned peers are unreachable
+        fault_addr
+            .send(Partition(vec!["victim.batsense.net".into()]))
+            .await
+            .unwrap();
+        assert_eq!(
+            fault_addr
+                .send(inspect_msg("victim.batsense.net", "/chain/add"))
+                .await
+                .unwrap(),
+            Verdict::Drop,
+            "partition works"
+        );
+
+        // blocked routes are unreachable
+        fault_addr.send(BlockRoute("/stake".into())).await.unwrap();
+        assert_eq!(
+            fault_addr
+                .send(inspect_msg("normal.batsense.net", "/stake"))
+                .await
+                .unwrap(),
+            Verdict::Drop,
+            "block route works"
+        );
+
+        // latency is added
+        fault_addr
+            .send(SetLatency {
+                latency: 20,
+                jitter: 0,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        fault_addr
+            .send(SetDuplicateRate(100))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            fault_addr
+                .send(inspect_msg("normal.batsense.net", "/chain/add"))
+                .await
+                .unwrap(),
+            Verdict::Deliver {
+                delay: 20,
+                copies: 2
+            },
+            "latency and duplication works"
+        );
+        assert_eq!(
+            fault_addr
+                .send(SetLatency {
+                    latency: MAX_LATENCY + 1,
+                    jitter: 0,
+                })
+                .await
+                .unwrap(),
+            Err(FaultError::InvalidLatency),
+            "latency is bounded"
+        );
+        let slow = FaultInjector {
+            latency: u64::MAX,
+            jitter: u64::MAX,
+            ..FaultInjector::default()
+        };
+        assert_eq!(
+            slow.inspect("normal.batsense.net", "/chain/add"),
+            Verdict::Deliver {
+                delay: u64::MAX,
+                copies: 1
+            },
+            "delay saturates"
+        );
+
+        // drop everything
+        fault_addr.send(SetDropRate(100)).await.unwrap().unwrap();
+        assert_eq!(
+            fault_addr
+                .send(inspect_msg("normal.batsense.net", "/chain/add"))
+                .await
+                .unwrap(),
+            Verdict::Drop,
+            "drop rate works"
+        );
+
+        // rates are percentages
+        assert_eq!(
+            fault_addr.send(SetDropRate(101)).await.unwrap(),
+            Err(FaultError::InvalidRate),
+            "invalid rate rejected"
+        );
+
+        fault_addr.send(ClearFaults).await.unwrap();
+        assert_eq!(
+            fault_addr.send(DumpFaults).await.unwrap(),
+            FaultInjector::default(),
+            "clear faults works"
+        );
+    }
+
+    #[test]
+    fn jitter_works() {
+        let faults = FaultInjector {
+            latency: 10,
+            jitter: 5,
+            ..FaultInjector::default()
+        };
+
+        for _ in 0..20 {
+            if let Verdict::Deliver { delay, copies } = faults.inspect("normal", "/chain/add") {
+                assert!((10..=15).contains(&delay), "delay within jitter");
+                assert_eq!(copies, 1);
+            } else {
+                panic!("message dropped without faults");
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Finality gadget. Peers [Vote] for blocks they add to their chains
+//! and gossip their votes. A block is final once peers that own more
+//! than 2/3 of stake voted for that exact block. Its ancestors become
+//! final with it, and the [Chain](crate::chain::Chain) refuses to
+//! reorganize past the last final block.
+//!
+//! A peer votes once per serial number. Votes for a different block
+//! with the same serial number are ignored, so a validator can't get
+//! conflicting blocks finalized by voting for both. Votes aren't signed,
+//! so gossiped votes are only recorded once the voter confirms them from
+//! the address it registered with, see [GetVote].
+//!
+//! # [Finality] actor supports the following messages:
+//! - [AddVote]: Record a vote
+//! - [IsFinal]: Check if votes for a block represent more than 2/3 of stake
+//! - [DumpVotes]: Get all recorded votes
+//! - [GetVote]: Get the vote of a peer for a serial number
+
+use std::collections::HashMap;
+
+use actix::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Vote of a peer for a block
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Vote {
+    pub voter: String,
+    /// hash of the block
+    pub hash: String,
+    /// serial number of the block
+    pub serial_no: usize,
+}
+
+/// Votes for blocks
+#[derive(Default, Clone, Debug)]
+pub struct Finality {
+    pub votes: Vec<Vote>,
+}
+
+impl Finality {
+    /// records `vote`, returns false if the voter already voted for a
+    /// block with the same serial number
+    pub fn add(&mut self, vote: Vote) -> bool {
+        if self
+            .votes
+            .iter()
+            .any(|v| v.voter == vote.voter && v.serial_no == vote.serial_no)
+        {
+            false
+        } else {
+            self.votes.push(vote);
+            true
+        }
+    }
+
+    /// vote of `voter` for the block with serial number `serial_no`
+    pub fn get_vote(&self, voter: &str, serial_no: usize) -> Option<&Vote> {
+        self.votes
+            .iter()
+            .find(|vote| vote.voter == voter && vote.serial_no == serial_no)
+    }
+
+    /// IDs of peers that voted for block `hash`
+    pub fn voters(&self, hash: &str) -> Vec<&str> {
+        self.votes
+            .iter()
+            .filter(|vote| vote.hash == hash)
+            .map(|vote| vote.voter.as_str())
+            .collect()
+    }
+
+    /// checks if peers that voted for block `hash` own more than 2/3 of
+    /// `stake`, the value of assets staked by every peer
+    pub fn is_final(&self, hash: &str, stake: &HashMap<String, usize>) -> bool {
+        let total: usize = stake.values().sum();
+        let voted: usize = self
+            .voters(hash)
+            .iter()
+            .filter_map(|voter| stake.get(*voter))
+            .sum();
+        total > 0 && voted * 3 > total * 2
+    }
+}
+
+impl Actor for Finality {
+    type Context = Context<Self>;
+}
+
+/// Record a vote, returns false if the voter already voted for a block
+/// with the same serial number
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct AddVote(pub Vote);
+
+/// Check if votes for a block represent more than 2/3 of stake,
+/// see [Finality::is_final]
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct IsFinal {
+    pub hash: String,
+    pub stake: HashMap<String, usize>,
+}
+
+/// Get all recorded votes
+#[derive(Message)]
+#[rtype(result = "Vec<Vote>")]
+pub struct DumpVotes;
+
+/// Get the vote of `voter` for serial number `serial_no`, see
+/// [Finality::get_vote]
+#[derive(Message)]
+#[rtype(result = "Option<Vote>")]
+pub struct GetVote {
+    pub voter: String,
+    pub serial_no: usize,
+}
+
+impl Handler<AddVote> for Finality {
+    type Result = MessageResult<AddVote>;
+
+    fn handle(&mut self, msg: AddVote, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.add(msg.0))
+    }
+}
+
+impl Handler<IsFinal> for Finality {
+    type Result = MessageResult<IsFinal>;
+
+    fn handle(&mut self, msg: IsFinal, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.is_final(&msg.hash, &msg.stake))
+    }
+}
+
+impl Handler<DumpVotes> for Finality {
+    type Result = MessageResult<DumpVotes>;
+
+    fn handle(&mut self, _msg: DumpVotes, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.votes.clone())
+    }
+}
+
+impl Handler<GetVote> for Finality {
+    type Result = MessageResult<GetVote>;
+
+    fn handle(&mut self, msg: GetVote, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.get_vote(&msg.voter, msg.serial_no).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote(voter: &str, hash: &str, serial_no: usize) -> Vote {
+        Vote {
+            voter: voter.into(),
+            hash: hash.into(),
+            serial_no,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn finality_works() {
+        let stake: HashMap<String, usize> = vec![
+            ("attacker".to_string(), 10),
+            ("normal".to_string(), 10),
+            ("victim".to_string(), 10),
+        ]
+        .into_iter()
+        .collect();
+        let finality = Finality::default().start();
+        let is_final = |hash: &str| IsFinal {
+            hash: hash.into(),
+            stake: stake.clone(),
+        };
+
+        assert!(finality
+            .send(AddVote(vote("attacker", "a", 4)))
+            .await
+            .unwrap());
+        assert!(finality
+            .send(AddVote(vote("victim", "a", 4)))
+            .await
+            .unwrap());
+        assert!(
+            !finality.send(is_final("a")).await.unwrap(),
+            "2/3 of stake isn't enough"
+        );
+
+        assert!(finality
+            .send(AddVote(vote("normal", "b", 4)))
+            .await
+            .unwrap());
+        assert!(
+            !finality
+                .send(AddVote(vote("attacker", "b", 4)))
+                .await
+                .unwrap(),
+            "one vote per serial number"
+        );
+        assert!(!finality.send(is_final("b")).await.unwrap());
+
+        assert!(finality
+            .send(AddVote(vote("attacker", "c", 5)))
+            .await
+            .unwrap());
+        assert!(finality
+            .send(AddVote(vote("normal", "c", 5)))
+            .await
+            .unwrap());
+        assert!(finality
+            .send(AddVote(vote("victim", "c", 5)))
+            .await
+            .unwrap());
+        assert!(finality.send(is_final("c")).await.unwrap());
+        assert!(
+            !finality
+                .send(IsFinal {
+                    hash: "c".into(),
+                    stake: HashMap::default(),
+                })
+                .await
+                .unwrap(),
+            "nothing is final without stake"
+        );
+        assert_eq!(finality.send(DumpVotes).await.unwrap().len(), 6);
+    }
+}
@@ -0,0 +1,348 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Diagrams of chains and asset transfers. Chains of peers are merged
+//! into a [BlockTree], where forks show up as branches, and rendered
+//! as [Graphviz](https://graphviz.org) DOT or [Mermaid](https://mermaid-js.github.io)
+//! flowcharts with [render].
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::payload::Status;
+
+/// Output format of diagrams
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Format::Dot),
+            "mermaid" => Ok(Format::Mermaid),
+            _ => Err(format!("unknown format {}, expected dot or mermaid", s)),
+        }
+    }
+}
+
+/// Query of graph endpoints, eg: `?format=mermaid`
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct GraphQuery {
+    #[serde(default)]
+    pub format: Format,
+}
+
+/// A block and the chains that contain it
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub block: Block,
+    /// labels of chains that contain the block
+    pub chains: Vec<String>,
+    /// set when only tampered chains contain the block
+    pub tampered: bool,
+}
+
+/// Chains merged by block hash, blocks that are in more than one
+/// chain appear once
+#[derive(Clone, Debug, Default)]
+pub struct BlockTree {
+    pub nodes: Vec<Node>,
+}
+
+impl BlockTree {
+    /// adds chain labelled `label`
+    pub fn add_chain(&mut self, label: &str, chain: &[Block], tampered: bool) {
+        for block in chain.iter() {
+            match self
+                .nodes
+                .iter_mut()
+                .find(|node| node.block.get_hash() == block.get_hash())
+            {
+                Some(node) => {
+                    node.chains.push(label.into());
+                    node.tampered = node.tampered && tampered;
+                }
+                None => self.nodes.push(Node {
+                    block: block.clone(),
+                    chains: vec![label.into()],
+                    tampered,
+                }),
+            }
+        }
+    }
+
+    /// legit and tampered chains of peers, tampered chains are
+    /// labelled `<peer ID> (tampered)`
+    pub fn from_states(states: &[Status]) -> Self {
+        let mut tree = BlockTree::default();
+        for state in states.iter() {
+            tree.add_chain(&state.peer_id, &state.chain, false);
+            if let Some(chain) = state.tampered_chain.as_ref() {
+                tree.add_chain(&format!("{} (tampered)", state.peer_id), chain, true);
+            }
+        }
+        tree
+    }
+
+    /// position of block `hash` in the tree
+    fn position(&self, hash: &str) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.block.get_hash() == hash)
+    }
+
+    /// edges between blocks, `(parent, child)`
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(child, node)| {
+                let parent = self.position(node.block.get_prev()?)?;
+                Some((parent, child))
+            })
+            .collect()
+    }
+
+    /// asset transfers between peers, `(seller, buyer, asset ID, tampered)`
+    pub fn transfers(&self) -> Vec<(&str, &str, &str, bool)> {
+        self.nodes
+            .iter()
+            .filter(|node| !node.block.is_genesis())
+            .filter_map(|node| {
+                Some((
+                    node.block.get_tx()?.as_str(),
+                    node.block.get_rx()?.as_str(),
+                    node.block.get_asset_id()?.as_str(),
+                    node.tampered,
+                ))
+            })
+            .collect()
+    }
+
+    /// IDs of peers involved in transfers
+    pub fn peers(&self) -> Vec<&str> {
+        let mut peers: Vec<&str> = self
+            .transfers()
+            .iter()
+            .flat_map(|(tx, rx, _, _)| vec![*tx, *rx])
+            .collect();
+        peers.sort_unstable();
+        peers.dedup();
+        peers
+    }
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(8)]
+}
+
+fn block_label(node: &Node) -> String {
+    let block = &node.block;
+    let title = match block.get_serial_no() {
+        Some(serial_no) => format!("block {} {}", serial_no, short(block.get_hash())),
+        None => format!("genesis {}", short(block.get_hash())),
+    };
+    match (block.get_tx(), block.get_rx()) {
+        (Some(tx), Some(rx)) => {
+            format!("{}\\n{} -> {}\\n{}", title, tx, rx, node.chains.join(", "))
+        }
+        _ => format!("{}\\n{}", title, node.chains.join(", ")),
+    }
+}
+
+/// renders chains and asset transfers as a Graphviz DOT digraph
+pub fn dot(tree: &BlockTree) -> String {
+    let mut out = String::from("digraph blockchain {\n    rankdir=LR;\n");
+
+    out.push_str("    subgraph cluster_chain {\n        label=\"Block tree\";\n");
+    for (i, node) in tree.nodes.iter().enumerate() {
+        let style = if node.tampered {
+            ", style=dashed, color=red"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "        b{} [shape=box, label=\"{}\"{}];",
+            i,
+            block_label(node),
+            style
+        )
+        .unwrap();
+    }
+    for (parent, child) in tree.edges() {
+        writeln!(out, "        b{} -> b{};", parent, child).unwrap();
+    }
+    out.push_str("    }\n");
+
+    out.push_str("    subgraph cluster_transfers {\n        label=\"Asset transfers\";\n");
+    let peers = tree.peers();
+    for (i, peer) in peers.iter().enumerate() {
+        writeln!(out, "        p{} [shape=ellipse, label=\"{}\"];", i, peer).unwrap();
+    }
+    for (tx, rx, asset_id, tampered) in tree.transfers() {
+        let tx = peers.iter().position(|peer| *peer == tx).unwrap();
+        let rx = peers.iter().position(|peer| *peer == rx).unwrap();
+        let style = if tampered {
+            ", style=dashed, color=red"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "        p{} -> p{} [label=\"{}\"{}];",
+            tx,
+            rx,
+            short(asset_id),
+            style
+        )
+        .unwrap();
+    }
+    out.push_str("    }\n}\n");
+    out
+}
+
+/// renders chains and asset transfers as a Mermaid flowchart
+pub fn mermaid(tree: &BlockTree) -> String {
+    let mut out = String::from("flowchart LR\n");
+
+    out.push_str("    subgraph chain [Block tree]\n");
+    for (i, node) in tree.nodes.iter().enumerate() {
+        let label = block_label(node).replace("\\n", "<br/>");
+        writeln!(out, "        b{}[\"{}\"]", i, label).unwrap();
+    }
+    for (parent, child) in tree.edges() {
+        writeln!(out, "        b{} --> b{}", parent, child).unwrap();
+    }
+    out.push_str("    end\n");
+
+    out.push_str("    subgraph transfers [Asset transfers]\n");
+    let peers = tree.peers();
+    for (i, peer) in peers.iter().enumerate() {
+        writeln!(out, "        p{}([\"{}\"])", i, peer).unwrap();
+    }
+    for (tx, rx, asset_id, tampered) in tree.transfers() {
+        let tx = peers.iter().position(|peer| *peer == tx).unwrap();
+        let rx = peers.iter().position(|peer| *peer == rx).unwrap();
+        let arrow = if tampered { "-.->" } else { "-->" };
+        writeln!(
+            out,
+            "        p{} {}|{}| p{}",
+            tx,
+            arrow,
+            short(asset_id),
+            rx
+        )
+        .unwrap();
+    }
+    out.push_str("    end\n");
+
+    let tampered: Vec<String> = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.tampered)
+        .map(|(i, _)| format!("b{}", i))
+        .collect();
+    if !tampered.is_empty() {
+        out.push_str("    classDef tampered stroke:#f00,stroke-dasharray:5 5\n");
+        writeln!(out, "    class {} tampered", tampered.join(",")).unwrap();
+    }
+    out
+}
+
+/// renders chains and asset transfers of peers
+pub fn render(states: &[Status], format: Format) -> String {
+    let tree = BlockTree::from_states(states);
+    match format {
+        Format::Dot => dot(&tree),
+        Format::Mermaid => mermaid(&tree),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::block::BlockBuilder;
+    use crate::chain::Chain;
+    use crate::payload::StatusBuilder;
+
+    fn sell(chain: &mut Chain, tx: &str, rx: &str, asset_id: &str) {
+        let block = BlockBuilder::default()
+            .set_tx(tx)
+            .set_rx(rx)
+            .set_asset_id(asset_id)
+            .set_validator(tx)
+            .set_prev(chain.get_last_block())
+            .build();
+        chain.add_block(block, 3).unwrap();
+    }
+
+    #[test]
+    fn graph_works() {
+        let mut chain = Chain::new("test");
+        let mut tampered = chain.clone();
+        sell(&mut chain, "attacker", "normal", "ASSET1");
+        sell(&mut tampered, "attacker", "victim", "ASSET1");
+
+        let states = vec![StatusBuilder::default()
+            .peer_id("attacker".into())
+            .asset(Vec::new())
+            .chain(chain.blocks())
+            .tampered_assets(None)
+            .tampered_chain(Some(tampered.blocks()))
+            .build()
+            .unwrap()];
+
+        let tree = BlockTree::from_states(&states);
+        let genesis_blocks = chain.blocks().len() - 1;
+        assert_eq!(tree.nodes.len(), genesis_blocks + 2, "shared blocks merged");
+        assert!(!tree.nodes[0].tampered);
+        assert_eq!(
+            tree.nodes[0].chains,
+            vec!["attacker", "attacker (tampered)"]
+        );
+        assert!(tree.nodes.last().unwrap().tampered, "fork is tampered");
+        assert_eq!(tree.edges().len(), tree.nodes.len() - 1, "fork is a branch");
+        assert_eq!(tree.peers(), vec!["attacker", "normal", "victim"]);
+
+        let dot = render(&states, Format::Dot);
+        assert!(dot.starts_with("digraph blockchain {"));
+        assert!(dot.contains("p0 -> p1 [label=\"ASSET1\"];"));
+        assert!(dot.contains("p0 -> p2 [label=\"ASSET1\", style=dashed, color=red];"));
+
+        let mermaid = render(&states, Format::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR"));
+        assert!(mermaid.contains("p0 -->|ASSET1| p1"));
+        assert!(mermaid.contains("p0 -.->|ASSET1| p2"));
+        assert!(mermaid.contains(" tampered\n"));
+
+        assert_eq!("mermaid".parse::<Format>(), Ok(Format::Mermaid));
+        assert!("png".parse::<Format>().is_err());
+    }
+}
@@ -61,16 +61,26 @@
 //! ```
 
 pub mod asset;
+pub mod attack;
 pub mod block;
 pub mod chain;
 pub mod client;
 pub mod config;
+pub mod detector;
+pub mod diff;
 pub mod discovery;
 pub mod error;
+pub mod evidence;
+pub mod faults;
+pub mod finality;
+pub mod graph;
 #[path = "./tests/helpers.rs"]
 pub mod helpers;
 pub mod logs;
 pub mod payload;
+pub mod pbft;
+pub mod timeline;
 pub mod utils;
+pub mod vrf;
 pub use client::Client;
 pub use config::Config;
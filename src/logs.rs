@@ -15,16 +15,35 @@
 * along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+//! Structured event journal. Events are [Action]s that peers take while
+//! running, they are recorded with timestamps as [Command]s.
+//! The easiest way to interact with the journal is with the [EventLog] actor.
+//!
+//! # [EventLog] actor supports the following messages:
+//! - [LogEvent]: Record an event
+//! - [QueryEvents]: Get events that match an [EventFilter]
+//! - [Subscribe]: Get a stream of events as they are recorded
+//!
+//! Subscriptions can be served as Server-Sent Events with [stream_events].
+
+use actix::prelude::*;
+use actix_web::{dev::BodyEncoding, http::ContentEncoding, web::Bytes, HttpResponse};
 use derive_more::Display;
-use serde::Deserialize;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 
-use crate::block::Block;
+use crate::block::{Block, Evidence};
+use crate::config::Mode;
 use crate::payload::*;
 use crate::{asset::Asset, utils::*};
 
-#[derive(Deserialize, Display)]
+#[derive(Deserialize, Display, Serialize, Clone, Debug)]
 #[display(fmt = "[{}]: {}", timesamp, action)]
 pub struct Command {
+    /// serial number of the event in the journal
+    #[serde(default)]
+    pub id: usize,
     pub timesamp: String,
     pub action: Action,
 }
@@ -32,13 +51,14 @@ pub struct Command {
 impl Command {
     pub fn new(action: Action) -> Command {
         Command {
+            id: 0,
             timesamp: get_current_time(),
             action,
         }
     }
 }
 
-#[derive(Deserialize, Display)]
+#[derive(Deserialize, Display, Serialize, Clone, Debug)]
 pub enum Action {
     /// Initialize log
     #[display(fmt = "Initializing log...")]
@@ -73,11 +93,472 @@ pub enum Action {
     TransactionValidated(Peer),
 
     /// Block creation event for block `block ID`
-    #[display(fmt = "Block creation Block ID {}" _0)]
+    #[display(fmt = "Block creation Block ID {}", _0)]
     BlockCreation(Block),
 
     /// Transaction broadcasting event by peer `peer ID` to peer `peer ID`
     /// after when a block is created
-    #[display(fmt = "")]
-    TransactionBroadcasting,
+    #[display(fmt = "Transaction broadcasting by peer {} to peer {}", _0, _1)]
+    TransactionBroadcasting(Peer, Peer),
+
+    /// Block added to the chain of peer `peer ID`
+    #[display(fmt = "Peer {} added block {}", _0, _1)]
+    BlockAdded(String, Block),
+
+    /// Peer `peer ID` changed owner of asset `asset ID` to peer `peer ID`
+    #[display(fmt = "Peer {} changed owner of asset {} to {}", _0, _1, _2)]
+    OwnershipChanged(String, String, String),
+
+    /// Peer `peer ID` changed mode
+    #[display(fmt = "Peer {} changed mode to {:?}", _0, _1)]
+    ModeChanged(String, Mode),
+
+    /// Peer `peer ID` was asked for its stake for block `block ID`
+    #[display(fmt = "Stake request to peer {} for block {}", _0, _1)]
+    StakeRequest(String, usize),
+
+    /// Peer `peer ID` found evidence of a validator signing conflicting blocks
+    #[display(fmt = "Peer {} found equivocation evidence {}", _0, "_1.id()")]
+    EquivocationDetected(String, Box<Evidence>),
+
+    /// Peer `peer ID` finalized block `block hash`
+    #[display(fmt = "Peer {} finalized block {}", _0, _1)]
+    BlockFinalized(String, String),
+
+    /// PBFT replica `peer ID` moved to view `view`
+    #[display(fmt = "Peer {} moved to view {}", _0, _1)]
+    ViewChanged(String, usize),
+}
+
+impl Action {
+    /// name of the event, same as the name of the variant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Action::InitLog => "InitLog",
+            Action::PeerConnected(_) => "PeerConnected",
+            Action::PeerEnroll(_) => "PeerEnroll",
+            Action::MintingAsset(_) => "MintingAsset",
+            Action::DistributingAssets(_, _) => "DistributingAssets",
+            Action::TransactionRequest(_, _) => "TransactionRequest",
+            Action::StakeBroadcast(_, _) => "StakeBroadcast",
+            Action::TransactionValidated(_) => "TransactionValidated",
+            Action::BlockCreation(_) => "BlockCreation",
+            Action::TransactionBroadcasting(_, _) => "TransactionBroadcasting",
+            Action::BlockAdded(_, _) => "BlockAdded",
+            Action::OwnershipChanged(_, _, _) => "OwnershipChanged",
+            Action::ModeChanged(_, _) => "ModeChanged",
+            Action::StakeRequest(_, _) => "StakeRequest",
+            Action::EquivocationDetected(_, _) => "EquivocationDetected",
+            Action::BlockFinalized(_, _) => "BlockFinalized",
+            Action::ViewChanged(_, _) => "ViewChanged",
+        }
+    }
+
+    /// checks if peer `peer_id` is involved in the event
+    pub fn involves(&self, peer_id: &str) -> bool {
+        match self {
+            Action::InitLog => false,
+            Action::PeerConnected(peer)
+            | Action::PeerEnroll(peer)
+            | Action::StakeBroadcast(peer, _)
+            | Action::TransactionValidated(peer) => peer.id == peer_id,
+            Action::MintingAsset(asset) => asset.get_owner().as_deref() == Some(peer_id),
+            Action::DistributingAssets(_, peer) | Action::TransactionRequest(_, peer) => {
+                peer.id == peer_id
+            }
+            Action::BlockCreation(block) => [block.get_tx(), block.get_rx(), block.get_validator()]
+                .iter()
+                .any(|id| id.map(|id| id == peer_id).unwrap_or(false)),
+            Action::TransactionBroadcasting(tx, rx) => tx.id == peer_id || rx.id == peer_id,
+            Action::BlockAdded(peer, block) => {
+                peer == peer_id || Action::BlockCreation(block.clone()).involves(peer_id)
+            }
+            Action::OwnershipChanged(peer, _, owner) => peer == peer_id || owner == peer_id,
+            Action::ModeChanged(peer, _)
+            | Action::StakeRequest(peer, _)
+            | Action::BlockFinalized(peer, _)
+            | Action::ViewChanged(peer, _) => peer == peer_id,
+            Action::EquivocationDetected(peer, evidence) => {
+                peer == peer_id || evidence.offender().map(|id| id == peer_id).unwrap_or(false)
+            }
+        }
+    }
+}
+
+/// Filter for querying events, all conditions must match
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct EventFilter {
+    /// event name, eg: `BlockCreation`
+    pub kind: Option<String>,
+    /// ID of a peer involved in the event
+    pub peer_id: Option<String>,
+    /// only events recorded after event with this ID
+    pub since: Option<usize>,
+}
+
+impl EventFilter {
+    /// checks if `event` matches filter
+    pub fn matches(&self, event: &Command) -> bool {
+        self.kind
+            .as_ref()
+            .map(|kind| kind == event.action.kind())
+            .unwrap_or(true)
+            && self
+                .peer_id
+                .as_ref()
+                .map(|peer_id| event.action.involves(peer_id))
+                .unwrap_or(true)
+            && self.since.map(|since| event.id > since).unwrap_or(true)
+    }
+}
+
+/// Event journal of a peer
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct EventLog {
+    pub events: Vec<Command>,
+    /// live subscriptions, events matching the filter are
+    /// sent to the subscriber as they are recorded
+    #[serde(skip)]
+    pub subscribers: Vec<(EventFilter, UnboundedSender<Command>)>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        let mut log = EventLog {
+            events: Vec::new(),
+            subscribers: Vec::new(),
+        };
+        log.log(Action::InitLog);
+        log
+    }
+}
+
+impl EventLog {
+    /// record an event
+    pub fn log(&mut self, action: Action) {
+        let mut event = Command::new(action);
+        event.id = self.events.len();
+
+        // subscribers that hung up are removed
+        self.subscribers.retain(|(filter, subscriber)| {
+            if filter.matches(&event) {
+                subscriber.unbounded_send(event.clone()).is_ok()
+            } else {
+                !subscriber.is_closed()
+            }
+        });
+        self.events.push(event);
+    }
+
+    /// serializes an event as a Server-Sent Event
+    pub fn to_sse(event: &Command) -> String {
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            event.id,
+            event.action.kind(),
+            serde_json::to_string(event).unwrap()
+        )
+    }
+
+    /// serializes events as JSON Lines, one event per line
+    pub fn to_json_lines(events: &[Command]) -> String {
+        events
+            .iter()
+            .map(|event| serde_json::to_string(event).unwrap() + "\n")
+            .collect()
+    }
+}
+
+impl Actor for EventLog {
+    type Context = Context<Self>;
+}
+
+/// Record an event
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct LogEvent(pub Action);
+
+/// Get events matching filter
+#[derive(Message)]
+#[rtype(result = "Vec<Command>")]
+pub struct QueryEvents(pub EventFilter);
+
+/// Subscribe to events matching filter. When `since` is set,
+/// matching events that were already recorded are sent first
+#[derive(Message)]
+#[rtype(result = "UnboundedReceiver<Command>")]
+pub struct Subscribe(pub EventFilter);
+
+impl Handler<LogEvent> for EventLog {
+    type Result = ();
+
+    fn handle(&mut self, msg: LogEvent, _ctx: &mut Self::Context) -> Self::Result {
+        self.log(msg.0);
+    }
+}
+
+impl Handler<QueryEvents> for EventLog {
+    type Result = MessageResult<QueryEvents>;
+
+    fn handle(&mut self, msg: QueryEvents, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.events
+                .iter()
+                .filter(|event| msg.0.matches(event))
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+impl Handler<Subscribe> for EventLog {
+    type Result = MessageResult<Subscribe>;
+
+    fn handle(&mut self, msg: Subscribe, _ctx: &mut Self::Context) -> Self::Result {
+        let (subscriber, events) = unbounded();
+        if msg.0.since.is_some() {
+            self.events
+                .iter()
+                .filter(|event| msg.0.matches(event))
+                .for_each(|event| subscriber.unbounded_send(event.clone()).unwrap());
+        }
+        self.subscribers.push((msg.0, subscriber));
+        MessageResult(events)
+    }
+}
+
+/// responds with a Server-Sent Events stream of events matching `filter`
+pub async fn stream_events(event_addr: &Addr<EventLog>, filter: EventFilter) -> HttpResponse {
+    let events = event_addr.send(Subscribe(filter)).await.unwrap();
+    let events =
+        events.map(|event| Ok::<_, actix_web::Error>(Bytes::from(EventLog::to_sse(&event))));
+    // compression buffers the stream, events wouldn't reach clients in time
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .encoding(ContentEncoding::Identity)
+        .header("Cache-Control", "no-cache")
+        .streaming(events)
+}
+
+/// events that explain how `old` snapshots of peers changed to `new`:
+/// blocks that peers added and assets that changed owners.
+/// Peers that aren't in `old` aren't reported
+pub fn changes(old: &[Status], new: &[Status]) -> Vec<Action> {
+    let mut actions = Vec::new();
+    for state in new.iter() {
+        let prev = match old.iter().find(|prev| prev.peer_id == state.peer_id) {
+            Some(prev) => prev,
+            None => continue,
+        };
+
+        state
+            .chain
+            .iter()
+            .filter(|block| {
+                !prev
+                    .chain
+                    .iter()
+                    .any(|seen| seen.get_hash() == block.get_hash())
+            })
+            .for_each(|block| {
+                actions.push(Action::BlockAdded(state.peer_id.clone(), block.clone()))
+            });
+
+        for asset in state.asset.iter() {
+            let owner = match asset.get_owner() {
+                Some(owner) => owner,
+                None => continue,
+            };
+            let changed = prev
+                .asset
+                .iter()
+                .find(|seen| seen.get_hash() == asset.get_hash())
+                .map(|seen| seen.get_owner().as_ref() != Some(owner))
+                .unwrap_or(true);
+            if changed {
+                actions.push(Action::OwnershipChanged(
+                    state.peer_id.clone(),
+                    asset.get_hash().into(),
+                    owner.clone(),
+                ));
+            }
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::block::BlockBuilder;
+    use crate::chain::Chain;
+
+    fn peer(id: &str) -> Peer {
+        Peer {
+            id: id.into(),
+            ip: "localhost:7001".into(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn event_log_works() {
+        let event_addr = EventLog::default().start();
+
+        let chain = Chain::new("test");
+        let block = BlockBuilder::default()
+            .set_tx("victim")
+            .set_rx("attacker")
+            .set_asset_id("asset")
+            .set_validator("normal")
+            .set_prev(chain.get_last_block())
+            .build();
+
+        event_addr
+            .send(LogEvent(Action::PeerEnroll(peer("victim"))))
+            .await
+            .unwrap();
+        event_addr
+            .send(LogEvent(Action::BlockCreation(block)))
+            .await
+            .unwrap();
+        event_addr
+            .send(LogEvent(Action::TransactionBroadcasting(
+                peer("normal"),
+                peer("attacker"),
+            )))
+            .await
+            .unwrap();
+
+        let all = event_addr
+            .send(QueryEvents(EventFilter::default()))
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 4, "init log recorded");
+        assert_eq!(all[0].action.kind(), "InitLog");
+        assert!(all.iter().enumerate().all(|(i, event)| event.id == i));
+
+        let filter = EventFilter {
+            kind: Some("BlockCreation".into()),
+            ..EventFilter::default()
+        };
+        let blocks = event_addr.send(QueryEvents(filter)).await.unwrap();
+        assert_eq!(blocks.len(), 1, "kind filter works");
+
+        let filter = EventFilter {
+            peer_id: Some("attacker".into()),
+            ..EventFilter::default()
+        };
+        let attacker = event_addr.send(QueryEvents(filter)).await.unwrap();
+        assert_eq!(attacker.len(), 2, "peer filter works");
+
+        let filter = EventFilter {
+            peer_id: Some("victim".into()),
+            since: Some(1),
+            ..EventFilter::default()
+        };
+        let victim = event_addr.send(QueryEvents(filter)).await.unwrap();
+        assert_eq!(victim.len(), 1, "since filter works");
+
+        let lines = EventLog::to_json_lines(&all);
+        assert_eq!(lines.lines().count(), all.len());
+        lines.lines().for_each(|line| {
+            let _: Command = serde_json::from_str(line).unwrap();
+        });
+    }
+
+    #[actix_rt::test]
+    async fn subscribe_works() {
+        let event_addr = EventLog::default().start();
+        let filter = EventFilter {
+            peer_id: Some("victim".into()),
+            ..EventFilter::default()
+        };
+        let mut events = event_addr.send(Subscribe(filter.clone())).await.unwrap();
+
+        event_addr
+            .send(LogEvent(Action::PeerEnroll(peer("attacker"))))
+            .await
+            .unwrap();
+        event_addr
+            .send(LogEvent(Action::ModeChanged(
+                "victim".into(),
+                Mode::Victim(true),
+            )))
+            .await
+            .unwrap();
+        let event = events.next().await.unwrap();
+        assert_eq!(event.id, 2, "only matching events are streamed");
+        assert_eq!(event.action.kind(), "ModeChanged");
+
+        // late subscribers catch up with `since`
+        let since = EventFilter {
+            since: Some(0),
+            ..filter
+        };
+        let mut late = event_addr.send(Subscribe(since)).await.unwrap();
+        assert_eq!(late.next().await.unwrap().id, 2, "since replays events");
+
+        drop(events);
+        event_addr
+            .send(LogEvent(Action::StakeRequest("victim".into(), 3)))
+            .await
+            .unwrap();
+        assert_eq!(late.next().await.unwrap().action.kind(), "StakeRequest");
+        let all = event_addr
+            .send(QueryEvents(EventFilter::default()))
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 4);
+
+        let sse = EventLog::to_sse(&all[3]);
+        assert!(sse.starts_with("id: 3\nevent: StakeRequest\ndata: {"));
+        assert!(sse.ends_with("}\n\n"));
+    }
+
+    #[test]
+    fn changes_works() {
+        use crate::asset::AssetLedger;
+        use crate::payload::StatusBuilder;
+
+        let status = |chain: &Chain, assets: &AssetLedger| {
+            StatusBuilder::default()
+                .peer_id("victim".into())
+                .asset(assets.assets.clone())
+                .chain(chain.blocks())
+                .tampered_assets(None)
+                .tampered_chain(None)
+                .build()
+                .unwrap()
+        };
+
+        let mut chain = Chain::new("test");
+        let mut assets = AssetLedger::generate("auditor");
+        let old = vec![status(&chain, &assets)];
+        assert!(changes(&[], &old).is_empty(), "new peers aren't reported");
+        assert!(changes(&old, &old).is_empty(), "no changes");
+
+        let asset_id = assets.assets[0].get_hash().to_owned();
+        let block = BlockBuilder::default()
+            .set_tx("auditor")
+            .set_rx("victim")
+            .set_asset_id(&asset_id)
+            .set_validator("auditor")
+            .set_prev(chain.get_last_block())
+            .build();
+        chain.add_block(block, 3).unwrap();
+        assets.assets[0].set_owner("victim");
+
+        let actions = changes(&old, &[status(&chain, &assets)]);
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].kind(), "BlockAdded");
+        match &actions[1] {
+            Action::OwnershipChanged(peer_id, id, owner) => {
+                assert_eq!(peer_id, "victim");
+                assert_eq!(id, &asset_id);
+                assert_eq!(owner, "victim");
+            }
+            _ => panic!("ownership change not reported"),
+        }
+    }
 }
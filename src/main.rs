@@ -98,6 +98,7 @@ async fn main() -> std::io::Result<()> {
 
     let clone_config = config.clone();
     let sync_fut = clone_config.sync();
+    let detect_fut = clone_config.detect();
 
     let server_fut = HttpServer::new(move || {
         let log = &format!(
@@ -117,7 +118,7 @@ async fn main() -> std::io::Result<()> {
     .unwrap()
     .run();
 
-    futures::join!(server_fut, sync_fut);
+    futures::join!(server_fut, sync_fut, detect_fut);
     Ok(())
 }
 
@@ -20,7 +20,8 @@ use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
 use crate::asset::Asset;
-use crate::block::Block;
+use crate::block::{Block, TxKind};
+use crate::finality::Vote;
 
 /// Represents a peer
 #[derive(Deserialize, Display, Serialize, Clone, Debug, Default)]
@@ -33,6 +34,34 @@ pub struct Peer {
     //pub balance: Option<u64>,
 }
 
+/// Peer enrollment payload
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct Enrollment {
+    #[serde(flatten)]
+    pub peer: Peer,
+    /// proof of work, required when auditor enforces
+    /// [EnrollPolicy](crate::discovery::EnrollPolicy).pow_difficulty
+    #[serde(default)]
+    pub nonce: u64,
+}
+
+/// Sybil attack payload
+#[derive(Deserialize, Serialize)]
+pub struct Sybil {
+    /// number of fake identities to enroll
+    pub count: usize,
+}
+
+/// Long-range attack payload
+#[derive(Deserialize, Serialize)]
+pub struct LongRange {
+    /// serial number of the block from which the alternative
+    /// history is built
+    pub from: usize,
+    /// number of blocks in the alternative history
+    pub length: usize,
+}
+
 #[derive(Deserialize, Display, Serialize, Clone, Debug, Default)]
 #[display(fmt = "from {} to {}", tx, rx)]
 pub struct Gossip {
@@ -41,27 +70,190 @@ pub struct Gossip {
 }
 
 /// Transaction payload
-#[derive(Deserialize, Builder, Clone, Serialize)]
+#[derive(Deserialize, Builder, Clone, Debug, PartialEq, Serialize)]
 pub struct Tx {
-    /// asset ID
+    /// asset ID, empty for token transfers
     pub asset_id: String,
     /// buyer peer ID
     pub buyer_peer_id: String,
+    /// type of transaction
+    #[builder(default)]
+    #[serde(default)]
+    pub kind: TxKind,
+    /// output spent by the transaction, UTXO ledgers only. The
+    /// asset's unspent output is used when not set
+    #[builder(default)]
+    #[serde(default)]
+    pub input: Option<String>,
+}
+
+/// Token mint payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Mint {
+    /// peer ID of recipient
+    pub peer_id: String,
+    pub amount: u64,
+}
+
+/// Token transfer payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TokenTransfer {
+    /// peer ID of recipient
+    pub to: String,
+    pub amount: u64,
+    /// fee paid to the validator
+    #[serde(default)]
+    pub fee: u64,
+}
+
+/// Asset purchase payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Buy {
+    pub asset_id: String,
+    /// tokens paid to the owner of the asset
+    pub price: u64,
+    /// fee paid to the validator
+    #[serde(default)]
+    pub fee: u64,
+}
+
+/// Price asked by the owner of an asset
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Ask {
+    pub asset_id: String,
+    /// tokens the owner wants for the asset
+    pub price: u64,
+}
+
+/// Offer to buy an asset, sent by the buyer to the owner of the asset
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Offer {
+    pub asset_id: String,
+    pub buyer_peer_id: String,
+    /// tokens paid to the owner of the asset
+    pub price: u64,
+    /// fee paid to the validator
+    #[serde(default)]
+    pub fee: u64,
+}
+
+/// Asset split payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Split {
+    pub asset_id: String,
+    /// values of the assets created, must add up to the asset's value
+    pub values: Vec<usize>,
+}
+
+/// Asset merge payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Merge {
+    /// assets to merge, must have the same owner
+    pub asset_ids: Vec<String>,
+}
+
+/// Asset burn payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Burn {
+    pub asset_id: String,
+}
+
+/// Asset bond and unbond payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Bond {
+    pub asset_id: String,
+}
+
+/// Asset mint payload
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AssetMint {
+    /// peer ID of recipient
+    pub peer_id: String,
+    pub name: String,
+    pub value: usize,
+}
+
+/// Asset supply and the supply policy of a peer
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Supply {
+    /// total value of unspent assets
+    pub supply: usize,
+    /// total value of assets minted at runtime
+    pub minted: usize,
+    pub cap: Option<usize>,
+    /// maximum value that can be minted per block
+    pub issuance: Option<usize>,
+}
+
+/// Last final block of a peer and the votes it received
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct FinalityStatus {
+    pub finalized: Block,
+    pub votes: Vec<Vote>,
+}
+
+/// Token balance of a peer
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Balance {
+    pub peer_id: String,
+    pub balance: u64,
+}
+
+/// Query parameter that peers use to identify themselves
+/// when requesting ledgers from other peers
+#[derive(Deserialize, Serialize, Default)]
+pub struct Requester {
+    /// requesting peer's ID
+    pub requester: Option<String>,
+}
+
+/// Query parameters of asset listing, eg: `?owner=victim.batsense.net`
+#[derive(Deserialize, Serialize, Default)]
+pub struct AssetQuery {
+    /// only list assets owned by this peer
+    pub owner: Option<String>,
 }
 
 /// Get stake payload
 #[derive(Deserialize, Serialize)]
 pub struct GetStake {
     pub block_id: usize,
+    /// ID of the peer whose stake is requested
+    #[serde(default)]
+    pub peer_id: Option<String>,
 }
 
 /// Transaction payload
-#[derive(Deserialize, Builder, Serialize)]
+#[derive(Deserialize, Builder, Clone, Debug, PartialEq, Serialize)]
 pub struct ValidateTx {
     /// Transaction request
     pub tx: Tx,
     /// seller peer ID
     pub seller_peer_id: String,
+    /// number of transactions sent by seller, used for replay protection
+    #[builder(default)]
+    #[serde(default)]
+    pub nonce: Option<usize>,
+    /// hash of the genesis block of the chain that the transaction
+    /// is meant for, used for replay protection
+    #[builder(default)]
+    #[serde(default)]
+    pub chain_id: Option<String>,
+}
+
+/// Share of blocks validated by a peer compared with
+/// its share of stake
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Share {
+    pub peer_id: String,
+    /// number of blocks validated by peer
+    pub blocks: usize,
+    /// fraction of blocks validated by peer
+    pub block_share: f64,
+    /// number of assets owned by peer
+    pub stake: usize,
+    /// fraction of assets owned by peer
+    pub stake_share: f64,
 }
 
 /// Transaction payload
@@ -0,0 +1,871 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! PBFT consensus for small permissioned networks. Replicas, all peers
+//! and the auditor, order transactions in three rounds:
+//!
+//! 1. the primary proposes a block for the next serial number with a
+//!    [PbftMessage::PrePrepare]
+//! 2. replicas that accept the proposal send a [PbftMessage::Prepare]
+//! 3. replicas that received a [quorum] of matching prepares send a
+//!    [PbftMessage::Commit], and add the block to their chain once they
+//!    received a quorum of matching commits
+//!
+//! where a quorum is `n - f` of the `n` replicas and `f` is the number
+//! of faulty replicas tolerated, see [faults].
+//! The primary rotates every block. When a request isn't executed in
+//! time, replicas ask for the next view with a [PbftMessage::ViewChange],
+//! which moves the primary to the next replica. Blocks that replicas
+//! prepared are carried in view changes and proposed again by the new
+//! primary, so blocks that might have been committed aren't replaced.
+//!
+//! Messages aren't signed, so replicas record the messages they send
+//! and a message from a replica is only handled once the replica
+//! confirms it sent it, see [HasSent]. Committed blocks that don't
+//! extend the chain of a replica yet, because it missed earlier blocks,
+//! are queued until it catches up, see [Pbft::next_block].
+//!
+//! The [Pbft] actor only keeps protocol state, it returns the [Step]s
+//! that a replica should take after handling a message.
+//!
+//! # [Pbft] actor supports the following messages:
+//! - [Receive]: Handle a message from a client or replica
+//! - [NextProposal]: Get the next request to propose as primary
+//! - [DropRequest]: Drop a request that can't be executed
+//! - [Timeout]: Start a view change if a request wasn't executed in time
+//! - [GetPbftStatus]: Get current view and pending requests
+//! - [RecordSent]: Record a message sent by this replica
+//! - [HasSent]: Check if this replica sent a message
+//! - [QueueBlock]: Queue a committed block for execution
+//! - [NextBlock]: Get the next queued block that extends the chain
+
+use std::collections::{BTreeMap, HashMap};
+
+use actix::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::payload::ValidateTx;
+use crate::utils::hasher;
+
+/// milliseconds replicas wait for a request to be executed before
+/// changing view, when not set
+pub const DEFAULT_TIMEOUT: u64 = 5000;
+
+/// number of faulty replicas tolerated by `n` replicas
+pub fn faults(n: usize) -> usize {
+    n.saturating_sub(1) / 3
+}
+
+/// number of matching messages required to prepare and commit a block
+/// or to change view, `n - f`. Any two quorums share at least `f + 1`
+/// replicas, so at least one honest replica, even when `n` isn't
+/// `3f + 1`
+pub fn quorum(n: usize) -> usize {
+    n - faults(n)
+}
+
+/// primary of `view` for serial number `seq`: the primary rotates every
+/// block and moves on to the next replica on every view change
+pub fn primary(replicas: &[String], view: usize, seq: usize) -> Option<&String> {
+    if replicas.is_empty() {
+        None
+    } else {
+        replicas.get((view + seq) % replicas.len())
+    }
+}
+
+/// digest of a client request
+pub fn digest(request: &ValidateTx) -> String {
+    hasher(&serde_json::to_string(request).unwrap())
+}
+
+/// Block proposed for serial number `seq` and the request it executes
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Proposal {
+    pub seq: usize,
+    pub block: Block,
+    pub request: ValidateTx,
+}
+
+/// Messages exchanged by replicas
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PbftMessage {
+    /// transaction submitted by a client, sent to all replicas
+    Request(ValidateTx),
+    /// block proposed by the primary
+    PrePrepare {
+        view: usize,
+        replica: String,
+        proposal: Box<Proposal>,
+    },
+    /// replica accepted the block `hash` for serial number `seq`
+    Prepare {
+        view: usize,
+        seq: usize,
+        hash: String,
+        replica: String,
+    },
+    /// replica saw a [quorum] of prepares for block `hash`
+    Commit {
+        view: usize,
+        seq: usize,
+        hash: String,
+        replica: String,
+    },
+    /// replica asks to move to `view`, carrying the block it prepared
+    /// but didn't commit yet
+    ViewChange {
+        view: usize,
+        replica: String,
+        prepared: Option<Box<Proposal>>,
+    },
+}
+
+impl PbftMessage {
+    /// replica that sent the message, `None` for client requests
+    pub fn replica(&self) -> Option<&String> {
+        match self {
+            PbftMessage::Request(_) => None,
+            PbftMessage::PrePrepare { replica, .. }
+            | PbftMessage::Prepare { replica, .. }
+            | PbftMessage::Commit { replica, .. }
+            | PbftMessage::ViewChange { replica, .. } => Some(replica),
+        }
+    }
+
+    /// view the message was sent in, `None` for client requests
+    pub fn view(&self) -> Option<usize> {
+        match self {
+            PbftMessage::Request(_) => None,
+            PbftMessage::PrePrepare { view, .. }
+            | PbftMessage::Prepare { view, .. }
+            | PbftMessage::Commit { view, .. }
+            | PbftMessage::ViewChange { view, .. } => Some(*view),
+        }
+    }
+
+    /// digest of the message
+    pub fn digest(&self) -> String {
+        hasher(&serde_json::to_string(self).unwrap())
+    }
+}
+
+/// Actions a replica takes after handling a message, in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// send message to all replicas, including this one
+    Broadcast(PbftMessage),
+    /// add committed block to the chain
+    Execute(Box<Proposal>),
+    /// moved to view
+    NewView(usize),
+    /// start view change timer for request
+    Timer(String),
+    /// propose the next request if this replica is the primary
+    Propose,
+}
+
+/// Messages received for a serial number in a view
+#[derive(Clone, Debug, Default)]
+struct Slot {
+    proposal: Option<Proposal>,
+    /// block hash every replica prepared
+    prepares: HashMap<String, String>,
+    /// block hash every replica committed
+    commits: HashMap<String, String>,
+    sent_prepare: bool,
+    sent_commit: bool,
+    committed: bool,
+}
+
+impl Slot {
+    fn count(votes: &HashMap<String, String>, hash: &str) -> usize {
+        votes.values().filter(|voted| *voted == hash).count()
+    }
+}
+
+/// PBFT state of a replica
+#[derive(Clone, Debug, Default)]
+pub struct Pbft {
+    pub view: usize,
+    /// requests that weren't executed yet, oldest first
+    pub requests: Vec<ValidateTx>,
+    /// keyed by view and serial number
+    slots: HashMap<(usize, usize), Slot>,
+    /// view changes received for every view, with the blocks they carry
+    view_changes: HashMap<usize, HashMap<String, Option<Box<Proposal>>>>,
+    /// highest view this replica asked for
+    requested_view: usize,
+    /// set while this replica's proposal is being ordered
+    proposing: bool,
+    /// prepared block carried over by view changes
+    carried: Option<Proposal>,
+    /// digests of messages this replica sent and the views they were
+    /// sent in
+    sent: HashMap<String, usize>,
+    /// committed blocks waiting to be executed, by serial number
+    queued: BTreeMap<usize, Proposal>,
+}
+
+impl Pbft {
+    /// records `request`, returns false if it was recorded before
+    pub fn add_request(&mut self, request: ValidateTx) -> bool {
+        let request_digest = digest(&request);
+        if self
+            .requests
+            .iter()
+            .any(|pending| digest(pending) == request_digest)
+        {
+            false
+        } else {
+            self.requests.push(request);
+            true
+        }
+    }
+
+    /// drops request `request_digest`, if it was being proposed the
+    /// next request can be proposed
+    pub fn drop_request(&mut self, request_digest: &str) {
+        self.requests
+            .retain(|request| digest(request) != request_digest);
+        self.proposing = false;
+    }
+
+    /// block this replica prepared but didn't commit, with the highest
+    /// serial number
+    fn prepared(&self) -> Option<Box<Proposal>> {
+        self.slots
+            .values()
+            .filter(|slot| slot.sent_commit && !slot.committed)
+            .filter_map(|slot| slot.proposal.clone())
+            .max_by_key(|proposal| proposal.seq)
+            .map(Box::new)
+    }
+
+    /// request to propose for serial number `seq` as primary and the
+    /// block carried over by view changes for it, if any. Returns `None`
+    /// while a proposal of this replica is being ordered
+    pub fn next_proposal(&mut self, seq: usize) -> Option<(ValidateTx, Option<Block>)> {
+        if self.proposing {
+            return None;
+        }
+        let next = match self.carried.take() {
+            Some(carried) if carried.seq == seq => Some((carried.request, Some(carried.block))),
+            _ => self.requests.first().map(|request| (request.clone(), None)),
+        };
+        self.proposing = next.is_some();
+        next
+    }
+
+    /// records message `msg` sent by this replica
+    pub fn record_sent(&mut self, msg: &PbftMessage) {
+        if let Some(view) = msg.view() {
+            self.sent.insert(msg.digest(), view);
+        }
+    }
+
+    /// checks if this replica sent message `msg_digest`
+    pub fn has_sent(&self, msg_digest: &str) -> bool {
+        self.sent.contains_key(msg_digest)
+    }
+
+    /// queues committed block `proposal` until it extends the chain
+    pub fn queue_block(&mut self, proposal: Proposal) {
+        self.queued.entry(proposal.seq).or_insert(proposal);
+    }
+
+    /// removes the queued block for serial number `seq` if it extends
+    /// the chain tip `tip`. Blocks for earlier serial numbers are
+    /// dropped, the chain already has blocks for them
+    pub fn next_block(&mut self, seq: usize, tip: &str) -> Option<Proposal> {
+        self.queued = self.queued.split_off(&seq);
+        match self.queued.get(&seq) {
+            Some(proposal) if proposal.block.get_prev().map(|prev| prev.as_str()) == Some(tip) => {
+                self.queued.remove(&seq)
+            }
+            _ => None,
+        }
+    }
+
+    /// checks if prepares and commits of slot `key` are enough to
+    /// commit or execute its block
+    fn advance(&mut self, key: (usize, usize), me: &str, quorum: usize) -> Vec<Step> {
+        let mut steps = Vec::new();
+        if key.0 != self.view {
+            return steps;
+        }
+        let slot = match self.slots.get_mut(&key) {
+            Some(slot) => slot,
+            None => return steps,
+        };
+        let proposal = match slot.proposal.as_ref() {
+            Some(proposal) => proposal.clone(),
+            None => return steps,
+        };
+        let hash = proposal.block.get_hash().to_owned();
+
+        if !slot.sent_prepare {
+            slot.sent_prepare = true;
+            steps.push(Step::Broadcast(PbftMessage::Prepare {
+                view: key.0,
+                seq: key.1,
+                hash: hash.clone(),
+                replica: me.into(),
+            }));
+        }
+        if !slot.sent_commit && Slot::count(&slot.prepares, &hash) >= quorum {
+            slot.sent_commit = true;
+            steps.push(Step::Broadcast(PbftMessage::Commit {
+                view: key.0,
+                seq: key.1,
+                hash: hash.clone(),
+                replica: me.into(),
+            }));
+        }
+        if slot.sent_commit && !slot.committed && Slot::count(&slot.commits, &hash) >= quorum {
+            slot.committed = true;
+            self.drop_request(&digest(&proposal.request));
+            steps.push(Step::Execute(Box::new(proposal)));
+            steps.push(Step::Propose);
+        }
+        steps
+    }
+
+    /// moves to `view`: the primary changes and blocks prepared by
+    /// replicas are carried over
+    fn enter_view(&mut self, view: usize, me: &str, quorum: usize) -> Vec<Step> {
+        self.carried = self.view_changes.get(&view).and_then(|changes| {
+            changes
+                .values()
+                .flatten()
+                .max_by_key(|proposal| proposal.seq)
+                .map(|proposal| *proposal.clone())
+        });
+        self.view = view;
+        self.requested_view = self.requested_view.max(view);
+        self.proposing = false;
+        self.view_changes.retain(|v, _| *v > view);
+        // messages of earlier views are ignored, replicas don't ask for them
+        self.sent.retain(|_, v| *v >= view);
+
+        let mut steps = vec![Step::NewView(view)];
+        steps.extend(
+            self.requests
+                .iter()
+                .map(|request| Step::Timer(digest(request))),
+        );
+        let mut keys: Vec<(usize, usize)> = self
+            .slots
+            .keys()
+            .filter(|(v, _)| *v == view)
+            .cloned()
+            .collect();
+        keys.sort_unstable();
+        for key in keys {
+            steps.extend(self.advance(key, me, quorum));
+        }
+        steps.push(Step::Propose);
+        steps
+    }
+
+    /// handles `msg` as replica `me` of `replicas`, returns the steps to
+    /// take. Messages from replicas that aren't in `replicas` are ignored
+    pub fn receive(&mut self, msg: PbftMessage, replicas: &[String], me: &str) -> Vec<Step> {
+        let quorum = quorum(replicas.len());
+        let is_replica = |replica: &String| replicas.contains(replica);
+
+        match msg {
+            PbftMessage::Request(request) => {
+                let request_digest = digest(&request);
+                if self.add_request(request) {
+                    vec![Step::Timer(request_digest), Step::Propose]
+                } else {
+                    Vec::new()
+                }
+            }
+            PbftMessage::PrePrepare {
+                view,
+                replica,
+                proposal,
+            } => {
+                if view < self.view || primary(replicas, view, proposal.seq) != Some(&replica) {
+                    return Vec::new();
+                }
+                let key = (view, proposal.seq);
+                let slot = self.slots.entry(key).or_default();
+                // first proposal wins, primaries can't change their mind
+                if slot.proposal.is_some() {
+                    return Vec::new();
+                }
+                slot.proposal = Some(*proposal.clone());
+
+                let mut steps = Vec::new();
+                let request_digest = digest(&proposal.request);
+                if self.add_request(proposal.request) {
+                    steps.push(Step::Timer(request_digest));
+                }
+                steps.extend(self.advance(key, me, quorum));
+                steps
+            }
+            PbftMessage::Prepare {
+                view,
+                seq,
+                hash,
+                replica,
+            } => {
+                if view < self.view || !is_replica(&replica) {
+                    return Vec::new();
+                }
+                let slot = self.slots.entry((view, seq)).or_default();
+                slot.prepares.entry(replica).or_insert(hash);
+                self.advance((view, seq), me, quorum)
+            }
+            PbftMessage::Commit {
+                view,
+                seq,
+                hash,
+                replica,
+            } => {
+                if view < self.view || !is_replica(&replica) {
+                    return Vec::new();
+                }
+                let slot = self.slots.entry((view, seq)).or_default();
+                slot.commits.entry(replica).or_insert(hash);
+                self.advance((view, seq), me, quorum)
+            }
+            PbftMessage::ViewChange {
+                view,
+                replica,
+                prepared,
+            } => {
+                if view <= self.view || !is_replica(&replica) {
+                    return Vec::new();
+                }
+                let changes = self.view_changes.entry(view).or_default();
+                changes.entry(replica).or_insert(prepared);
+                let count = changes.len();
+
+                let mut steps = Vec::new();
+                // replicas join view changes that a faulty replica
+                // couldn't have started on its own
+                if count > faults(replicas.len()) && self.requested_view < view {
+                    self.requested_view = view;
+                    steps.push(Step::Broadcast(PbftMessage::ViewChange {
+                        view,
+                        replica: me.into(),
+                        prepared: self.prepared(),
+                    }));
+                }
+                if count >= quorum {
+                    steps.extend(self.enter_view(view, me, quorum));
+                }
+                steps
+            }
+        }
+    }
+
+    /// view change message to send when request `request_digest`
+    /// wasn't executed in `view`. Replicas ask for a view once
+    pub fn timeout(&mut self, view: usize, request_digest: &str, me: &str) -> Option<PbftMessage> {
+        let pending = self
+            .requests
+            .iter()
+            .any(|request| digest(request) == request_digest);
+        if !pending || self.view != view || self.requested_view > view {
+            return None;
+        }
+        self.requested_view = view + 1;
+        Some(PbftMessage::ViewChange {
+            view: view + 1,
+            replica: me.into(),
+            prepared: self.prepared(),
+        })
+    }
+}
+
+impl Actor for Pbft {
+    type Context = Context<Self>;
+}
+
+/// Current view and pending requests of a replica
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PbftStatus {
+    pub view: usize,
+    pub requests: Vec<ValidateTx>,
+    /// serial numbers of committed blocks waiting for earlier blocks
+    pub queued: Vec<usize>,
+}
+
+/// Handle a message from a client or replica, see [Pbft::receive]
+#[derive(Message)]
+#[rtype(result = "Vec<Step>")]
+pub struct Receive {
+    pub msg: PbftMessage,
+    /// IDs of replicas, sorted
+    pub replicas: Vec<String>,
+    /// ID of this replica
+    pub me: String,
+}
+
+/// Get the next request to propose for serial number `0` as primary,
+/// see [Pbft::next_proposal]
+#[derive(Message)]
+#[rtype(result = "Option<(ValidateTx, Option<Block>)>")]
+pub struct NextProposal(pub usize);
+
+/// Drop a request that can't be executed, by digest
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DropRequest(pub String);
+
+/// Start a view change if a request wasn't executed in a view,
+/// see [Pbft::timeout]
+#[derive(Message)]
+#[rtype(result = "Option<PbftMessage>")]
+pub struct Timeout {
+    pub view: usize,
+    pub digest: String,
+    pub me: String,
+}
+
+/// Get current view and pending requests
+#[derive(Message)]
+#[rtype(result = "PbftStatus")]
+pub struct GetPbftStatus;
+
+/// Record a message sent by this replica, see [Pbft::record_sent]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordSent(pub PbftMessage);
+
+/// Check if this replica sent a message, by digest
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct HasSent(pub String);
+
+/// Queue a committed block for execution, see [Pbft::queue_block]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QueueBlock(pub Box<Proposal>);
+
+/// Get the next queued block that extends the chain, see
+/// [Pbft::next_block]
+#[derive(Message)]
+#[rtype(result = "Option<Proposal>")]
+pub struct NextBlock {
+    pub seq: usize,
+    pub tip: String,
+}
+
+impl Handler<Receive> for Pbft {
+    type Result = MessageResult<Receive>;
+
+    fn handle(&mut self, msg: Receive, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.receive(msg.msg, &msg.replicas, &msg.me))
+    }
+}
+
+impl Handler<NextProposal> for Pbft {
+    type Result = MessageResult<NextProposal>;
+
+    fn handle(&mut self, msg: NextProposal, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.next_proposal(msg.0))
+    }
+}
+
+impl Handler<DropRequest> for Pbft {
+    type Result = ();
+
+    fn handle(&mut self, msg: DropRequest, _ctx: &mut Self::Context) -> Self::Result {
+        self.drop_request(&msg.0);
+    }
+}
+
+impl Handler<Timeout> for Pbft {
+    type Result = MessageResult<Timeout>;
+
+    fn handle(&mut self, msg: Timeout, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.timeout(msg.view, &msg.digest, &msg.me))
+    }
+}
+
+impl Handler<GetPbftStatus> for Pbft {
+    type Result = MessageResult<GetPbftStatus>;
+
+    fn handle(&mut self, _msg: GetPbftStatus, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(PbftStatus {
+            view: self.view,
+            requests: self.requests.clone(),
+            queued: self.queued.keys().cloned().collect(),
+        })
+    }
+}
+
+impl Handler<RecordSent> for Pbft {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordSent, _ctx: &mut Self::Context) -> Self::Result {
+        self.record_sent(&msg.0);
+    }
+}
+
+impl Handler<HasSent> for Pbft {
+    type Result = bool;
+
+    fn handle(&mut self, msg: HasSent, _ctx: &mut Self::Context) -> Self::Result {
+        self.has_sent(&msg.0)
+    }
+}
+
+impl Handler<QueueBlock> for Pbft {
+    type Result = ();
+
+    fn handle(&mut self, msg: QueueBlock, _ctx: &mut Self::Context) -> Self::Result {
+        self.queue_block(*msg.0);
+    }
+}
+
+impl Handler<NextBlock> for Pbft {
+    type Result = MessageResult<NextBlock>;
+
+    fn handle(&mut self, msg: NextBlock, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.next_block(msg.seq, &msg.tip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::{TxBuilder, ValidateTxBuilder};
+
+    fn request() -> ValidateTx {
+        let tx = TxBuilder::default()
+            .asset_id("asset".into())
+            .buyer_peer_id("d".into())
+            .build()
+            .unwrap();
+        ValidateTxBuilder::default()
+            .tx(tx)
+            .seller_peer_id("a".into())
+            .build()
+            .unwrap()
+    }
+
+    /// delivers broadcasts between replicas that aren't `silent` until
+    /// there's nothing left to deliver, returns executed proposals
+    fn deliver(
+        replicas: &mut HashMap<String, Pbft>,
+        ids: &[String],
+        silent: &str,
+        mut queue: Vec<PbftMessage>,
+    ) -> Vec<(String, usize)> {
+        let mut executed = Vec::new();
+        while let Some(msg) = queue.pop() {
+            for id in ids.iter().filter(|id| *id != silent) {
+                let steps = replicas.get_mut(id).unwrap().receive(msg.clone(), ids, id);
+                for step in steps.into_iter() {
+                    match step {
+                        Step::Broadcast(msg) => queue.push(msg),
+                        Step::Execute(proposal) => executed.push((id.clone(), proposal.seq)),
+                        _ => (),
+                    }
+                }
+            }
+        }
+        executed
+    }
+
+    fn propose(replicas: &mut HashMap<String, Pbft>, primary: &str, view: usize) -> PbftMessage {
+        let (request, _) = replicas.get_mut(primary).unwrap().next_proposal(5).unwrap();
+        PbftMessage::PrePrepare {
+            view,
+            replica: primary.into(),
+            proposal: Box::new(Proposal {
+                seq: 5,
+                block: Block::default(),
+                request,
+            }),
+        }
+    }
+
+    #[test]
+    fn quorum_works() {
+        assert_eq!(faults(3), 0);
+        assert_eq!(faults(4), 1);
+        assert_eq!(quorum(3), 3);
+        assert_eq!(quorum(4), 3);
+        assert_eq!(quorum(5), 4);
+        assert_eq!(quorum(6), 5);
+        assert_eq!(quorum(7), 5);
+        for n in 1..20 {
+            assert!(
+                2 * quorum(n) > n + faults(n),
+                "quorums of {} replicas share an honest replica",
+                n
+            );
+        }
+
+        let ids: Vec<String> = vec!["a".into(), "b".into(), "c".into()];
+        assert_eq!(primary(&ids, 0, 5), Some(&ids[2]));
+        assert_eq!(primary(&ids, 1, 5), Some(&ids[0]));
+        assert_eq!(primary(&[], 1, 5), None);
+    }
+
+    #[test]
+    fn pbft_works() {
+        let ids: Vec<String> = vec!["a".into(), "b".into(), "c".into(), "d".into()];
+        let new = || -> HashMap<String, Pbft> {
+            ids.iter().map(|id| (id.clone(), Pbft::default())).collect()
+        };
+
+        // normal case, "b" is the primary of block 5 in view 0
+        let mut replicas = new();
+        assert!(deliver(
+            &mut replicas,
+            &ids,
+            "",
+            vec![PbftMessage::Request(request())]
+        )
+        .is_empty());
+        assert!(
+            replicas.get_mut("b").unwrap().next_proposal(5).is_some(),
+            "all replicas record requests"
+        );
+        replicas.get_mut("b").unwrap().proposing = false;
+        let pre_prepare = propose(&mut replicas, "b", 0);
+        assert!(
+            deliver(
+                &mut replicas,
+                &ids,
+                "",
+                vec![PbftMessage::PrePrepare {
+                    view: 0,
+                    replica: "a".into(),
+                    proposal: Box::new(Proposal {
+                        seq: 5,
+                        block: Block::default(),
+                        request: request(),
+                    }),
+                }]
+            )
+            .is_empty(),
+            "only the primary proposes"
+        );
+        let mut executed = deliver(&mut replicas, &ids, "", vec![pre_prepare]);
+        executed.sort();
+        assert_eq!(
+            executed,
+            ids.iter().map(|id| (id.clone(), 5)).collect::<Vec<_>>()
+        );
+        assert!(replicas.values().all(|replica| replica.requests.is_empty()));
+
+        // silent primary, replicas move to view 1 where "c" is primary
+        let mut replicas = new();
+        deliver(
+            &mut replicas,
+            &ids,
+            "b",
+            vec![PbftMessage::Request(request())],
+        );
+        let mut timeout = |id: &str| {
+            replicas
+                .get_mut(id)
+                .unwrap()
+                .timeout(0, &digest(&request()), id)
+        };
+        let view_changes = vec![timeout("a").unwrap(), timeout("c").unwrap()];
+        assert!(timeout("a").is_none(), "replicas ask for a view once");
+        // "d" didn't time out, it joins once f + 1 replicas asked
+        assert!(deliver(&mut replicas, &ids, "b", view_changes).is_empty());
+        for id in ["a", "c", "d"].iter() {
+            assert_eq!(replicas[*id].view, 1);
+        }
+        let pre_prepare = propose(&mut replicas, "c", 1);
+        let mut executed = deliver(&mut replicas, &ids, "b", vec![pre_prepare]);
+        executed.sort();
+        assert_eq!(
+            executed,
+            vec![("a".into(), 5), ("c".into(), 5), ("d".into(), 5)]
+        );
+    }
+
+    #[test]
+    fn sent_works() {
+        let mut replica = Pbft::default();
+        let prepare = |view: usize| PbftMessage::Prepare {
+            view,
+            seq: 5,
+            hash: "block".into(),
+            replica: "a".into(),
+        };
+        replica.record_sent(&PbftMessage::Request(request()));
+        assert!(replica.sent.is_empty(), "requests aren't replica messages");
+        replica.record_sent(&prepare(0));
+        replica.record_sent(&prepare(1));
+        assert!(replica.has_sent(&prepare(0).digest()));
+        assert!(!replica.has_sent(&prepare(2).digest()));
+
+        replica.enter_view(1, "a", 1);
+        assert!(!replica.has_sent(&prepare(0).digest()), "old views pruned");
+        assert!(replica.has_sent(&prepare(1).digest()));
+    }
+
+    #[test]
+    fn queue_works() {
+        use crate::block::BlockBuilder;
+
+        let genesis = Block::genesis();
+        let block = |prev: &Block| {
+            BlockBuilder::default()
+                .set_tx("a")
+                .set_rx("d")
+                .set_asset_id("asset")
+                .set_prev(prev)
+                .build()
+        };
+        let first = block(&genesis);
+        let second = block(&first);
+        let proposal = |seq: usize, block: &Block| Proposal {
+            seq,
+            block: block.clone(),
+            request: request(),
+        };
+
+        let mut replica = Pbft::default();
+        replica.queue_block(proposal(2, &second));
+        assert!(
+            replica.next_block(1, genesis.get_hash()).is_none(),
+            "blocks wait for earlier blocks"
+        );
+        replica.queue_block(proposal(1, &first));
+        assert_eq!(
+            replica.next_block(1, genesis.get_hash()),
+            Some(proposal(1, &first))
+        );
+        assert_eq!(
+            replica.next_block(2, first.get_hash()),
+            Some(proposal(2, &second))
+        );
+        assert!(replica.queued.is_empty());
+
+        replica.queue_block(proposal(1, &first));
+        assert!(replica.next_block(2, first.get_hash()).is_none());
+        assert!(replica.queued.is_empty(), "executed blocks are dropped");
+    }
+}
@@ -18,98 +18,614 @@
 use actix_web::{
     get, post,
     web::{self, ServiceConfig},
-    HttpResponse, Responder,
+    HttpRequest, HttpResponse, Responder,
 };
 
-use damn_vuln_blockchain::block::Block;
-use damn_vuln_blockchain::config::{Config, GetMode, Mode, SetMode};
+use damn_vuln_blockchain::attack::{SetEclipse, SetSelfish};
+use damn_vuln_blockchain::block::{Block, Evidence};
+use damn_vuln_blockchain::chain::BlockQuery;
+use damn_vuln_blockchain::config::{Config, Consensus, GetMode, Mode, SetMode};
+use damn_vuln_blockchain::error::{FaultError, PeerError, QueryError, TxError};
+use damn_vuln_blockchain::faults::{
+    BlockRoute, Partition, SetDropRate, SetDuplicateRate, SetLatency,
+};
+use damn_vuln_blockchain::finality::Vote;
+use damn_vuln_blockchain::graph::GraphQuery;
+use damn_vuln_blockchain::logs::{Action, EventFilter};
 use damn_vuln_blockchain::payload::{
-    GetStake as PayloadGetStake, Peer, StatusBuilder, Tx, ValidateTx, ValidateTxBuilder,
+    Ask, AssetMint, AssetQuery, Balance, Bond, Burn, Buy, Enrollment, GetStake as PayloadGetStake,
+    LongRange, Merge, Mint, Offer, Peer, Requester, Split, Supply, Sybil, TokenTransfer, Tx,
+    ValidateTx,
 };
+use damn_vuln_blockchain::pbft::PbftMessage;
 use damn_vuln_blockchain::Client;
 use log::debug;
 
-//#[post("/assets/buy")]
-//async fn asset_buy(data: web::Data<Config>) -> impl Responder {
-//}
-
 // peer enrollment
 #[post("/peers/enroll")]
-async fn peer_enroll(peer: web::Json<Peer>, data: web::Data<Config>) -> impl Responder {
-    use damn_vuln_blockchain::asset::InitNetworkBuilder;
-    use damn_vuln_blockchain::discovery::{AddPeer, GetCurrentSize};
+async fn peer_enroll(
+    req: HttpRequest,
+    payload: web::Json<Enrollment>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::asset::{GetPeerAssets, InitNetworkBuilder};
+    use damn_vuln_blockchain::discovery::{
+        AddPeer, EnrollPolicy, GetCurrentSize, GetEnrollments, RecordEnrollment,
+    };
+
+    let Enrollment { peer, nonce } = payload.into_inner();
+    // peers are limited by the host they connect from, the address they
+    // declare can be changed at will
+    let host = req
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default();
+    let policy = &data.enroll_policy;
+
+    if let Some(difficulty) = policy.pow_difficulty {
+        if !EnrollPolicy::verify_pow(&peer, nonce, difficulty) {
+            data.debug(&format!("Invalid proof of work from {}", &peer.id));
+            return Err(PeerError::InvalidProofOfWork);
+        }
+    }
+
+    let peers_at_address = data
+        .network_addr
+        .send(GetEnrollments(host.clone()))
+        .await
+        .unwrap();
+
+    if let Some(limit) = policy.address_limit {
+        if peers_at_address >= limit {
+            data.debug(&format!("Enrollment limit reached for {}", &host));
+            return Err(PeerError::AddressLimitReached);
+        }
+    }
 
     // peer enrollment should only happen when current_network_size < data.init_network_size
     let current_network_size = data.network_addr.send(GetCurrentSize).await.unwrap();
-    if current_network_size < data.init_network_size {
-        let asset_message = InitNetworkBuilder::default()
-            .network_size(data.init_network_size)
-            .peer_id(peer.id.clone())
-            .build()
-            .unwrap();
+    if current_network_size >= data.init_network_size {
+        data.debug(&format!("Network is full, not enrolling {}", &peer.id));
+        return Err(PeerError::NetworkFull);
+    }
 
-        data.network_addr
-            .send(AddPeer(peer.into_inner()))
-            .await
-            .unwrap();
+    let asset_message = InitNetworkBuilder::default()
+        .network_size(data.init_network_size)
+        .peer_id(peer.id.clone())
+        .build()
+        .unwrap();
 
+    data.network_addr.send(AddPeer(peer.clone())).await.unwrap();
+    data.network_addr
+        .send(RecordEnrollment(host))
+        .await
+        .unwrap();
+    data.log_event(Action::PeerEnroll(peer.clone())).await;
+
+    // additional identities from an address that already
+    // holds stake are enrolled without stake
+    if policy.stake_gated && peers_at_address > 0 {
+        data.debug("Address already holds stake, enrolling without assets");
+    } else {
         data.asset_addr.send(asset_message).await.unwrap();
+        let assets = data
+            .asset_addr
+            .send(GetPeerAssets(peer.id.clone()))
+            .await
+            .unwrap();
+        for asset in assets.into_iter() {
+            data.log_event(Action::DistributingAssets(asset, peer.clone()))
+                .await;
+        }
     }
 
-    // TODO, must return error when current_network_size == data.init_network_size
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok().finish())
+}
+
+// enrollment policy
+#[get("/peers/enroll/policy")]
+async fn enroll_policy(data: web::Data<Config>) -> impl Responder {
+    HttpResponse::Ok().json(&data.enroll_policy)
 }
 
 // peer enrollment
 #[get("/peers/all")]
-async fn peer_dump(data: web::Data<Config>) -> impl Responder {
+async fn peer_dump(query: web::Query<Requester>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::attack::Attack;
     use damn_vuln_blockchain::discovery::DumpPeer;
-    let peer_data = data.network_addr.send(DumpPeer).await.unwrap();
+    use damn_vuln_blockchain::utils::is_eclipsed;
+
+    let mut peer_data = data.network_addr.send(DumpPeer).await.unwrap();
+    if let Some(victim) = is_eclipsed(&data, &query.requester).await {
+        data.debug(&format!("Poisoning peer ledger for {}", &victim));
+        peer_data = Attack::poison(&peer_data, &victim, &data.peer_id, &data.public_ip);
+    }
     HttpResponse::Ok().json(peer_data)
 }
 
+// own identity
+#[get("/peers/self")]
+async fn peer_self(data: web::Data<Config>) -> impl Responder {
+    HttpResponse::Ok().json(Peer {
+        id: data.peer_id.clone(),
+        ip: data.public_ip.clone(),
+    })
+}
+
 // asset dump
 #[get("/assets/all")]
-async fn assets_dump(data: web::Data<Config>) -> impl Responder {
+async fn assets_dump(query: web::Query<Requester>, data: web::Data<Config>) -> impl Responder {
     use damn_vuln_blockchain::asset::DumpLedger;
-    let assets = data.asset_addr.send(DumpLedger).await.unwrap();
+    use damn_vuln_blockchain::utils::is_eclipsed;
+
+    let asset_addr = if is_eclipsed(&data, &query.requester).await.is_some() {
+        &data.tampered_asset_addr
+    } else {
+        &data.asset_addr
+    };
+    let assets = asset_addr.send(DumpLedger).await.unwrap();
     HttpResponse::Ok().json(assets)
 }
 
+// assets, optionally filtered by owner
+#[get("/assets")]
+async fn assets_query(query: web::Query<AssetQuery>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::asset::{DumpLedger, GetPeerAssets};
+
+    let asset_addr = data.get_asset_ledger().await;
+    let assets = match query.into_inner().owner {
+        Some(owner) => asset_addr.send(GetPeerAssets(owner)).await.unwrap(),
+        None => asset_addr.send(DumpLedger).await.unwrap(),
+    };
+    HttpResponse::Ok().json(assets)
+}
+
+// asset by ID
+#[get("/assets/{id}")]
+async fn asset_info(
+    path: web::Path<String>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, QueryError> {
+    use damn_vuln_blockchain::asset::GetAssetInfo;
+
+    let asset_addr = data.get_asset_ledger().await;
+    match asset_addr
+        .send(GetAssetInfo(path.into_inner()))
+        .await
+        .unwrap()
+    {
+        Some(asset) => Ok(HttpResponse::Ok().json(asset)),
+        None => Err(QueryError::AssetNotFound),
+    }
+}
+
+// every transfer of an asset, rebuilt from the chain
+#[get("/assets/{id}/history")]
+async fn asset_history(
+    path: web::Path<String>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, QueryError> {
+    use damn_vuln_blockchain::asset::GetAssetInfo;
+    use damn_vuln_blockchain::chain::GetAssetHistory;
+
+    let asset_id = path.into_inner();
+    let asset_addr = data.get_asset_ledger().await;
+    if asset_addr
+        .send(GetAssetInfo(asset_id.clone()))
+        .await
+        .unwrap()
+        .is_none()
+    {
+        return Err(QueryError::AssetNotFound);
+    }
+    let chain_addr = data.get_chain_addr().await;
+    let history = chain_addr.send(GetAssetHistory(asset_id)).await.unwrap();
+    Ok(HttpResponse::Ok().json(history))
+}
+
+// chain replacement, follows longest chain rule
+#[post("/chain/replace")]
+async fn chain_replace(
+    payload: web::Json<Vec<Block>>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::asset::GetAssetsInfo;
+    use damn_vuln_blockchain::chain::Reorg;
+    use damn_vuln_blockchain::utils::reorg_assets;
+
+    let chain = payload.into_inner();
+    let chain_addr = data.get_chain_addr().await;
+    let (fork_point, discarded) = chain_addr
+        .send(Reorg {
+            chain: chain.clone(),
+            checkpoint: data.checkpoint,
+        })
+        .await
+        .unwrap()
+        .map_err(|e| {
+            data.debug(&format!("Chain replacement rejected: {}", e));
+            PeerError::ChainError(e)
+        })?;
+
+    data.info(&format!(
+        "Reorg: discarded {} blocks, adopted {} blocks",
+        discarded.len(),
+        chain.len() - fork_point
+    ));
+    reorg_assets(
+        &data.get_asset_ledger().await,
+        &discarded,
+        &chain[fork_point..],
+    )
+    .await;
+
+    let asset_addr = data.get_asset_ledger().await;
+    let mut asset_ids: Vec<&String> = discarded
+        .iter()
+        .chain(chain[fork_point..].iter())
+        .filter_map(|block| block.get_asset_id())
+        .collect();
+    asset_ids.sort_unstable();
+    asset_ids.dedup();
+    let asset_ids = asset_ids.into_iter().cloned().collect();
+    let assets = asset_addr.send(GetAssetsInfo(asset_ids)).await.unwrap();
+    for asset in assets.iter() {
+        if let Some(owner) = asset.get_owner() {
+            data.log_event(Action::OwnershipChanged(
+                data.peer_id.clone(),
+                asset.get_hash().into(),
+                owner.clone(),
+            ))
+            .await;
+        }
+    }
+    for block in chain[fork_point..].iter() {
+        data.log_event(Action::BlockAdded(data.peer_id.clone(), block.clone()))
+            .await;
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+// chain and fork diagram
+#[get("/chain/graph")]
+async fn chain_graph(query: web::Query<GraphQuery>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::graph::{render, Format};
+    use damn_vuln_blockchain::utils::local_state;
+
+    let status = local_state(&data).await;
+    let content_type = match query.format {
+        Format::Dot => "text/vnd.graphviz",
+        Format::Mermaid => "text/plain",
+    };
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .body(render(&[status], query.format))
+}
+
 // chain dump
 #[get("/chain/all")]
-async fn chain_dump(data: web::Data<Config>) -> impl Responder {
+async fn chain_dump(query: web::Query<Requester>, data: web::Data<Config>) -> impl Responder {
     use damn_vuln_blockchain::chain::DumpLedger;
-    let chain = data.chain_addr.send(DumpLedger).await.unwrap();
+    use damn_vuln_blockchain::utils::is_eclipsed;
+
+    let chain_addr = if is_eclipsed(&data, &query.requester).await.is_some() {
+        &data.tampered_chain_addr
+    } else {
+        &data.chain_addr
+    };
+    let chain = chain_addr.send(DumpLedger).await.unwrap();
     HttpResponse::Ok().json(chain)
 }
 
+// latest block
+#[get("/chain/tip")]
+async fn chain_tip(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::chain::GetLastBlock;
+
+    let chain_addr = data.get_chain_addr().await;
+    let block = chain_addr.send(GetLastBlock).await.unwrap();
+    HttpResponse::Ok().json(block)
+}
+
+// block by hash
+#[get("/chain/block/{hash}")]
+async fn block_by_hash(
+    path: web::Path<String>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, QueryError> {
+    use damn_vuln_blockchain::chain::GetBlock;
+
+    let chain_addr = data.get_chain_addr().await;
+    match chain_addr.send(GetBlock(path.into_inner())).await.unwrap() {
+        Some(block) => Ok(HttpResponse::Ok().json(block)),
+        None => Err(QueryError::BlockNotFound),
+    }
+}
+
+// block by serial number
+#[get("/chain/block/serial/{serial_no}")]
+async fn block_by_serial(
+    path: web::Path<usize>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, QueryError> {
+    use damn_vuln_blockchain::chain::GetBlockBySerial;
+
+    let chain_addr = data.get_chain_addr().await;
+    match chain_addr
+        .send(GetBlockBySerial(path.into_inner()))
+        .await
+        .unwrap()
+    {
+        Some(block) => Ok(HttpResponse::Ok().json(block)),
+        None => Err(QueryError::BlockNotFound),
+    }
+}
+
+// paginated blocks, filtered by serial range and participants
+#[get("/chain/blocks")]
+async fn blocks_query(
+    query: web::Query<BlockQuery>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, QueryError> {
+    use damn_vuln_blockchain::chain::QueryBlocks;
+
+    let chain_addr = data.get_chain_addr().await;
+    let page = chain_addr
+        .send(QueryBlocks(query.into_inner()))
+        .await
+        .unwrap()?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
 // attack
 #[post("/fork")]
 async fn fork(data: web::Data<Config>) -> impl Responder {
-    use damn_vuln_blockchain::asset::{DumpLedger as DumpAsset, ReplaceLedger as ReplaceAsset};
-    use damn_vuln_blockchain::chain::{DumpLedger, ReplaceChain};
+    use damn_vuln_blockchain::utils::fork_state;
 
     let current_mode = data.mode_addr.send(GetMode).await.unwrap();
     data.debug(&format!("current mode: {:?}", &current_mode));
     if current_mode == Mode::Attacker(true) || current_mode == Mode::Attacker(false) {
-        data.info("Forking chain");
-        let chain = data.chain_addr.send(DumpLedger).await.unwrap();
-        data.tampered_chain_addr
-            .send(ReplaceChain(chain))
-            .await
-            .unwrap()
-            .unwrap();
-        let assets = data.asset_addr.send(DumpAsset).await.unwrap();
-        data.tampered_asset_addr
-            .send(ReplaceAsset(assets))
-            .await
-            .unwrap();
+        fork_state(&data).await;
     }
 
     HttpResponse::Ok()
 }
 
+// eclipse attack
+#[post("/eclipse")]
+async fn eclipse(
+    payload: web::Json<SetEclipse>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    match data.mode_addr.send(GetMode).await.unwrap() {
+        Mode::Attacker(_) => {
+            data.info(&format!("Eclipsing peer: {:?}", &payload.victim));
+            data.attack_addr.send(payload.into_inner()).await.unwrap();
+            Ok(HttpResponse::Ok().finish())
+        }
+        _ => Err(PeerError::NotAttacker),
+    }
+}
+
+// sybil attack
+#[post("/sybil")]
+async fn sybil(
+    client: web::Data<Client>,
+    payload: web::Json<Sybil>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::attack::{AddSybils, GetSybils};
+
+    match data.mode_addr.send(GetMode).await.unwrap() {
+        Mode::Attacker(_) => {
+            let offset = data.attack_addr.send(GetSybils).await.unwrap().len();
+            let mut sybils: Vec<String> = Vec::new();
+            for i in offset..offset + payload.count {
+                let peer = Peer {
+                    id: format!("sybil-{}.{}", i, &data.peer_id),
+                    ip: data.public_ip.clone(),
+                };
+                data.info(&format!("Enrolling sybil: {}", &peer.id));
+                let id = peer.id.clone();
+                if client.enroll(&data, peer).await {
+                    sybils.push(id);
+                } else {
+                    data.info("Sybil enrollment rejected by auditor");
+                    break;
+                }
+            }
+            data.attack_addr
+                .send(AddSybils(sybils.clone()))
+                .await
+                .unwrap();
+            Ok(HttpResponse::Ok().json(sybils))
+        }
+        _ => Err(PeerError::NotAttacker),
+    }
+}
+
+// replay attack
+#[post("/replay")]
+async fn replay(
+    client: web::Data<Client>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::attack::GetCapturedTxs;
+    use damn_vuln_blockchain::utils::{consensus, get_next_block_id};
+
+    match data.mode_addr.send(GetMode).await.unwrap() {
+        Mode::Attacker(_) => {
+            let captured = data.attack_addr.send(GetCapturedTxs).await.unwrap();
+            for tx in captured.iter() {
+                data.info(&format!(
+                    "Replaying transaction of asset {} from {}",
+                    &tx.tx.asset_id, &tx.seller_peer_id
+                ));
+                let next_block_id = get_next_block_id(&data).await;
+                if let Some(validator) = consensus(&data, next_block_id, &client).await {
+                    client.send_tx_to_validator(&data, &validator, tx).await;
+                }
+            }
+            Ok(HttpResponse::Ok().json(captured.len()))
+        }
+        _ => Err(PeerError::NotAttacker),
+    }
+}
+
+// long-range attack
+#[post("/long-range")]
+async fn long_range(
+    client: web::Data<Client>,
+    payload: web::Json<LongRange>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::asset::{DumpLedger as DumpAsset, ReplaceLedger as ReplaceAsset};
+    use damn_vuln_blockchain::block::BlockBuilder;
+    use damn_vuln_blockchain::chain::{Chain, DumpLedger, ReplaceChain};
+    use damn_vuln_blockchain::discovery::DumpPeer;
+    use damn_vuln_blockchain::utils::reorg_assets;
+
+    match data.mode_addr.send(GetMode).await.unwrap() {
+        Mode::Attacker(_) => (),
+        _ => return Err(PeerError::NotAttacker),
+    }
+
+    let mut history = data.chain_addr.send(DumpLedger).await.unwrap();
+    let fork_point = history
+        .iter()
+        .take_while(|block| block.get_serial_no().unwrap() <= payload.from)
+        .count();
+    let discarded = history.split_off(fork_point);
+
+    // rewinding assets to the fork point gives back stake that
+    // has since been sold
+    let assets = data.asset_addr.send(DumpAsset).await.unwrap();
+    data.tampered_asset_addr
+        .send(ReplaceAsset(assets))
+        .await
+        .unwrap();
+    reorg_assets(&data.tampered_asset_addr, &discarded, &[]).await;
+    let stake: Vec<String> = data
+        .tampered_asset_addr
+        .send(DumpAsset)
+        .await
+        .unwrap()
+        .iter()
+        .filter(|asset| asset.get_owner().as_deref() == Some(data.peer_id.as_str()))
+        .map(|asset| asset.get_hash().into())
+        .collect();
+    if stake.is_empty() {
+        return Err(PeerError::NoStake);
+    }
+
+    // validating blocks on an alternative history costs nothing
+    let mut alternative = Chain::new("Alternative");
+    alternative
+        .replace_chain(history)
+        .map_err(PeerError::ChainError)?;
+    for asset_id in stake.iter().cycle().take(payload.length) {
+        let block = BlockBuilder::default()
+            .set_tx(&data.peer_id)
+            .set_rx(&data.peer_id)
+            .set_asset_id(asset_id)
+            .set_validator(&data.peer_id)
+            .set_prev(alternative.get_last_block())
+            .build();
+        alternative
+            .add_block(block, data.init_network_size)
+            .map_err(PeerError::ChainError)?;
+    }
+
+    let alternative = alternative.blocks();
+    data.info(&format!(
+        "Built alternative history of {} blocks from block {}",
+        alternative.len(),
+        payload.from
+    ));
+    data.tampered_chain_addr
+        .send(ReplaceChain(alternative.clone()))
+        .await
+        .unwrap()
+        .map_err(PeerError::ChainError)?;
+
+    let peers = data.network_addr.send(DumpPeer).await.unwrap();
+    for peer in peers.iter().filter(|peer| peer.id != data.peer_id) {
+        data.debug(&format!("Sending alternative history to {}", &peer.id));
+        client.send_chain_to_peer(&data, peer, &alternative).await;
+    }
+
+    Ok(HttpResponse::Ok().json(alternative.len()))
+}
+
+// selfish mining
+#[post("/selfish")]
+async fn selfish(
+    payload: web::Json<SetSelfish>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::utils::fork_state;
+
+    match data.mode_addr.send(GetMode).await.unwrap() {
+        Mode::Attacker(_) => {
+            data.info(&format!("Withholding blocks: {}", payload.enabled));
+            if payload.enabled {
+                fork_state(&data).await;
+            }
+            data.attack_addr.send(payload.into_inner()).await.unwrap();
+            Ok(HttpResponse::Ok().finish())
+        }
+        _ => Err(PeerError::NotAttacker),
+    }
+}
+
+// mine a block privately
+#[post("/selfish/mine")]
+async fn selfish_mine(
+    client: web::Data<Client>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::attack::GetSelfish;
+    use damn_vuln_blockchain::utils::{consensus, get_next_private_block_id, mine_private_block};
+
+    if !data.attack_addr.send(GetSelfish).await.unwrap() {
+        return Err(PeerError::NotSelfish);
+    }
+    let next_block_id = get_next_private_block_id(&data).await;
+    let validator = consensus(&data, next_block_id, &client)
+        .await
+        .ok_or(PeerError::NotValidator)?;
+    let lead = mine_private_block(&data, &validator).await?;
+    Ok(HttpResponse::Ok().json(lead))
+}
+
+// release withheld blocks
+#[post("/selfish/release")]
+async fn selfish_release(
+    client: web::Data<Client>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::attack::GetSelfish;
+    use damn_vuln_blockchain::utils::release_withheld_blocks;
+
+    if !data.attack_addr.send(GetSelfish).await.unwrap() {
+        return Err(PeerError::NotSelfish);
+    }
+    let released = release_withheld_blocks(&data, &client, true).await;
+    Ok(HttpResponse::Ok().json(released))
+}
+
+// share of blocks validated compared with share of stake
+#[get("/metrics")]
+async fn metrics(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::asset::DumpLedger as DumpAsset;
+    use damn_vuln_blockchain::chain::DumpLedger;
+    use damn_vuln_blockchain::utils::shares;
+
+    let chain = data.chain_addr.send(DumpLedger).await.unwrap();
+    let assets = data.asset_addr.send(DumpAsset).await.unwrap();
+    HttpResponse::Ok().json(shares(&chain, &assets))
+}
+
 // attack
 #[post("/attack")]
 async fn set_attack(data: web::Data<Config>) -> impl Responder {
@@ -139,15 +655,110 @@ async fn set_attack(data: web::Data<Config>) -> impl Responder {
 
     if let Some(mode) = new_mode {
         data.debug(&format!("changing mode to: {:?}", &mode));
-        data.mode_addr.send(SetMode(mode)).await.unwrap();
+        data.mode_addr.send(SetMode(mode.clone())).await.unwrap();
+        data.log_event(Action::ModeChanged(data.peer_id.clone(), mode))
+            .await;
     }
     HttpResponse::Ok()
 }
 
+// fault injection
+#[get("/faults")]
+async fn faults_dump(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::faults::DumpFaults;
+    let faults = data.fault_addr.send(DumpFaults).await.unwrap();
+    HttpResponse::Ok().json(faults)
+}
+
+// partition peers
+#[post("/faults/partition")]
+async fn faults_partition(
+    payload: web::Json<Partition>,
+    data: web::Data<Config>,
+) -> impl Responder {
+    let partition = payload.into_inner();
+    data.info(&format!("Partitioning peers: {:?}", &partition.0));
+    data.fault_addr.send(partition).await.unwrap();
+    HttpResponse::Ok()
+}
+
+// add latency and jitter
+#[post("/faults/latency")]
+async fn faults_latency(
+    payload: web::Json<SetLatency>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, FaultError> {
+    data.fault_addr.send(payload.into_inner()).await.unwrap()?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// drop a percentage of messages
+#[post("/faults/drop")]
+async fn faults_drop(
+    payload: web::Json<SetDropRate>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, FaultError> {
+    data.fault_addr.send(payload.into_inner()).await.unwrap()?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// duplicate a percentage of messages
+#[post("/faults/duplicate")]
+async fn faults_duplicate(
+    payload: web::Json<SetDuplicateRate>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, FaultError> {
+    data.fault_addr.send(payload.into_inner()).await.unwrap()?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// block a route
+#[post("/faults/block")]
+async fn faults_block(payload: web::Json<BlockRoute>, data: web::Data<Config>) -> impl Responder {
+    data.fault_addr.send(payload.into_inner()).await.unwrap();
+    HttpResponse::Ok()
+}
+
+// remove all faults
+#[post("/faults/clear")]
+async fn faults_clear(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::faults::ClearFaults;
+    data.fault_addr.send(ClearFaults).await.unwrap();
+    HttpResponse::Ok()
+}
+
 // get stake for a particular block ID
 #[post("/stake")]
 async fn get_stake(payload: web::Json<PayloadGetStake>, data: web::Data<Config>) -> impl Responder {
-    use damn_vuln_blockchain::asset::{GetStake as ActorGetStake, StakeBuilder};
+    use damn_vuln_blockchain::asset::{GetPeerAssets, GetStake as ActorGetStake, StakeBuilder};
+    use damn_vuln_blockchain::utils::is_own_identity;
+
+    data.log_event(Action::StakeRequest(
+        payload
+            .peer_id
+            .clone()
+            .unwrap_or_else(|| data.peer_id.clone()),
+        payload.block_id,
+    ))
+    .await;
+
+    // stake requests for sybils are answered with their assets
+    if let Some(peer_id) = payload.peer_id.as_ref() {
+        if peer_id != &data.peer_id && is_own_identity(&data, peer_id).await {
+            let assets = data
+                .asset_addr
+                .send(GetPeerAssets(peer_id.clone()))
+                .await
+                .unwrap();
+            let stake = StakeBuilder::default()
+                .stake(assets.iter().map(|asset| asset.get_hash().into()).collect())
+                .block_id(payload.block_id)
+                .build()
+                .unwrap();
+            return HttpResponse::Ok().json(stake);
+        }
+    }
+
     let msg: ActorGetStake = payload.into_inner().into();
     // attacking peer should always return stake = 0
     let current_mode = data.mode_addr.send(GetMode).await.unwrap();
@@ -180,8 +791,15 @@ async fn sell(
     client: web::Data<Client>,
     payload: web::Json<Tx>,
     data: web::Data<Config>,
-) -> impl Responder {
-    use damn_vuln_blockchain::utils::{check_ownership, consensus, get_next_block_id};
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::asset::GetAssetInfo;
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::{check_transaction, submit_tx};
+
+    // sales for tokens need the buyer's consent, see /assets/offer
+    if payload.kind != TxKind::Asset {
+        return Err(TxError::NotAssetTransfer);
+    }
 
     //  let mut config = data.into_inner();
     //  if config.mode_addr.send(GetMode).await.unwrap() == Mode::Attacker(true)
@@ -191,24 +809,575 @@ async fn sell(
     //      use std::sync::Arc;
     //      config = Arc::new(config.fork_chain());
     //  }
-    if check_ownership(&data, &data.peer_id, &payload.asset_id).await {
-        let next_block_id = get_next_block_id(&data).await;
-        let validator = consensus(&data, next_block_id, &client).await;
-        let validator_payload = ValidateTxBuilder::default()
-            .tx(payload.into_inner())
-            .seller_peer_id(data.peer_id.clone())
-            .build()
+    if check_transaction(
+        &data,
+        &data.peer_id,
+        &payload.buyer_peer_id,
+        Some(&payload.asset_id),
+        &payload.kind,
+    )
+    .await
+    {
+        let asset = data
+            .get_asset_ledger()
+            .await
+            .send(GetAssetInfo(payload.asset_id.clone()))
+            .await
+            .unwrap()
             .unwrap();
-        client
-            .send_tx_to_validator(&validator, &validator_payload)
+        let mut tx = payload.into_inner();
+        if tx.input.is_none() {
+            tx.input = asset.get_output().cloned();
+        }
+        let seller = Peer {
+            id: data.peer_id.clone(),
+            ip: data.public_ip.clone(),
+        };
+        data.log_event(Action::TransactionRequest(asset, seller))
             .await;
+        submit_tx(&data, &client, &data.peer_id, tx).await;
+    } else {
+        data.debug("Transaction not verified");
+    };
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+// ask a price for an asset of this peer
+#[post("/assets/ask")]
+async fn asset_ask(
+    payload: web::Json<Ask>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::asset::SetAsk;
+
+    let payload = payload.into_inner();
+    data.get_asset_ledger()
+        .await
+        .send(SetAsk {
+            owner: data.peer_id.clone(),
+            asset_id: payload.asset_id,
+            price: payload.price,
+        })
+        .await
+        .unwrap()?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// offer to buy an asset for tokens, the owner sells it if the
+// offer covers its asked price
+#[post("/assets/buy")]
+async fn asset_buy(
+    client: web::Data<Client>,
+    payload: web::Json<Buy>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::asset::GetAssetInfo;
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::discovery::GetPeer;
+    use damn_vuln_blockchain::utils::check_transaction;
+
+    let asset = data
+        .get_asset_ledger()
+        .await
+        .send(GetAssetInfo(payload.asset_id.clone()))
+        .await
+        .unwrap()
+        .ok_or(TxError::UnknownAsset)?;
+    let owner = asset.get_owner().clone().ok_or(TxError::UnknownAsset)?;
+    let kind = TxKind::Sale {
+        price: payload.price,
+        fee: payload.fee,
+    };
+    if !check_transaction(&data, &owner, &data.peer_id, Some(&payload.asset_id), &kind).await {
+        return Err(TxError::InsufficientBalance);
+    }
+    let owner = data
+        .network_addr
+        .send(GetPeer(owner))
+        .await
+        .unwrap()
+        .ok_or(TxError::NotForSale)?;
+
+    let offer = Offer {
+        asset_id: payload.asset_id.clone(),
+        buyer_peer_id: data.peer_id.clone(),
+        price: payload.price,
+        fee: payload.fee,
+    };
+    if client.send_offer(&data, &owner, &offer).await {
+        Ok(HttpResponse::Ok().finish())
     } else {
-        data.debug("Ownership not verified");
+        Err(TxError::NotForSale)
+    }
+}
+
+// sell asset of this peer to a buyer whose offer covers the asked price
+#[post("/assets/offer")]
+async fn asset_offer(
+    client: web::Data<Client>,
+    payload: web::Json<Offer>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::asset::{GetAssetInfo, TakeAsk};
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::{check_transaction, submit_tx};
+
+    let kind = TxKind::Sale {
+        price: payload.price,
+        fee: payload.fee,
+    };
+    if !check_transaction(
+        &data,
+        &data.peer_id,
+        &payload.buyer_peer_id,
+        Some(&payload.asset_id),
+        &kind,
+    )
+    .await
+    {
+        return Err(TxError::InsufficientBalance);
+    }
+    let asset_addr = data.get_asset_ledger().await;
+    asset_addr
+        .send(TakeAsk {
+            asset_id: payload.asset_id.clone(),
+            price: payload.price,
+        })
+        .await
+        .unwrap()?;
+    let asset = asset_addr
+        .send(GetAssetInfo(payload.asset_id.clone()))
+        .await
+        .unwrap()
+        .ok_or(TxError::UnknownAsset)?;
+
+    let tx = Tx {
+        asset_id: payload.asset_id.clone(),
+        buyer_peer_id: payload.buyer_peer_id.clone(),
+        kind,
+        input: asset.get_output().cloned(),
+    };
+    submit_tx(&data, &client, &data.peer_id, tx).await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// split asset into assets of smaller value
+#[post("/assets/split")]
+async fn asset_split(
+    client: web::Data<Client>,
+    payload: web::Json<Split>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::change_asset;
+
+    let kind = TxKind::Split {
+        values: payload.values.clone(),
+    };
+    change_asset(&data, &client, &payload.asset_id, kind).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// merge assets into one asset
+#[post("/assets/merge")]
+async fn asset_merge(
+    client: web::Data<Client>,
+    payload: web::Json<Merge>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::asset::GetAssetsInfo;
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::change_asset;
+
+    let (asset_id, assets) = payload
+        .asset_ids
+        .split_first()
+        .ok_or(TxError::InvalidAssetChange)?;
+    let value = data
+        .get_asset_ledger()
+        .await
+        .send(GetAssetsInfo(payload.asset_ids.clone()))
+        .await
+        .unwrap()
+        .iter()
+        .try_fold(0usize, |sum, asset| sum.checked_add(asset.get_value()))
+        .ok_or(TxError::ValueOverflow)?;
+    let kind = TxKind::Merge {
+        assets: assets.to_vec(),
+        value,
     };
+    change_asset(&data, &client, asset_id, kind).await?;
+    Ok(HttpResponse::Ok().finish())
+}
 
+// burn asset
+#[post("/assets/burn")]
+async fn asset_burn(
+    client: web::Data<Client>,
+    payload: web::Json<Burn>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::asset::GetAssetInfo;
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::change_asset;
+
+    let value = data
+        .get_asset_ledger()
+        .await
+        .send(GetAssetInfo(payload.asset_id.clone()))
+        .await
+        .unwrap()
+        .ok_or(TxError::UnknownAsset)?
+        .get_value();
+    change_asset(&data, &client, &payload.asset_id, TxKind::Burn { value }).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// mint asset, auditor only
+#[post("/assets/mint")]
+async fn asset_mint(
+    client: web::Data<Client>,
+    payload: web::Json<AssetMint>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::asset::{mint_id, GetAssetInfo};
+    use damn_vuln_blockchain::block::{BlockBuilder, TxKind};
+    use damn_vuln_blockchain::chain::GetLastBlock;
+    use damn_vuln_blockchain::utils::{
+        add_block_runner, broadcast_block, check_supply, sync_chain,
+    };
+
+    if data.mode_addr.send(GetMode).await.unwrap() != Mode::Auditor {
+        return Err(PeerError::NotAuditor);
+    }
+
+    // the auditor doesn't take part in consensus, so it catches up
+    // with the longest chain before minting
+    sync_chain(&data, &client).await;
+    let chain_addr = data.get_chain_addr().await;
+
+    let last_block = chain_addr.send(GetLastBlock).await.unwrap();
+    let asset_id = mint_id(last_block.get_hash(), &payload.name, payload.value);
+    let block = BlockBuilder::default()
+        .set_tx(&data.peer_id)
+        .set_rx(&payload.peer_id)
+        .set_asset_id(&asset_id)
+        .set_validator(&data.peer_id)
+        .set_kind(TxKind::MintAsset {
+            name: payload.name.clone(),
+            value: payload.value,
+        })
+        .set_prev(&last_block)
+        .build();
+    check_supply(&data, &block)
+        .await
+        .map_err(PeerError::TxError)?;
+
+    data.log_event(Action::BlockCreation(block.clone())).await;
+    add_block_runner(&data, &client, &block).await;
+    if let Some(asset) = data
+        .get_asset_ledger()
+        .await
+        .send(GetAssetInfo(asset_id))
+        .await
+        .unwrap()
+    {
+        data.log_event(Action::MintingAsset(asset)).await;
+    }
+    // stored block carries its serial number
+    let block = chain_addr.send(GetLastBlock).await.unwrap();
+    broadcast_block(&data, &client, &block).await;
+    Ok(HttpResponse::Ok().json(block))
+}
+
+// asset supply and supply policy
+#[get("/assets/supply")]
+async fn asset_supply(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::asset::GetSupply;
+    use damn_vuln_blockchain::chain::GetMinted;
+
+    let supply = data.get_asset_ledger().await.send(GetSupply).await.unwrap();
+    let minted = data.get_chain_addr().await.send(GetMinted).await.unwrap();
+    HttpResponse::Ok().json(Supply {
+        supply,
+        minted,
+        cap: data.supply_policy.cap,
+        issuance: data.supply_policy.issuance,
+    })
+}
+
+// bond asset as stake
+#[post("/stake/bond")]
+async fn stake_bond(
+    client: web::Data<Client>,
+    payload: web::Json<Bond>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::bond_asset;
+
+    bond_asset(&data, &client, &payload.asset_id, TxKind::Bond).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// unbond asset, it stays locked for the unbonding period
+#[post("/stake/unbond")]
+async fn stake_unbond(
+    client: web::Data<Client>,
+    payload: web::Json<Bond>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::{bond_asset, get_next_block_id};
+
+    let unlock = get_next_block_id(&data).await + data.unbonding_period;
+    bond_asset(&data, &client, &payload.asset_id, TxKind::Unbond { unlock }).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// equivocation evidence found by this peer and its peers
+#[get("/evidence")]
+async fn evidence_dump(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::evidence::DumpEvidence;
+    let evidence = data.evidence_addr.send(DumpEvidence).await.unwrap();
+    HttpResponse::Ok().json(evidence)
+}
+
+// evidence gossiped by peers, new evidence is passed on. Peers only
+// record evidence when they have seen both of its blocks themselves
+#[post("/evidence")]
+async fn evidence_add(
+    client: web::Data<Client>,
+    payload: web::Json<Evidence>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::evidence::AddEvidence;
+    use damn_vuln_blockchain::utils::{announce_evidence, has_seen};
+
+    let payload = payload.into_inner();
+    let evidence = Evidence::new(payload.first, payload.second).ok_or(TxError::InvalidEvidence)?;
+    if !has_seen(&data, &evidence.first).await || !has_seen(&data, &evidence.second).await {
+        return Err(TxError::UnseenEvidence);
+    }
+    if data
+        .evidence_addr
+        .send(AddEvidence(evidence.clone()))
+        .await
+        .unwrap()
+    {
+        announce_evidence(&data, &client, &evidence).await;
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+// last final block and finality votes
+#[get("/finality")]
+async fn finality(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::chain::GetFinalized;
+    use damn_vuln_blockchain::finality::DumpVotes;
+    use damn_vuln_blockchain::payload::FinalityStatus;
+
+    let finalized = data.chain_addr.send(GetFinalized).await.unwrap();
+    let votes = data.finality_addr.send(DumpVotes).await.unwrap();
+    HttpResponse::Ok().json(FinalityStatus { finalized, votes })
+}
+
+// finality votes gossiped by peers
+#[post("/finality/vote")]
+async fn finality_vote(
+    client: web::Data<Client>,
+    payload: web::Json<Vote>,
+    data: web::Data<Config>,
+) -> impl Responder {
+    use damn_vuln_blockchain::utils::{confirm_vote, record_vote};
+
+    let vote = payload.into_inner();
+    if !confirm_vote(&data, &client, &vote).await {
+        data.debug(&format!(
+            "Ignoring unconfirmed vote of {} for block {}",
+            vote.voter, vote.serial_no
+        ));
+    } else if !record_vote(&data, vote.clone()).await {
+        data.debug(&format!(
+            "Ignoring vote of {} for block {}",
+            vote.voter, vote.serial_no
+        ));
+    }
     HttpResponse::Ok()
 }
 
+// vote of this peer for a serial number, peers confirm gossiped
+// votes with it
+#[get("/finality/vote/{serial_no}")]
+async fn finality_own_vote(path: web::Path<usize>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::finality::GetVote;
+
+    let vote = data
+        .finality_addr
+        .send(GetVote {
+            voter: data.peer_id.clone(),
+            serial_no: path.into_inner(),
+        })
+        .await
+        .unwrap();
+    HttpResponse::Ok().json(vote)
+}
+
+// VRF public key of this peer
+#[get("/lottery/key")]
+async fn lottery_key(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::vrf::GetPublicKey;
+    let key = data.lottery_addr.send(GetPublicKey).await.unwrap();
+    HttpResponse::Ok().json(key)
+}
+
+// PBFT view and pending requests
+#[get("/pbft")]
+async fn pbft(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::pbft::GetPbftStatus;
+    let status = data.pbft_addr.send(GetPbftStatus).await.unwrap();
+    HttpResponse::Ok().json(status)
+}
+
+// PBFT messages from clients and replicas
+#[post("/pbft")]
+async fn pbft_message(
+    client: web::Data<Client>,
+    payload: web::Json<PbftMessage>,
+    data: web::Data<Config>,
+) -> impl Responder {
+    use damn_vuln_blockchain::utils::pbft_receive;
+
+    // replicas under DoS go silent
+    if data.mode_addr.send(GetMode).await.unwrap() == Mode::Victim(true) {
+        data.debug("Dropping PBFT message");
+    } else {
+        pbft_receive(&data, &client, payload.into_inner()).await;
+    }
+    HttpResponse::Ok()
+}
+
+// checks if this replica sent a PBFT message, replicas confirm
+// messages with it
+#[get("/pbft/sent/{digest}")]
+async fn pbft_sent(path: web::Path<String>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::pbft::HasSent;
+    let sent = data
+        .pbft_addr
+        .send(HasSent(path.into_inner()))
+        .await
+        .unwrap();
+    HttpResponse::Ok().json(sent)
+}
+
+// slash validator that signed conflicting blocks
+#[post("/stake/slash")]
+async fn stake_slash(
+    client: web::Data<Client>,
+    payload: web::Json<Evidence>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::utils::slash;
+
+    slash(&data, &client, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// transfer tokens
+#[post("/tokens/transfer")]
+async fn token_transfer(
+    client: web::Data<Client>,
+    payload: web::Json<TokenTransfer>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::block::TxKind;
+    use damn_vuln_blockchain::utils::{check_transaction, submit_tx};
+
+    let kind = TxKind::Transfer {
+        amount: payload.amount,
+        fee: payload.fee,
+    };
+    if !check_transaction(&data, &data.peer_id, &payload.to, None, &kind).await {
+        return Err(TxError::InsufficientBalance);
+    }
+
+    let tx = Tx {
+        asset_id: String::default(),
+        buyer_peer_id: payload.to.clone(),
+        kind,
+        input: None,
+    };
+    submit_tx(&data, &client, &data.peer_id, tx).await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// mint tokens, only the auditor can mint
+#[post("/tokens/mint")]
+async fn token_mint(
+    client: web::Data<Client>,
+    payload: web::Json<Mint>,
+    data: web::Data<Config>,
+) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::block::{BlockBuilder, TxKind};
+    use damn_vuln_blockchain::chain::{AddBlock, GetLastBlock};
+    use damn_vuln_blockchain::utils::{broadcast_block, sync_chain};
+
+    if data.mode_addr.send(GetMode).await.unwrap() != Mode::Auditor {
+        return Err(PeerError::NotAuditor);
+    }
+
+    // the auditor doesn't take part in consensus, so it catches up
+    // with the longest chain before minting
+    sync_chain(&data, &client).await;
+    let chain_addr = data.get_chain_addr().await;
+
+    let last_block = chain_addr.send(GetLastBlock).await.unwrap();
+    let block = BlockBuilder::default()
+        .set_tx(&data.peer_id)
+        .set_rx(&payload.peer_id)
+        .set_validator(&data.peer_id)
+        .set_kind(TxKind::Mint {
+            amount: payload.amount,
+        })
+        .set_prev(&last_block)
+        .build();
+    data.log_event(Action::BlockCreation(block.clone())).await;
+    chain_addr
+        .send(AddBlock(block.clone(), data.init_network_size))
+        .await
+        .unwrap()
+        .map_err(PeerError::ChainError)?;
+    // stored block carries its serial number
+    let block = chain_addr.send(GetLastBlock).await.unwrap();
+    data.log_event(Action::BlockAdded(data.peer_id.clone(), block.clone()))
+        .await;
+    broadcast_block(&data, &client, &block).await;
+    Ok(HttpResponse::Ok().json(block))
+}
+
+// token balances of all peers
+#[get("/tokens/balances")]
+async fn token_balances(data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::chain::GetBalances;
+    let balances = data.get_chain_addr().await.send(GetBalances).await.unwrap();
+    HttpResponse::Ok().json(balances)
+}
+
+// token balance of a peer
+#[get("/tokens/balance/{peer_id}")]
+async fn token_balance(path: web::Path<String>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::chain::GetBalance;
+    let peer_id = path.into_inner();
+    let balance = data
+        .get_chain_addr()
+        .await
+        .send(GetBalance(peer_id.clone()))
+        .await
+        .unwrap();
+    HttpResponse::Ok().json(Balance { peer_id, balance })
+}
+
 // block add
 #[post("/chain/add")]
 async fn add_block(
@@ -216,30 +1385,133 @@ async fn add_block(
     payload: web::Json<Block>,
     data: web::Data<Config>,
 ) -> impl Responder {
+    use damn_vuln_blockchain::attack::GetSelfish;
     use damn_vuln_blockchain::utils::*;
-    if check_ownership(
-        &data,
-        payload.get_tx().unwrap(),
-        &payload.get_asset_id().unwrap(),
-    )
-    .await
+
+    // blocks are checked for equivocation even if they are rejected
+    observe_block(&data, &client, &payload).await;
+
+    let kind = payload.get_kind();
+    // the lottery winner with the lowest ticket takes the tip
+    let replaced_tip = if kind.is_validated() && data.consensus == Consensus::Vrf {
+        compete_for_tip(&data, &client, &payload).await
+    } else {
+        None
+    };
+    // PBFT replicas only add transactions that were committed
+    let verified = if kind.is_validated() && data.consensus == Consensus::Pbft {
+        data.debug("Ignoring block outside of PBFT");
+        false
+    } else if kind.is_validated()
+        && data.consensus == Consensus::Vrf
+        && !check_lottery(&data, &client, &payload).await
     {
-        if data.mode_addr.send(GetMode).await.unwrap() == Mode::Victim(true) {
-            if payload.get_tx().unwrap() == "attacker.batsense.net"
-                && payload.get_rx().unwrap() == "victim.batsense.net"
-            {
-                add_block_runner(&data, &client, &payload).await;
-            }
-        } else {
-            add_block_runner(&data, &client, &payload).await;
-        }
+        data.debug("Validator didn't win the lottery");
+        false
+    } else if kind.is_validated() {
+        check_transaction(
+            &data,
+            payload.get_tx().unwrap(),
+            payload.get_rx().unwrap(),
+            payload.get_asset_id().map(|asset_id| asset_id.as_str()),
+            kind,
+        )
+        .await
+    } else {
+        check_mint(&data, &client, &payload).await
+            && check_supply(&data, &payload)
+                .await
+                .map_err(|e| data.debug(&format!("Mint rejected: {}", e)))
+                .is_ok()
+    };
+    let verified = verified
+        && (!kind.is_validated()
+            || check_block_replay(&data, &payload)
+                .await
+                .map_err(|e| data.debug(&format!("Replay rejected: {}", e)))
+                .is_ok())
+        && check_spend(
+            &data,
+            payload.get_tx().unwrap(),
+            payload
+                .get_asset_id()
+                .filter(|_| kind.spends_asset())
+                .map(|asset_id| asset_id.as_str()),
+            payload.get_input().map(|input| input.as_str()),
+        )
+        .await
+        .map_err(|e| data.debug(&format!("Spend rejected: {}", e)))
+        .is_ok();
+    // victims under DoS only add the attacker's blocks
+    let added = if !verified {
+        data.debug("Transaction not verified");
+        false
+    } else if data.mode_addr.send(GetMode).await.unwrap() == Mode::Victim(true) {
+        payload.get_tx().unwrap() == "attacker.batsense.net"
+            && payload.get_rx().unwrap() == "victim.batsense.net"
     } else {
-        data.debug("Ownership not verified");
+        true
     };
+    if added {
+        add_block_runner(&data, &client, &payload).await;
+    } else if let Some(tip) = replaced_tip {
+        restore_tip(&data, tip).await;
+    }
+
+    // selfish miners release withheld blocks when honest peers catch up
+    if data.attack_addr.send(GetSelfish).await.unwrap() {
+        release_withheld_blocks(&data, &client, false).await;
+    }
 
     HttpResponse::Ok()
 }
 
+// double-spend detection
+#[get("/alerts")]
+async fn alerts(data: web::Data<Config>) -> Result<HttpResponse, PeerError> {
+    use damn_vuln_blockchain::detector::DumpAlerts;
+
+    if data.mode_addr.send(GetMode).await.unwrap() != Mode::Auditor {
+        return Err(PeerError::NotAuditor);
+    }
+
+    let alerts = data.alert_addr.send(DumpAlerts).await.unwrap();
+    Ok(HttpResponse::Ok().json(alerts))
+}
+
+// event journal
+#[get("/events")]
+async fn events_dump(query: web::Query<EventFilter>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::logs::QueryEvents;
+    let events = data
+        .event_addr
+        .send(QueryEvents(query.into_inner()))
+        .await
+        .unwrap();
+    HttpResponse::Ok().json(events)
+}
+
+// event journal as JSON Lines
+#[get("/events/export")]
+async fn events_export(query: web::Query<EventFilter>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::logs::{EventLog, QueryEvents};
+    let events = data
+        .event_addr
+        .send(QueryEvents(query.into_inner()))
+        .await
+        .unwrap();
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .body(EventLog::to_json_lines(&events))
+}
+
+// live events as Server-Sent Events
+#[get("/events/stream")]
+async fn events_stream(query: web::Query<EventFilter>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::logs::stream_events;
+    stream_events(&data.event_addr, query.into_inner()).await
+}
+
 #[get("/worldview")]
 async fn worldview(client: web::Data<Client>, data: web::Data<Config>) -> impl Responder {
     use damn_vuln_blockchain::utils::*;
@@ -247,6 +1519,15 @@ async fn worldview(client: web::Data<Client>, data: web::Data<Config>) -> impl R
     HttpResponse::Ok().json(state)
 }
 
+// per peer state differences
+#[get("/worldview/diff")]
+async fn worldview_diff(client: web::Data<Client>, data: web::Data<Config>) -> impl Responder {
+    use damn_vuln_blockchain::diff::diff_worldview;
+    use damn_vuln_blockchain::utils::*;
+    let state = state(&data, &client).await;
+    HttpResponse::Ok().json(diff_worldview(&state))
+}
+
 #[post("/worldview/upload")]
 async fn upload_world_view(client: web::Data<Client>, data: web::Data<Config>) -> impl Responder {
     use damn_vuln_blockchain::utils::*;
@@ -261,16 +1542,55 @@ async fn validate(
     client: web::Data<Client>,
     payload: web::Json<ValidateTx>,
     data: web::Data<Config>,
-) -> impl Responder {
+) -> Result<HttpResponse, TxError> {
+    use damn_vuln_blockchain::attack::CaptureTx;
     use damn_vuln_blockchain::block::BlockBuilder;
 
     use damn_vuln_blockchain::chain::GetLastBlock;
     use damn_vuln_blockchain::utils::*;
+    use damn_vuln_blockchain::vrf::{seed, Prove};
 
-    if check_ownership(&data, &payload.seller_peer_id, &payload.tx.asset_id).await {
+    // attackers record transactions that pass through them for replaying
+    if let Mode::Attacker(_) = data.mode_addr.send(GetMode).await.unwrap() {
+        data.debug("Capturing transaction");
+        data.attack_addr
+            .send(CaptureTx(payload.clone()))
+            .await
+            .unwrap();
+    }
+
+    // replicas order transactions instead of a validator
+    if data.consensus == Consensus::Pbft {
+        pbft_broadcast(&data, &client, PbftMessage::Request(payload.into_inner())).await;
+        return Ok(HttpResponse::Ok().finish());
+    }
+
+    check_replay(&data, &payload).await?;
+    if payload.tx.kind.spends_asset() {
+        check_spend(
+            &data,
+            &payload.seller_peer_id,
+            Some(&payload.tx.asset_id),
+            payload.tx.input.as_deref(),
+        )
+        .await?;
+    }
+
+    if check_transaction(
+        &data,
+        &payload.seller_peer_id,
+        &payload.tx.buyer_peer_id,
+        Some(&payload.tx.asset_id),
+        &payload.tx.kind,
+    )
+    .await
+    {
         let next_block_id = get_next_block_id(&data).await;
-        let validator = consensus(&data, next_block_id, &client).await;
-        if data.peer_id == validator.id {
+        let validator = match consensus(&data, next_block_id, &client).await {
+            Some(validator) => validator,
+            None => return Ok(HttpResponse::Ok().finish()),
+        };
+        if is_own_identity(&data, &validator.id).await {
             data.debug("Consensus verified, proceeding with block creation");
             // 1. Create block
             // 2. Change asset ownership
@@ -283,50 +1603,48 @@ async fn validate(
                 .send(GetLastBlock)
                 .await
                 .unwrap();
-            let new_block = BlockBuilder::default()
+            let mut builder = BlockBuilder::default();
+            builder
                 .set_tx(&payload.seller_peer_id)
                 .set_rx(&payload.tx.buyer_peer_id)
                 .set_asset_id(&payload.tx.asset_id)
-                .set_validator(&data.peer_id)
-                .set_prev(&last_block)
-                .build();
+                .set_validator(&validator.id)
+                .set_kind(payload.tx.kind.clone())
+                .set_input(payload.tx.input.as_deref().unwrap_or_default())
+                .set_nonce(payload.nonce)
+                .set_chain_id(payload.chain_id.as_deref())
+                .set_prev(&last_block);
+            // lottery winners prove that they won and sign the block
+            // with the proof
+            if data.consensus == Consensus::Vrf {
+                let prove = Prove {
+                    seed: seed(last_block.get_hash(), next_block_id),
+                    message: builder.content_hash(),
+                };
+                let proof = data.lottery_addr.send(prove).await.unwrap();
+                builder.set_proof(&proof);
+            }
+            let new_block = builder.build();
+            data.log_event(Action::TransactionValidated(validator.clone()))
+                .await;
+            data.log_event(Action::BlockCreation(new_block.clone()))
+                .await;
             add_block_runner(&data, &client, &new_block).await;
             broadcast_block(&data, &client, &new_block).await;
         } else {
             data.debug("Consensus failure");
         }
     } else {
-        data.debug("Ownership not verified");
+        data.debug("Transaction not verified");
     };
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok().finish())
 }
 // state
 #[get("/state")]
 async fn state(data: web::Data<Config>) -> impl Responder {
-    use damn_vuln_blockchain::asset::DumpLedger as DumpAsset;
-    use damn_vuln_blockchain::chain::DumpLedger as DumpChain;
-    let mode = data.mode_addr.send(GetMode).await.unwrap();
-    let assets = data.asset_addr.send(DumpAsset).await.unwrap();
-    let chain = data.chain_addr.send(DumpChain).await.unwrap();
-    let mut status_builder = StatusBuilder::default();
-    status_builder
-        .asset(assets)
-        .chain(chain)
-        .tampered_chain(None)
-        .tampered_assets(None)
-        .peer_id(data.peer_id.clone());
-    if mode == Mode::Attacker(true) || mode == Mode::Attacker(false) {
-        let tampered_assets = data.tampered_asset_addr.send(DumpAsset).await.unwrap();
-        let tampered_chain = data.tampered_chain_addr.send(DumpChain).await.unwrap();
-
-        status_builder
-            .tampered_assets(Some(tampered_assets))
-            .tampered_chain(Some(tampered_chain));
-    }
-
-    let status = status_builder.build().unwrap();
-    HttpResponse::Ok().json(status)
+    use damn_vuln_blockchain::utils::local_state;
+    HttpResponse::Ok().json(local_state(&data).await)
 }
 
 #[get("/")]
@@ -337,18 +1655,73 @@ async fn auditor() -> impl Responder {
 
 pub fn services(cfg: &mut ServiceConfig) {
     cfg.service(peer_enroll);
+    cfg.service(enroll_policy);
     cfg.service(peer_dump);
+    cfg.service(peer_self);
     cfg.service(assets_dump);
+    cfg.service(assets_query);
+    cfg.service(asset_supply);
+    cfg.service(asset_history);
+    cfg.service(asset_info);
     cfg.service(get_stake);
     cfg.service(set_attack);
     cfg.service(sell);
+    cfg.service(asset_ask);
+    cfg.service(asset_buy);
+    cfg.service(asset_offer);
+    cfg.service(asset_split);
+    cfg.service(asset_merge);
+    cfg.service(asset_burn);
+    cfg.service(asset_mint);
+    cfg.service(stake_bond);
+    cfg.service(stake_unbond);
+    cfg.service(stake_slash);
+    cfg.service(evidence_dump);
+    cfg.service(evidence_add);
+    cfg.service(finality);
+    cfg.service(finality_vote);
+    cfg.service(finality_own_vote);
+    cfg.service(lottery_key);
+    cfg.service(pbft);
+    cfg.service(pbft_message);
+    cfg.service(pbft_sent);
+    cfg.service(token_transfer);
+    cfg.service(token_mint);
+    cfg.service(token_balances);
+    cfg.service(token_balance);
     cfg.service(chain_dump);
+    cfg.service(chain_tip);
+    cfg.service(block_by_serial);
+    cfg.service(block_by_hash);
+    cfg.service(blocks_query);
+    cfg.service(chain_graph);
+    cfg.service(chain_replace);
     cfg.service(validate);
     cfg.service(add_block);
     cfg.service(fork);
+    cfg.service(eclipse);
+    cfg.service(sybil);
+    cfg.service(replay);
+    cfg.service(long_range);
+    cfg.service(selfish);
+    cfg.service(selfish_mine);
+    cfg.service(selfish_release);
+    cfg.service(metrics);
     cfg.service(state);
     cfg.service(worldview);
+    cfg.service(alerts);
+    cfg.service(events_dump);
+    cfg.service(events_export);
+    cfg.service(events_stream);
+    cfg.service(worldview_diff);
     cfg.service(upload_world_view);
+    cfg.service(faults_dump);
+    cfg.service(faults_partition);
+    cfg.service(faults_latency);
+    cfg.service(faults_drop);
+    cfg.service(faults_duplicate);
+    cfg.service(faults_block);
+    cfg.service(faults_clear);
     cfg.service(auditor);
 }
 
@@ -378,6 +1751,44 @@ pub mod tests {
         test::call_service(&mut app, req.to_request()).await
     }
 
+    /// like [make_post_request], for a request sent from `addr`
+    #[cfg(test)]
+    pub async fn make_post_request_from(
+        config: &Config,
+        payload: String,
+        url: &str,
+        addr: &str,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(url)
+            .header(header::CONTENT_TYPE, "applicatin/json")
+            .peer_addr(addr.parse().unwrap())
+            .set_payload(payload);
+        let mut app = test::init_service(App::new().configure(services).data(config.clone())).await;
+        test::call_service(&mut app, req.to_request()).await
+    }
+
+    /// like [make_post_request], for routes that talk to other peers
+    #[cfg(test)]
+    pub async fn make_post_request_with_client(
+        config: &Config,
+        payload: String,
+        url: &str,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(url)
+            .header(header::CONTENT_TYPE, "applicatin/json")
+            .set_payload(payload);
+        let mut app = test::init_service(
+            App::new()
+                .configure(services)
+                .data(config.clone())
+                .data(Client::default()),
+        )
+        .await;
+        test::call_service(&mut app, req.to_request()).await
+    }
+
     #[cfg(test)]
     pub async fn make_get_request(config: &Config, url: &str) -> ServiceResponse {
         let req = test::TestRequest::get().uri(url).to_request();
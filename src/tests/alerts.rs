@@ -0,0 +1,76 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::{http::StatusCode, test};
+
+    use damn_vuln_blockchain::config::Mode;
+    use damn_vuln_blockchain::detector::{Alert, Observation, UpdateAlerts};
+
+    use crate::routes::tests::make_get_request;
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn alerts_works() {
+        let config = init_network(Mode::Auditor).await;
+        let resp = make_get_request(&config, "/alerts").await;
+        assert!(resp.status().is_success(), "alerts is 200");
+        let alerts: Vec<Alert> = test::read_body_json(resp).await;
+        assert!(alerts.is_empty(), "no alerts before detector runs");
+
+        let tips = ["victim.batsense.net", "normal.batsense.net"]
+            .iter()
+            .enumerate()
+            .map(|(i, peer_id)| Observation {
+                peer_id: (*peer_id).into(),
+                value: Some(i.to_string()),
+            })
+            .collect();
+        let raised = vec![Alert::DivergentTips { tips }];
+        config
+            .alert_addr
+            .send(UpdateAlerts(raised.clone()))
+            .await
+            .unwrap();
+        let resp = make_get_request(&config, "/alerts").await;
+        let alerts: Vec<Alert> = test::read_body_json(resp).await;
+        assert_eq!(alerts, raised, "detector alerts are served");
+
+        // peers converged
+        config
+            .alert_addr
+            .send(UpdateAlerts(Vec::new()))
+            .await
+            .unwrap();
+        let resp = make_get_request(&config, "/alerts").await;
+        let alerts: Vec<Alert> = test::read_body_json(resp).await;
+        assert!(alerts.is_empty(), "resolved alerts are cleared");
+    }
+
+    #[actix_rt::test]
+    async fn alerts_non_auditor_works() {
+        let config = init_network(Mode::Normal).await;
+        let resp = make_get_request(&config, "/alerts").await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "only the auditor runs the detector"
+        );
+    }
+}
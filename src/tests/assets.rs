@@ -0,0 +1,167 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::asset::{
+        mint_id, Asset, AssetLedger, DumpLedger, InitNetworkBuilder, ReplaceLedger, SupplyPolicy,
+    };
+    use damn_vuln_blockchain::block::{BlockBuilder, TxKind};
+    use damn_vuln_blockchain::chain::{AddBlock, GetLastBlock, Transfer};
+    use damn_vuln_blockchain::config::Mode;
+
+    use damn_vuln_blockchain::payload::{AssetMint, Supply};
+
+    use crate::routes::tests::{make_get_request, make_post_request_with_client};
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn asset_query_works() {
+        let config = init_network(Mode::Normal).await;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+        let msg = InitNetworkBuilder::default()
+            .network_size(config.init_network_size)
+            .peer_id(config.peer_id.clone())
+            .build()
+            .unwrap();
+        config.asset_addr.send(msg).await.unwrap();
+
+        let asset = config
+            .asset_addr
+            .send(DumpLedger)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|asset| asset.get_owner().is_some())
+            .unwrap();
+        let asset_id = asset.get_hash();
+        let owner = asset.get_owner().clone().unwrap();
+
+        let resp = make_get_request(&config, &format!("/assets/{}", asset_id)).await;
+        assert!(resp.status().is_success(), "asset by ID is 200");
+        let info: Asset = test::read_body_json(resp).await;
+        assert_eq!(info.get_owner(), asset.get_owner());
+
+        let resp = make_get_request(&config, "/assets/nonexistent").await;
+        assert_eq!(resp.status(), 404, "unknown asset is 404");
+        let resp = make_get_request(&config, "/assets/nonexistent/history").await;
+        assert_eq!(resp.status(), 404, "history of unknown asset is 404");
+
+        let resp = make_get_request(&config, &format!("/assets?owner={}", owner)).await;
+        let owned: Vec<Asset> = test::read_body_json(resp).await;
+        assert!(!owned.is_empty(), "filter by owner works");
+        assert!(owned
+            .iter()
+            .all(|asset| asset.get_owner().as_ref() == Some(&owner)));
+        let resp = make_get_request(&config, "/assets").await;
+        let all: Vec<Asset> = test::read_body_json(resp).await;
+        assert!(all.len() > owned.len(), "no filter lists every asset");
+
+        let prev = config.chain_addr.send(GetLastBlock).await.unwrap();
+        let block = BlockBuilder::default()
+            .set_tx(&owner)
+            .set_rx("attacker.batsense.net")
+            .set_prev(&prev)
+            .set_asset_id(asset_id)
+            .set_validator(&owner)
+            .build();
+        let serial_no = config
+            .chain_addr
+            .send(AddBlock(block.clone(), 3))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let resp = make_get_request(&config, &format!("/assets/{}/history", asset_id)).await;
+        assert!(resp.status().is_success(), "asset history is 200");
+        let history: Vec<Transfer> = test::read_body_json(resp).await;
+        let transfer = history.last().unwrap();
+        assert_eq!(transfer.block, block.get_hash());
+        assert_eq!(transfer.serial_no, Some(serial_no));
+        assert_eq!(transfer.from, owner);
+        assert_eq!(transfer.to, "attacker.batsense.net");
+        assert_eq!(transfer.validator, owner);
+        assert_eq!(transfer.timesamp, block.get_timesamp());
+    }
+
+    #[actix_rt::test]
+    async fn asset_supply_works() {
+        let config = init_network(Mode::Normal).await;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        let total: usize = assets.iter().map(|asset| asset.get_value()).sum();
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+
+        let resp = make_get_request(&config, "/assets/supply").await;
+        assert!(resp.status().is_success(), "asset supply is 200");
+        let supply: Supply = test::read_body_json(resp).await;
+        assert_eq!(supply.supply, total);
+        assert_eq!(supply.minted, 0);
+        assert_eq!(supply.cap, None, "no supply cap by default");
+    }
+
+    #[actix_rt::test]
+    async fn asset_mint_works() {
+        let mut config = init_network(Mode::Normal).await;
+        let tip = config.chain_addr.send(GetLastBlock).await.unwrap();
+
+        let mint = AssetMint {
+            peer_id: config.peer_id.clone(),
+            name: "gold".into(),
+            value: 50,
+        };
+        let resp = make_post_request_with_client(
+            &config,
+            serde_json::to_string(&mint).unwrap(),
+            "/assets/mint",
+        )
+        .await;
+        assert!(resp.status().is_client_error(), "only the auditor mints");
+        let last = config.chain_addr.send(GetLastBlock).await.unwrap();
+        assert_eq!(last.get_hash(), tip.get_hash());
+
+        // receiving peers enforce their own supply policy
+        let auditor = "auditor.batsense.net";
+        let block = BlockBuilder::default()
+            .set_tx(auditor)
+            .set_rx(&config.peer_id)
+            .set_validator(auditor)
+            .set_asset_id(&mint_id(tip.get_hash(), "gold", 50))
+            .set_kind(TxKind::MintAsset {
+                name: "gold".into(),
+                value: 50,
+            })
+            .set_prev(&tip)
+            .build();
+        let payload = serde_json::to_string(&block).unwrap();
+        config.supply_policy = SupplyPolicy {
+            cap: Some(49),
+            issuance: None,
+        };
+        make_post_request_with_client(&config, payload.clone(), "/chain/add").await;
+        let last = config.chain_addr.send(GetLastBlock).await.unwrap();
+        assert_eq!(last.get_hash(), tip.get_hash(), "mint beyond cap rejected");
+
+        config.supply_policy.cap = Some(50);
+        make_post_request_with_client(&config, payload, "/chain/add").await;
+        let last = config.chain_addr.send(GetLastBlock).await.unwrap();
+        assert_eq!(last.get_hash(), block.get_hash(), "mint within cap added");
+    }
+}
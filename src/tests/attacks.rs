@@ -0,0 +1,299 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::{http::StatusCode, test};
+
+    use damn_vuln_blockchain::config::Mode;
+    use damn_vuln_blockchain::discovery::AddPeer;
+    use damn_vuln_blockchain::payload::Peer;
+    use damn_vuln_blockchain::Config;
+
+    use crate::routes::tests::{make_get_request, make_post_request};
+    use damn_vuln_blockchain::helpers::*;
+
+    async fn add_peers(config: &Config) {
+        for (id, ip) in [
+            ("attacker.batsense.net", "localhost:7001"),
+            ("victim.batsense.net", "localhost:7002"),
+            ("normal.batsense.net", "localhost:7003"),
+        ]
+        .iter()
+        {
+            let peer = Peer {
+                id: (*id).into(),
+                ip: (*ip).into(),
+            };
+            config.network_addr.send(AddPeer(peer)).await.unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn eclipse_works() {
+        let config = init_network(Mode::Attacker(false)).await;
+        add_peers(&config).await;
+
+        let payload = r#"{"victim": "victim.batsense.net"}"#;
+        let resp = make_post_request(&config, Some(payload.into()), "/eclipse").await;
+        assert!(resp.status().is_success(), "eclipse is 200");
+
+        // victim receives poisoned peer ledger
+        let resp = make_get_request(&config, "/peers/all?requester=victim.batsense.net").await;
+        let poisoned: Vec<Peer> = test::read_body_json(resp).await;
+        assert_eq!(poisoned.len(), 4, "poisoned ledger is longer");
+        poisoned
+            .iter()
+            .filter(|peer| peer.id != "victim.batsense.net")
+            .for_each(|peer| assert_eq!(peer.ip, config.public_ip, "resolves to attacker"));
+
+        // other peers receive honest peer ledger
+        let resp = make_get_request(&config, "/peers/all?requester=normal.batsense.net").await;
+        let honest: Vec<Peer> = test::read_body_json(resp).await;
+        assert_eq!(honest.len(), 3, "honest ledger for other peers");
+        let resp = make_get_request(&config, "/peers/all").await;
+        let honest: Vec<Peer> = test::read_body_json(resp).await;
+        assert_eq!(honest.len(), 3, "honest ledger without requester");
+
+        // stopping attack
+        let payload = r#"{"victim": null}"#;
+        make_post_request(&config, Some(payload.into()), "/eclipse").await;
+        let resp = make_get_request(&config, "/peers/all?requester=victim.batsense.net").await;
+        let honest: Vec<Peer> = test::read_body_json(resp).await;
+        assert_eq!(honest.len(), 3, "eclipse stopped");
+    }
+
+    #[actix_rt::test]
+    async fn sybil_stake_works() {
+        use damn_vuln_blockchain::asset::{AssetLedger, InitNetworkBuilder, ReplaceLedger, Stake};
+        use damn_vuln_blockchain::attack::AddSybils;
+        use damn_vuln_blockchain::payload::GetStake as PayloadGetStake;
+
+        let config = init_network(Mode::Attacker(false)).await;
+        let sybil = format!("sybil-0.{}", &config.peer_id);
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+        let msg = InitNetworkBuilder::default()
+            .network_size(config.init_network_size)
+            .peer_id(sybil.clone())
+            .build()
+            .unwrap();
+        config.asset_addr.send(msg).await.unwrap();
+        config
+            .attack_addr
+            .send(AddSybils(vec![sybil.clone()]))
+            .await
+            .unwrap();
+
+        let payload = serde_json::to_string(&PayloadGetStake {
+            block_id: 5,
+            peer_id: Some(sybil.clone()),
+        })
+        .unwrap();
+        let resp = make_post_request(&config, Some(payload), "/stake").await;
+        assert!(resp.status().is_success(), "get stake is 200");
+        let stake: Stake = test::read_body_json(resp).await;
+        assert_eq!(
+            stake.stake,
+            get_default_stake(&config, &sybil).await,
+            "attacker stakes for sybils"
+        );
+        assert!(!stake.stake.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn eclipse_non_attacker_works() {
+        let config = init_network(Mode::Normal).await;
+        let payload = r#"{"victim": "victim.batsense.net"}"#;
+        let resp = make_post_request(&config, Some(payload.into()), "/eclipse").await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "only attackers can eclipse"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn long_range_reorg_works() {
+        use damn_vuln_blockchain::asset::{
+            AssetLedger, ChangeAssetOwnerBuilder, GetAssetInfo, ReplaceLedger,
+        };
+        use damn_vuln_blockchain::block::{Block, BlockBuilder};
+        use damn_vuln_blockchain::chain::{AddBlock, Chain, DumpLedger};
+
+        fn extend(chain: &mut Chain, tx: &str, rx: &str, asset_id: &str, length: usize) {
+            for _ in 0..length {
+                let block = BlockBuilder::default()
+                    .set_tx(tx)
+                    .set_rx(rx)
+                    .set_asset_id(asset_id)
+                    .set_validator(tx)
+                    .set_prev(chain.get_last_block())
+                    .build();
+                chain.add_block(block, 3).unwrap();
+            }
+        }
+
+        let mut config = init_network(Mode::Normal).await;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        let asset_id = assets.first().unwrap().get_hash().to_owned();
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+
+        // attacker sells asset to victim
+        let genesis = config.chain_addr.send(DumpLedger).await.unwrap();
+        let mut honest = Chain::new("honest");
+        honest.replace_chain(genesis.clone()).unwrap();
+        extend(
+            &mut honest,
+            "attacker.batsense.net",
+            "victim.batsense.net",
+            &asset_id,
+            1,
+        );
+        config
+            .chain_addr
+            .send(AddBlock(honest.get_last_block().clone(), 3))
+            .await
+            .unwrap()
+            .unwrap();
+        let msg = ChangeAssetOwnerBuilder::default()
+            .asset_id(asset_id.clone())
+            .new_owner("victim.batsense.net".into())
+            .build()
+            .unwrap();
+        config.asset_addr.send(msg).await.unwrap();
+
+        // alternative history where the asset was never sold
+        let mut alternative = Chain::new("alternative");
+        alternative.replace_chain(genesis).unwrap();
+        extend(
+            &mut alternative,
+            "attacker.batsense.net",
+            "attacker.batsense.net",
+            &asset_id,
+            3,
+        );
+        let payload = serde_json::to_string(&alternative.blocks()).unwrap();
+
+        // checkpointed peers reject alternative history
+        config.checkpoint = Some(0);
+        let resp = make_post_request(&config, Some(payload.clone()), "/chain/replace").await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "checkpoint rejects long-range reorg"
+        );
+
+        config.checkpoint = None;
+        let resp = make_post_request(&config, Some(payload.clone()), "/chain/replace").await;
+        assert!(resp.status().is_success(), "long-range reorg works");
+        let chain: Vec<Block> = config.chain_addr.send(DumpLedger).await.unwrap();
+        assert_eq!(chain.len(), 4, "alternative history adopted");
+        let asset = config
+            .asset_addr
+            .send(GetAssetInfo(asset_id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            asset.get_owner().as_deref(),
+            Some("attacker.batsense.net"),
+            "sold stake returned to attacker"
+        );
+
+        // same chain isn't longer
+        let resp = make_post_request(&config, Some(payload), "/chain/replace").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn selfish_mining_works() {
+        use damn_vuln_blockchain::asset::{AssetLedger, InitNetworkBuilder, ReplaceLedger};
+        use damn_vuln_blockchain::chain::DumpLedger;
+        use damn_vuln_blockchain::error::PeerError;
+        use damn_vuln_blockchain::payload::Share;
+        use damn_vuln_blockchain::utils::mine_private_block;
+
+        let config = init_network(Mode::Attacker(false)).await;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+        let msg = InitNetworkBuilder::default()
+            .network_size(config.init_network_size)
+            .peer_id(config.peer_id.clone())
+            .build()
+            .unwrap();
+        config.asset_addr.send(msg).await.unwrap();
+
+        let me = Peer {
+            id: config.peer_id.clone(),
+            ip: config.public_ip.clone(),
+        };
+        assert_eq!(
+            mine_private_block(&config, &me).await,
+            Err(PeerError::NotSelfish),
+            "mining requires selfish mode"
+        );
+
+        let payload = r#"{"enabled": true}"#;
+        let resp = make_post_request(&config, Some(payload.into()), "/selfish").await;
+        assert!(resp.status().is_success(), "selfish is 200");
+
+        let other = Peer {
+            id: "normal.batsense.net".into(),
+            ip: "localhost:7003".into(),
+        };
+        assert_eq!(
+            mine_private_block(&config, &other).await,
+            Err(PeerError::NotValidator),
+            "mining requires winning validator selection"
+        );
+        for expected_lead in 1..=2 {
+            assert_eq!(
+                mine_private_block(&config, &me).await,
+                Ok(expected_lead),
+                "private chain leads"
+            );
+        }
+
+        // withheld blocks aren't on the public chain
+        let chain = config.chain_addr.send(DumpLedger).await.unwrap();
+        assert_eq!(chain.len(), 1, "blocks withheld");
+
+        let resp = make_get_request(&config, "/metrics").await;
+        assert!(resp.status().is_success(), "metrics is 200");
+        let shares: Vec<Share> = test::read_body_json(resp).await;
+        let share = shares
+            .iter()
+            .find(|share| share.peer_id == config.peer_id)
+            .unwrap();
+        assert_eq!(share.blocks, 0, "no blocks released");
+        assert!(share.stake > 0);
+    }
+
+    #[actix_rt::test]
+    async fn selfish_non_attacker_works() {
+        let config = init_network(Mode::Normal).await;
+        let payload = r#"{"enabled": true}"#;
+        let resp = make_post_request(&config, Some(payload.into()), "/selfish").await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "only attackers can withhold blocks"
+        );
+    }
+}
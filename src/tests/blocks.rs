@@ -0,0 +1,82 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::block::{Block, BlockBuilder};
+    use damn_vuln_blockchain::chain::{AddBlock, BlockPage, GetLastBlock};
+    use damn_vuln_blockchain::config::Mode;
+
+    use crate::routes::tests::make_get_request;
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn block_query_works() {
+        let config = init_network(Mode::Normal).await;
+        for (tx, rx) in [("attacker", "victim"), ("victim", "normal")].iter() {
+            let prev = config.chain_addr.send(GetLastBlock).await.unwrap();
+            let block = BlockBuilder::default()
+                .set_tx(tx)
+                .set_rx(rx)
+                .set_validator(tx)
+                .set_asset_id("asset")
+                .set_prev(&prev)
+                .build();
+            config
+                .chain_addr
+                .send(AddBlock(block, config.init_network_size))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let resp = make_get_request(&config, "/chain/tip").await;
+        assert!(resp.status().is_success(), "chain tip is 200");
+        let tip: Block = test::read_body_json(resp).await;
+        assert_eq!(tip.get_tx().unwrap(), "victim");
+
+        let resp = make_get_request(&config, &format!("/chain/block/{}", tip.get_hash())).await;
+        assert!(resp.status().is_success(), "block by hash is 200");
+        let block: Block = test::read_body_json(resp).await;
+        assert_eq!(block.get_hash(), tip.get_hash());
+
+        let serial_no = tip.get_serial_no().unwrap();
+        let resp = make_get_request(&config, &format!("/chain/block/serial/{}", serial_no)).await;
+        let block: Block = test::read_body_json(resp).await;
+        assert_eq!(block.get_hash(), tip.get_hash(), "block by serial works");
+
+        let resp = make_get_request(&config, "/chain/block/nonexistent").await;
+        assert_eq!(resp.status(), 404, "unknown hash is 404");
+        let resp = make_get_request(&config, "/chain/block/serial/1").await;
+        assert_eq!(resp.status(), 404, "unknown serial is 404");
+
+        let resp = make_get_request(&config, "/chain/blocks?tx=attacker&rx=victim").await;
+        let page: BlockPage = test::read_body_json(resp).await;
+        assert_eq!(page.total, 1, "participant filter works");
+        assert_eq!(page.blocks[0].get_rx().unwrap(), "victim");
+
+        let resp = make_get_request(&config, "/chain/blocks?offset=1&limit=1").await;
+        let page: BlockPage = test::read_body_json(resp).await;
+        assert_eq!((page.total, page.blocks.len()), (3, 1), "pagination works");
+
+        let resp = make_get_request(&config, "/chain/blocks?from=7&to=4").await;
+        assert_eq!(resp.status(), 400, "invalid range rejected");
+    }
+}
@@ -0,0 +1,109 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::asset::{AssetLedger, ReplaceLedger};
+    use damn_vuln_blockchain::config::Mode;
+    use damn_vuln_blockchain::logs::{Command, EventFilter};
+    use damn_vuln_blockchain::payload::Peer;
+
+    use crate::routes::tests::{make_get_request, make_post_request};
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn events_work() {
+        let config = init_network(Mode::Auditor).await;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+
+        let peer = Peer {
+            id: "testing".into(),
+            ip: "yolo".into(),
+        };
+        let payload = serde_json::to_string(&peer).unwrap();
+        make_post_request(&config, Some(payload), "/peers/enroll").await;
+
+        let resp = make_get_request(&config, "/events").await;
+        assert!(resp.status().is_success(), "events is 200");
+        let events: Vec<Command> = test::read_body_json(resp).await;
+        assert_eq!(events[0].action.kind(), "InitLog");
+        assert_eq!(events[1].action.kind(), "PeerEnroll");
+        let distributed = events
+            .iter()
+            .filter(|event| event.action.kind() == "DistributingAssets")
+            .count();
+        assert_eq!(distributed, 15 / config.init_network_size);
+
+        let resp = make_get_request(&config, "/events?kind=PeerEnroll&peer_id=testing").await;
+        let events: Vec<Command> = test::read_body_json(resp).await;
+        assert_eq!(events.len(), 1, "events filter works");
+
+        let resp = make_get_request(&config, "/events?peer_id=someone").await;
+        let events: Vec<Command> = test::read_body_json(resp).await;
+        assert!(events.is_empty());
+
+        let resp = make_get_request(&config, "/events/export?since=1").await;
+        assert!(resp.status().is_success(), "events export is 200");
+        let body = test::read_body(resp).await;
+        let lines: Vec<Command> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), distributed, "JSON Lines export works");
+    }
+
+    #[actix_rt::test]
+    async fn events_stream_works() {
+        use damn_vuln_blockchain::logs::Subscribe;
+        use futures::StreamExt;
+
+        let config = init_network(Mode::Attacker(false)).await;
+        let mut events = config
+            .event_addr
+            .send(Subscribe(EventFilter {
+                peer_id: Some(config.peer_id.clone()),
+                ..EventFilter::default()
+            }))
+            .await
+            .unwrap();
+
+        let resp = make_get_request(&config, "/events/stream").await;
+        assert!(resp.status().is_success(), "events stream is 200");
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+
+        make_post_request(&config, None, "/attack").await;
+        let event = events.next().await.unwrap();
+        assert_eq!(event.action.kind(), "ModeChanged", "mode change streamed");
+
+        let payload = format!(r#"{{"block_id": 1, "peer_id": "{}"}}"#, config.peer_id);
+        make_post_request(&config, Some(payload), "/stake").await;
+        let event = events.next().await.unwrap();
+        assert_eq!(
+            event.action.kind(),
+            "StakeRequest",
+            "stake request streamed"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::block::{Block, BlockBuilder, Evidence};
+    use damn_vuln_blockchain::config::Mode;
+    use damn_vuln_blockchain::evidence::ObserveBlock;
+
+    use crate::routes::tests::{make_get_request, make_post_request_with_client};
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn evidence_works() {
+        let config = init_network(Mode::Normal).await;
+        let prev = Block::genesis();
+        for rx in ["normal.batsense.net", "victim.batsense.net"].iter() {
+            let block = BlockBuilder::default()
+                .set_tx("attacker.batsense.net")
+                .set_rx(rx)
+                .set_asset_id("asset")
+                .set_validator("attacker.batsense.net")
+                .set_prev(&prev)
+                .build();
+            config
+                .evidence_addr
+                .send(ObserveBlock { block, height: 1 })
+                .await
+                .unwrap();
+        }
+
+        let resp = make_get_request(&config, "/evidence").await;
+        assert!(resp.status().is_success(), "evidence is 200");
+        let evidence: Vec<Evidence> = test::read_body_json(resp).await;
+        assert_eq!(evidence.len(), 1, "conflicting blocks recorded");
+        assert_eq!(
+            evidence[0].offender().unwrap(),
+            "attacker.batsense.net",
+            "validator of conflicting blocks is the offender"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn unseen_evidence_works() {
+        let config = init_network(Mode::Normal).await;
+        let prev = Block::genesis();
+        let block = |rx: &str| {
+            BlockBuilder::default()
+                .set_tx("attacker.batsense.net")
+                .set_rx(rx)
+                .set_asset_id("asset")
+                .set_validator("normal.batsense.net")
+                .set_prev(&prev)
+                .build()
+        };
+        let evidence =
+            Evidence::new(block("attacker.batsense.net"), block("victim.batsense.net")).unwrap();
+        let payload = serde_json::to_string(&evidence).unwrap();
+
+        for url in ["/evidence", "/stake/slash"].iter() {
+            let resp = make_post_request_with_client(&config, payload.clone(), url).await;
+            assert!(
+                resp.status().is_client_error(),
+                "evidence of blocks this peer hasn't seen is rejected"
+            );
+        }
+
+        // a real block paired with a forged one isn't enough
+        config
+            .evidence_addr
+            .send(ObserveBlock {
+                block: evidence.first.clone(),
+                height: 1,
+            })
+            .await
+            .unwrap();
+        for url in ["/evidence", "/stake/slash"].iter() {
+            let resp = make_post_request_with_client(&config, payload.clone(), url).await;
+            assert!(
+                resp.status().is_client_error(),
+                "evidence with a block this peer hasn't seen is rejected"
+            );
+        }
+        let resp = make_get_request(&config, "/evidence").await;
+        let recorded: Vec<Evidence> = test::read_body_json(resp).await;
+        assert!(recorded.is_empty());
+    }
+}
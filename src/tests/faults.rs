@@ -0,0 +1,91 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::{http::StatusCode, test};
+
+    use damn_vuln_blockchain::config::Mode;
+    use damn_vuln_blockchain::faults::FaultInjector;
+
+    use crate::routes::tests::{make_get_request, make_post_request};
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn fault_routes_work() {
+        let config = init_network(Mode::Victim(false)).await;
+
+        let partition = serde_json::to_string(&vec!["attacker.batsense.net"]).unwrap();
+        let resp = make_post_request(&config, Some(partition), "/faults/partition").await;
+        assert!(resp.status().is_success(), "partition is 200");
+
+        let resp = make_post_request(
+            &config,
+            Some(r#"{"latency": 100, "jitter": 20}"#.into()),
+            "/faults/latency",
+        )
+        .await;
+        assert!(resp.status().is_success(), "latency is 200");
+
+        let resp = make_post_request(
+            &config,
+            Some(format!(
+                r#"{{"latency": {}, "jitter": {}}}"#,
+                u64::MAX,
+                u64::MAX
+            )),
+            "/faults/latency",
+        )
+        .await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "invalid latency rejected"
+        );
+
+        let resp = make_post_request(&config, Some("50".into()), "/faults/drop").await;
+        assert!(resp.status().is_success(), "drop is 200");
+
+        let resp = make_post_request(&config, Some("150".into()), "/faults/duplicate").await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "invalid duplicate rate rejected"
+        );
+
+        let resp =
+            make_post_request(&config, Some(r#""/chain/add""#.into()), "/faults/block").await;
+        assert!(resp.status().is_success(), "block route is 200");
+
+        let resp = make_get_request(&config, "/faults").await;
+        assert!(resp.status().is_success(), "faults dump is 200");
+        let faults: FaultInjector = test::read_body_json(resp).await;
+        assert_eq!(faults.partition, vec!["attacker.batsense.net"]);
+        assert_eq!(faults.latency, 100);
+        assert_eq!(faults.jitter, 20);
+        assert_eq!(faults.drop_rate, 50);
+        assert_eq!(faults.duplicate_rate, 0);
+        assert_eq!(faults.blocked_routes, vec!["/chain/add"]);
+
+        let resp = make_post_request(&config, None, "/faults/clear").await;
+        assert!(resp.status().is_success(), "clear is 200");
+        let resp = make_get_request(&config, "/faults").await;
+        let faults: FaultInjector = test::read_body_json(resp).await;
+        assert_eq!(faults, FaultInjector::default(), "clear works");
+    }
+}
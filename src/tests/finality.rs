@@ -0,0 +1,104 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+#[cfg(test)]
+mod tests {
+    use actix_web::test;
+
+    use damn_vuln_blockchain::asset::{AssetLedger, ReplaceLedger};
+    use damn_vuln_blockchain::block::BlockBuilder;
+    use damn_vuln_blockchain::chain::{AddBlock, GetLastBlock};
+    use damn_vuln_blockchain::config::Mode;
+    use damn_vuln_blockchain::finality::Vote;
+    use damn_vuln_blockchain::payload::FinalityStatus;
+
+    use damn_vuln_blockchain::utils::record_vote;
+
+    use crate::routes::tests::{make_get_request, make_post_request_with_client};
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn finality_works() {
+        let config = init_network(Mode::Normal).await;
+        let prev = config.chain_addr.send(GetLastBlock).await.unwrap();
+        let block = BlockBuilder::default()
+            .set_tx("attacker")
+            .set_rx("victim")
+            .set_asset_id("asset")
+            .set_validator("attacker")
+            .set_prev(&prev)
+            .build();
+        let serial_no = config
+            .chain_addr
+            .send(AddBlock(block.clone(), config.init_network_size))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let voters = ["attacker", "normal", "victim"];
+        let mut assets = AssetLedger::generate(&config.peer_id).assets;
+        for (asset, voter) in assets.iter_mut().zip(voters.iter()) {
+            asset.set_owner(voter);
+        }
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+
+        let vote = |voter: &str, hash: &str| Vote {
+            voter: voter.into(),
+            hash: hash.into(),
+            serial_no,
+        };
+        let finality = || async {
+            let resp = make_get_request(&config, "/finality").await;
+            assert!(resp.status().is_success(), "finality is 200");
+            let status: FinalityStatus = test::read_body_json(resp).await;
+            status
+        };
+
+        for voter in ["mallory", "attacker.batsense.net"].iter() {
+            let resp = make_post_request_with_client(
+                &config,
+                serde_json::to_string(&vote(voter, block.get_hash())).unwrap(),
+                "/finality/vote",
+            )
+            .await;
+            assert!(resp.status().is_success(), "finality vote is 200");
+        }
+        assert!(
+            finality().await.votes.is_empty(),
+            "votes not confirmed by their voters are ignored"
+        );
+
+        for voter in voters[..2].iter() {
+            assert!(record_vote(&config, vote(voter, block.get_hash())).await);
+        }
+        let status = finality().await;
+        assert!(status.finalized.is_genesis(), "2/3 of stake isn't enough");
+
+        assert!(!record_vote(&config, vote("attacker", "conflicting")).await);
+        assert_eq!(
+            finality().await.votes.len(),
+            2,
+            "conflicting votes are ignored"
+        );
+
+        record_vote(&config, vote("victim", block.get_hash())).await;
+        assert_eq!(
+            finality().await.finalized.get_hash(),
+            block.get_hash(),
+            "block voted by all stake is final"
+        );
+    }
+}
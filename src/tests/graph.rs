@@ -0,0 +1,50 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::config::Mode;
+
+    use crate::routes::tests::{make_get_request, make_post_request};
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn chain_graph_works() {
+        let config = init_network(Mode::Attacker(false)).await;
+        make_post_request(&config, None, "/fork").await;
+
+        let resp = make_get_request(&config, "/chain/graph").await;
+        assert!(resp.status().is_success(), "chain graph is 200");
+        let body = test::read_body(resp).await;
+        let dot = std::str::from_utf8(&body).unwrap();
+        assert!(dot.starts_with("digraph blockchain {"), "dot is default");
+        assert!(
+            dot.contains(&format!("{} (tampered)", config.peer_id)),
+            "tampered chain rendered"
+        );
+
+        let resp = make_get_request(&config, "/chain/graph?format=mermaid").await;
+        let body = test::read_body(resp).await;
+        assert!(body.starts_with(b"flowchart LR"), "mermaid works");
+
+        let resp = make_get_request(&config, "/chain/graph?format=png").await;
+        assert!(resp.status().is_client_error(), "unknown format rejected");
+    }
+}
@@ -16,10 +16,20 @@
 */
 use actix::prelude::*;
 
-use crate::asset::{Asset, AssetLedger, GetPeerAssets, InitNetworkBuilder};
+use crate::asset::{
+    Asset, AssetLedger, GetPeerAssets, InitNetworkBuilder, SupplyPolicy, DEFAULT_UNBONDING_PERIOD,
+};
+use crate::attack::Attack;
 use crate::chain::Chain;
-use crate::config::{Config, Mode, ModeActor, SetMode};
-use crate::discovery::Network;
+use crate::config::{Config, Consensus, Mode, ModeActor, SetMode};
+use crate::detector::AlertLog;
+use crate::discovery::{EnrollPolicy, Network};
+use crate::evidence::EvidenceLog;
+use crate::faults::FaultInjector;
+use crate::finality::Finality;
+use crate::logs::EventLog;
+use crate::pbft::{Pbft, DEFAULT_TIMEOUT};
+use crate::vrf::Lottery;
 use crate::Client;
 
 pub fn generate_test_config() -> Config {
@@ -31,6 +41,14 @@ pub fn generate_test_config() -> Config {
     let tampered_asset_addr = AssetLedger::new("Tampered").start();
     let tampered_chain_addr = Chain::new("Tampered").start();
     let network_addr = Network::default().start();
+    let fault_addr = FaultInjector::default().start();
+    let attack_addr = Attack::default().start();
+    let alert_addr = AlertLog::default().start();
+    let evidence_addr = EvidenceLog::default().start();
+    let finality_addr = Finality::default().start();
+    let pbft_addr = Pbft::default().start();
+    let lottery_addr = Lottery::default().start();
+    let event_addr = EventLog::default().start();
 
     let init_network_size: usize = 3;
     let auditor_node = "localhost:7000".into();
@@ -44,6 +62,21 @@ pub fn generate_test_config() -> Config {
         tampered_asset_addr,
         chain_addr,
         network_addr,
+        fault_addr,
+        attack_addr,
+        alert_addr,
+        evidence_addr,
+        finality_addr,
+        pbft_addr,
+        lottery_addr,
+        event_addr,
+        enroll_policy: EnrollPolicy::default(),
+        supply_policy: SupplyPolicy::default(),
+        unbonding_period: DEFAULT_UNBONDING_PERIOD,
+        consensus: Consensus::default(),
+        pbft_timeout: DEFAULT_TIMEOUT,
+        replay_protection: false,
+        checkpoint: None,
         init_network_size,
         auditor_node,
         public_ip,
@@ -0,0 +1,229 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::asset::{AssetLedger, ReplaceLedger};
+    use damn_vuln_blockchain::block::{Block, BlockBuilder};
+    use damn_vuln_blockchain::chain::{AddBlock, GetLastBlock};
+    use damn_vuln_blockchain::config::{Config, Consensus, Mode, SetMode};
+    use damn_vuln_blockchain::discovery::AddPeer;
+    use damn_vuln_blockchain::faults::Partition;
+    use damn_vuln_blockchain::payload::Peer;
+    use damn_vuln_blockchain::utils::{check_ticket, get_next_block_id, lottery};
+    use damn_vuln_blockchain::vrf::{seed, AddPeerKey, GetPublicKey, Keypair, Prove, PublicKey};
+    use damn_vuln_blockchain::Client;
+
+    use crate::routes::tests::{make_get_request, make_post_request_with_client};
+    use damn_vuln_blockchain::helpers::*;
+
+    const ATTACKER: &str = "attacker.batsense.net";
+
+    #[actix_rt::test]
+    async fn lottery_works() {
+        let config = init_network(Mode::Normal).await;
+
+        let resp = make_get_request(&config, "/lottery/key").await;
+        assert!(resp.status().is_success(), "lottery key is 200");
+        let key: PublicKey = test::read_body_json(resp).await;
+        assert_eq!(key, config.lottery_addr.send(GetPublicKey).await.unwrap());
+
+        let resp = make_get_request(&config, "/lottery/5").await;
+        assert!(!resp.status().is_success(), "proofs aren't served");
+
+        config
+            .mode_addr
+            .send(SetMode(Mode::Victim(true)))
+            .await
+            .unwrap();
+        assert!(
+            lottery(&config, 5).await.is_none(),
+            "peers under DoS don't take part"
+        );
+    }
+
+    /// sale of `asset_id` of the test peer to the victim, validated by
+    /// `validator` and signed with the lottery proof of `keypair` or of
+    /// the test peer
+    async fn sale(
+        config: &Config,
+        validator: &str,
+        keypair: Option<&Keypair>,
+        asset_id: &str,
+    ) -> Block {
+        let tip = config.chain_addr.send(GetLastBlock).await.unwrap();
+        let block_id = get_next_block_id(config).await;
+        let mut builder = BlockBuilder::default();
+        builder
+            .set_tx(&config.peer_id)
+            .set_rx("victim.batsense.net")
+            .set_asset_id(asset_id)
+            .set_validator(validator)
+            .set_prev(&tip);
+        let (seed, message) = (seed(tip.get_hash(), block_id), builder.content_hash());
+        let proof = match keypair {
+            Some(keypair) => keypair.prove(&seed, &message),
+            None => config
+                .lottery_addr
+                .send(Prove { seed, message })
+                .await
+                .unwrap(),
+        };
+        builder.set_proof(&proof).build()
+    }
+
+    async fn add_block(config: &Config, block: &Block) {
+        let resp = make_post_request_with_client(
+            config,
+            serde_json::to_string(block).unwrap(),
+            "/chain/add",
+        )
+        .await;
+        assert!(resp.status().is_success(), "add block is 200");
+    }
+
+    async fn tip(config: &Config) -> String {
+        let tip = config.chain_addr.send(GetLastBlock).await.unwrap();
+        tip.get_hash().into()
+    }
+
+    #[actix_rt::test]
+    async fn lottery_add_block_works() {
+        let mut config = init_network(Mode::Normal).await;
+        config.consensus = Consensus::Vrf;
+        let client = Client::default();
+
+        // stake is split between the test peer and the attacker, who
+        // is cut off so that nothing is sent to it
+        let mut assets = AssetLedger::generate(&config.peer_id).assets;
+        for (i, asset) in assets.iter_mut().enumerate() {
+            asset.set_owner(if i % 2 == 0 {
+                &config.peer_id
+            } else {
+                ATTACKER
+            });
+        }
+        let asset_ids: Vec<String> = assets
+            .iter()
+            .step_by(2)
+            .map(|asset| asset.get_hash().into())
+            .collect();
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+        let peers = [
+            (config.peer_id.clone(), config.public_ip.clone()),
+            (ATTACKER.into(), "localhost:7001".into()),
+        ];
+        for (id, ip) in peers.iter() {
+            let peer = Peer {
+                id: id.into(),
+                ip: ip.into(),
+            };
+            config.network_addr.send(AddPeer(peer)).await.unwrap();
+        }
+        config
+            .fault_addr
+            .send(Partition(vec![ATTACKER.into()]))
+            .await
+            .unwrap();
+        let keypair = Keypair::generate();
+        config
+            .lottery_addr
+            .send(AddPeerKey {
+                peer_id: ATTACKER.into(),
+                key: keypair.public(),
+            })
+            .await
+            .unwrap();
+
+        // blocks are added until both validators win the lottery
+        let (lower, higher) = loop {
+            let block_id = get_next_block_id(&config).await;
+            let mine = sale(&config, &config.peer_id, None, &asset_ids[0]).await;
+            let theirs = sale(&config, ATTACKER, Some(&keypair), &asset_ids[0]).await;
+            if let (Some(a), Some(b)) = (
+                check_ticket(&config, &client, &mine, block_id).await,
+                check_ticket(&config, &client, &theirs, block_id).await,
+            ) {
+                assert_ne!(a, b);
+                break if a < b {
+                    (mine, theirs)
+                } else {
+                    (theirs, mine)
+                };
+            }
+            let tip = config.chain_addr.send(GetLastBlock).await.unwrap();
+            let filler = BlockBuilder::default()
+                .set_tx("a")
+                .set_rx("d")
+                .set_asset_id("asset")
+                .set_prev(&tip)
+                .build();
+            config
+                .chain_addr
+                .send(AddBlock(filler, config.init_network_size))
+                .await
+                .unwrap()
+                .unwrap();
+        };
+        assert!(lottery(&config, get_next_block_id(&config).await)
+            .await
+            .is_some());
+
+        add_block(&config, &higher).await;
+        assert_eq!(tip(&config).await, higher.get_hash());
+        config
+            .mode_addr
+            .send(SetMode(Mode::Victim(true)))
+            .await
+            .unwrap();
+        add_block(&config, &lower).await;
+        assert_eq!(
+            tip(&config).await,
+            higher.get_hash(),
+            "tip is kept when the block isn't added"
+        );
+        config.mode_addr.send(SetMode(Mode::Normal)).await.unwrap();
+        add_block(&config, &lower).await;
+        assert_eq!(tip(&config).await, lower.get_hash(), "lowest ticket wins");
+        add_block(&config, &higher).await;
+        assert_eq!(tip(&config).await, lower.get_hash(), "higher ticket loses");
+        let assets = get_my_assets(&config).await;
+        assert!(
+            assets.iter().all(|asset| asset.get_hash() != asset_ids[0]),
+            "asset is sold once"
+        );
+
+        // proofs can't be reused for other blocks
+        let mut forged = BlockBuilder::default();
+        forged
+            .set_tx(&config.peer_id)
+            .set_rx("victim.batsense.net")
+            .set_asset_id(&asset_ids[1])
+            .set_validator(lower.get_validator().unwrap())
+            .set_prev(&lower)
+            .set_proof(lower.get_proof().unwrap());
+        add_block(&config, &forged.build()).await;
+        assert_eq!(
+            tip(&config).await,
+            lower.get_hash(),
+            "copied proof is rejected"
+        );
+    }
+}
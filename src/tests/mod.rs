@@ -15,11 +15,35 @@
 * along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+#[cfg(test)]
+pub mod alerts;
 #[cfg(test)]
 pub mod api_tests;
 #[cfg(test)]
+pub mod assets;
+#[cfg(test)]
+pub mod attacks;
+#[cfg(test)]
+pub mod blocks;
+#[cfg(test)]
+pub mod events;
+#[cfg(test)]
+pub mod evidence;
+#[cfg(test)]
+pub mod faults;
+#[cfg(test)]
+pub mod finality;
+#[cfg(test)]
+pub mod graph;
+#[cfg(test)]
+pub mod lottery;
+#[cfg(test)]
+pub mod pbft;
+#[cfg(test)]
 pub mod race_cond;
 #[cfg(test)]
 pub mod routes_enroll;
 #[cfg(test)]
+pub mod tokens;
+#[cfg(test)]
 pub mod tx;
@@ -0,0 +1,227 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[cfg(test)]
+mod tests {
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::asset::{AssetLedger, InitNetworkBuilder, ReplaceLedger};
+    use damn_vuln_blockchain::block::BlockBuilder;
+    use damn_vuln_blockchain::chain::GetLastBlock;
+    use damn_vuln_blockchain::client::Client;
+    use damn_vuln_blockchain::config::{Config, Consensus, Mode};
+    use damn_vuln_blockchain::discovery::AddPeer;
+    use damn_vuln_blockchain::payload::{Peer, TxBuilder, ValidateTx, ValidateTxBuilder};
+    use damn_vuln_blockchain::pbft::{
+        primary, PbftMessage, PbftStatus, Proposal, Receive, RecordSent,
+    };
+    use damn_vuln_blockchain::utils::{check_transaction, get_next_block_id, pbft_replicas};
+
+    use crate::routes::tests::{make_get_request, make_post_request_with_client};
+    use damn_vuln_blockchain::helpers::*;
+
+    /// network where the test peer owns assets
+    async fn init_with_assets() -> Config {
+        let config = init_network(Mode::Normal).await;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+        let msg = InitNetworkBuilder::default()
+            .network_size(config.init_network_size)
+            .peer_id(config.peer_id.clone())
+            .build()
+            .unwrap();
+        config.asset_addr.send(msg).await.unwrap();
+        config
+    }
+
+    /// sale of an asset of the test peer to the victim
+    async fn sale(config: &Config) -> ValidateTx {
+        let tx = TxBuilder::default()
+            .asset_id(get_my_assets(config).await[0].get_hash().into())
+            .buyer_peer_id("victim.batsense.net".into())
+            .build()
+            .unwrap();
+        ValidateTxBuilder::default()
+            .tx(tx)
+            .seller_peer_id(config.peer_id.clone())
+            .build()
+            .unwrap()
+    }
+
+    async fn status(config: &Config) -> PbftStatus {
+        let resp = make_get_request(config, "/pbft").await;
+        assert!(resp.status().is_success(), "pbft is 200");
+        test::read_body_json(resp).await
+    }
+
+    async fn post(config: &Config, msg: &PbftMessage) {
+        let resp =
+            make_post_request_with_client(config, serde_json::to_string(msg).unwrap(), "/pbft")
+                .await;
+        assert!(resp.status().is_success(), "pbft message is 200");
+    }
+
+    #[actix_rt::test]
+    async fn pbft_works() {
+        let config = init_network(Mode::Normal).await;
+        let tx = TxBuilder::default()
+            .asset_id("asset".into())
+            .buyer_peer_id("victim.batsense.net".into())
+            .build()
+            .unwrap();
+        let request = ValidateTxBuilder::default()
+            .tx(tx)
+            .seller_peer_id("normal.batsense.net".into())
+            .build()
+            .unwrap();
+        config
+            .pbft_addr
+            .send(Receive {
+                msg: PbftMessage::Request(request.clone()),
+                replicas: vec![config.peer_id.clone()],
+                me: config.peer_id.clone(),
+            })
+            .await
+            .unwrap();
+
+        let resp = make_get_request(&config, "/pbft").await;
+        assert!(resp.status().is_success(), "pbft is 200");
+        let status: PbftStatus = test::read_body_json(resp).await;
+        assert_eq!(status.view, 0);
+        assert_eq!(status.requests, vec![request], "pending requests listed");
+    }
+
+    #[actix_rt::test]
+    async fn pbft_message_works() {
+        let config = init_with_assets().await;
+        let peers = [
+            ("attacker.batsense.net", "localhost:7001"),
+            ("victim.batsense.net", "localhost:7002"),
+            (config.peer_id.as_str(), config.public_ip.as_str()),
+        ];
+        for (id, ip) in peers.iter() {
+            let peer = Peer {
+                id: (*id).into(),
+                ip: (*ip).into(),
+            };
+            config.network_addr.send(AddPeer(peer)).await.unwrap();
+        }
+        let replicas: Vec<String> = pbft_replicas(&config, &Client::default())
+            .await
+            .into_iter()
+            .map(|replica| replica.id)
+            .collect();
+        assert_eq!(replicas.len(), 4);
+
+        // quorum of view changes sent on behalf of other replicas
+        for replica in replicas
+            .iter()
+            .filter(|replica| **replica != config.peer_id)
+        {
+            let msg = PbftMessage::ViewChange {
+                view: 1,
+                replica: replica.clone(),
+                prepared: None,
+            };
+            post(&config, &msg).await;
+        }
+        assert_eq!(status(&config).await.view, 0, "forged replicas ignored");
+
+        // proposals of this replica, which is only the primary of some views
+        let seq = get_next_block_id(&config).await;
+        let tip = config.chain_addr.send(GetLastBlock).await.unwrap();
+        let request = sale(&config).await;
+        let block = BlockBuilder::default()
+            .set_tx(&request.seller_peer_id)
+            .set_rx(&request.tx.buyer_peer_id)
+            .set_asset_id(&request.tx.asset_id)
+            .set_validator(&config.peer_id)
+            .set_kind(request.tx.kind.clone())
+            .set_prev(&tip)
+            .build();
+        let pre_prepare = |view: usize| PbftMessage::PrePrepare {
+            view,
+            replica: config.peer_id.clone(),
+            proposal: Box::new(Proposal {
+                seq,
+                block: block.clone(),
+                request: request.clone(),
+            }),
+        };
+        let view = (0..replicas.len())
+            .find(|view| primary(&replicas, *view, seq) == Some(&config.peer_id))
+            .unwrap();
+        for msg in [pre_prepare(view + 1), pre_prepare(view)].iter() {
+            config
+                .pbft_addr
+                .send(RecordSent(msg.clone()))
+                .await
+                .unwrap();
+        }
+
+        post(&config, &pre_prepare(view + 1)).await;
+        assert!(
+            status(&config).await.requests.is_empty(),
+            "proposals of wrong primary ignored"
+        );
+        post(&config, &pre_prepare(view)).await;
+        assert_eq!(
+            status(&config).await.requests,
+            vec![request],
+            "proposals of primary accepted"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn pbft_add_block_works() {
+        let mut config = init_with_assets().await;
+        config.consensus = Consensus::Pbft;
+        let request = sale(&config).await;
+        assert!(
+            check_transaction(
+                &config,
+                &request.seller_peer_id,
+                &request.tx.buyer_peer_id,
+                Some(&request.tx.asset_id),
+                &request.tx.kind,
+            )
+            .await
+        );
+        let tip = config.chain_addr.send(GetLastBlock).await.unwrap();
+        let block = BlockBuilder::default()
+            .set_tx(&request.seller_peer_id)
+            .set_rx(&request.tx.buyer_peer_id)
+            .set_asset_id(&request.tx.asset_id)
+            .set_validator(&config.peer_id)
+            .set_prev(&tip)
+            .build();
+
+        make_post_request_with_client(
+            &config,
+            serde_json::to_string(&block).unwrap(),
+            "/chain/add",
+        )
+        .await;
+        let last = config.chain_addr.send(GetLastBlock).await.unwrap();
+        assert_eq!(
+            last.get_hash(),
+            tip.get_hash(),
+            "blocks outside of PBFT rejected"
+        );
+    }
+}
@@ -18,14 +18,14 @@
 #[cfg(test)]
 mod tests {
 
-    use actix_web::test;
+    use actix_web::{dev::ServiceResponse, http::StatusCode, test};
 
     use damn_vuln_blockchain::asset::Asset;
     use damn_vuln_blockchain::config::{GetMode, Mode};
     use damn_vuln_blockchain::payload::Peer;
     use damn_vuln_blockchain::Config;
 
-    use crate::routes::tests::{make_get_request, make_post_request};
+    use crate::routes::tests::{make_get_request, make_post_request, make_post_request_from};
     use damn_vuln_blockchain::helpers::*;
 
     pub async fn prepare_default_stake(config: &Config) -> Vec<String> {
@@ -41,7 +41,11 @@ mod tests {
     fn get_stake_payload(block_id: usize) -> Option<String> {
         use damn_vuln_blockchain::payload::GetStake as PayloadGetStake;
 
-        let payload = serde_json::to_string(&PayloadGetStake { block_id }).unwrap();
+        let payload = serde_json::to_string(&PayloadGetStake {
+            block_id,
+            peer_id: None,
+        })
+        .unwrap();
         Some(payload)
     }
 
@@ -170,6 +174,150 @@ mod tests {
         );
     }
 
+    fn enrollment_payload(id: &str, ip: &str, nonce: u64) -> Option<String> {
+        use damn_vuln_blockchain::payload::Enrollment;
+
+        let enrollment = Enrollment {
+            peer: Peer {
+                id: id.into(),
+                ip: ip.into(),
+            },
+            nonce,
+        };
+        Some(serde_json::to_string(&enrollment).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn enroll_pow_works() {
+        use damn_vuln_blockchain::discovery::EnrollPolicy;
+
+        let mut config = init_network(Mode::Auditor).await;
+        config.enroll_policy.pow_difficulty = Some(2);
+
+        let resp = make_get_request(&config, "/peers/enroll/policy").await;
+        assert!(resp.status().is_success(), "enroll policy is 200");
+        let policy: EnrollPolicy = test::read_body_json(resp).await;
+        assert_eq!(policy, config.enroll_policy, "enroll policy works");
+
+        let peer = Peer {
+            id: "pow".into(),
+            ip: "yolo".into(),
+        };
+        let invalid_nonce = (0..)
+            .find(|nonce| !EnrollPolicy::verify_pow(&peer, *nonce, 2))
+            .unwrap();
+        let resp = make_post_request(
+            &config,
+            enrollment_payload(&peer.id, &peer.ip, invalid_nonce),
+            "/peers/enroll",
+        )
+        .await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "invalid proof of work rejected"
+        );
+
+        let nonce = EnrollPolicy::solve_pow(&peer, 2).unwrap();
+        let resp = make_post_request(
+            &config,
+            enrollment_payload(&peer.id, &peer.ip, nonce),
+            "/peers/enroll",
+        )
+        .await;
+        assert!(resp.status().is_success(), "valid proof of work enrolled");
+    }
+
+    #[actix_rt::test]
+    async fn enroll_full_network_works() {
+        let config = init_network(Mode::Auditor).await;
+        for i in 0..config.init_network_size {
+            let resp = make_post_request(
+                &config,
+                enrollment_payload(&i.to_string(), &i.to_string(), 0),
+                "/peers/enroll",
+            )
+            .await;
+            assert!(resp.status().is_success(), "peer enrolled");
+        }
+
+        let resp = make_post_request(
+            &config,
+            enrollment_payload("late", "late", 0),
+            "/peers/enroll",
+        )
+        .await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "full network rejects enrollment"
+        );
+    }
+
+    /// enrolls peer `id` with address `ip`, from address `addr`
+    async fn enroll_from(config: &Config, id: &str, ip: &str, addr: &str) -> ServiceResponse {
+        make_post_request_from(
+            config,
+            enrollment_payload(id, ip, 0).unwrap(),
+            "/peers/enroll",
+            addr,
+        )
+        .await
+    }
+
+    #[actix_rt::test]
+    async fn enroll_address_limit_works() {
+        let mut config = init_network(Mode::Auditor).await;
+        config.enroll_policy.address_limit = Some(1);
+        let resp = enroll_from(&config, "one", "10.0.0.1:7001", "10.0.0.1:7001").await;
+        assert!(resp.status().is_success(), "first peer enrolled");
+
+        let resp = enroll_from(&config, "two", "10.0.0.1:7002", "10.0.0.1:7002").await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "address limit enforced on other ports"
+        );
+
+        let resp = enroll_from(&config, "three", "10.0.0.2:7001", "10.0.0.1:7003").await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "address limit enforced on the real address"
+        );
+
+        let resp = enroll_from(&config, "four", "10.0.0.2:7001", "10.0.0.2:7001").await;
+        assert!(resp.status().is_success(), "other addresses enrolled");
+    }
+
+    #[actix_rt::test]
+    async fn enroll_stake_gated_works() {
+        use damn_vuln_blockchain::asset::{AssetLedger, GetPeerAssets, ReplaceLedger};
+
+        let mut config = generate_test_config();
+        config.enroll_policy.stake_gated = true;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+
+        for (id, addr) in [("one", "10.0.0.1:7001"), ("two", "10.0.0.1:7002")].iter() {
+            let resp = enroll_from(&config, id, addr, addr).await;
+            assert!(resp.status().is_success(), "peer enrolled");
+        }
+
+        let one = config
+            .asset_addr
+            .send(GetPeerAssets("one".into()))
+            .await
+            .unwrap();
+        let two = config
+            .asset_addr
+            .send(GetPeerAssets("two".into()))
+            .await
+            .unwrap();
+        assert!(!one.is_empty(), "first identity gets stake");
+        assert!(two.is_empty(), "identities from staked address don't");
+    }
+
     #[actix_rt::test]
     async fn set_attack_works() {
         let config = init_network(Mode::Attacker(false)).await;
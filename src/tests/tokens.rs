@@ -0,0 +1,124 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use actix_web::test;
+
+    use damn_vuln_blockchain::asset::{AssetLedger, InitNetworkBuilder, ReplaceLedger};
+    use damn_vuln_blockchain::block::{BlockBuilder, TxKind};
+    use damn_vuln_blockchain::chain::{AddBlock, GetLastBlock};
+    use damn_vuln_blockchain::config::Mode;
+    use damn_vuln_blockchain::payload::{Ask, Balance, TxBuilder};
+
+    use crate::routes::tests::{
+        make_get_request, make_post_request, make_post_request_with_client,
+    };
+    use damn_vuln_blockchain::helpers::*;
+
+    #[actix_rt::test]
+    async fn token_balance_works() {
+        let config = init_network(Mode::Normal).await;
+        let transactions = [
+            ("auditor", "victim", "auditor", TxKind::Mint { amount: 100 }),
+            (
+                "victim",
+                "normal",
+                "attacker",
+                TxKind::Transfer { amount: 30, fee: 5 },
+            ),
+        ];
+        for (tx, rx, validator, kind) in transactions.iter() {
+            let prev = config.chain_addr.send(GetLastBlock).await.unwrap();
+            let block = BlockBuilder::default()
+                .set_tx(tx)
+                .set_rx(rx)
+                .set_validator(validator)
+                .set_kind(kind.clone())
+                .set_prev(&prev)
+                .build();
+            config
+                .chain_addr
+                .send(AddBlock(block, config.init_network_size))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let resp = make_get_request(&config, "/tokens/balance/victim").await;
+        assert!(resp.status().is_success(), "balance is 200");
+        let balance: Balance = test::read_body_json(resp).await;
+        assert_eq!(balance.balance, 65, "transfer and fee deducted");
+
+        let resp = make_get_request(&config, "/tokens/balances").await;
+        let balances: BTreeMap<String, u64> = test::read_body_json(resp).await;
+        assert_eq!(balances.get("normal"), Some(&30));
+        assert_eq!(balances.get("attacker"), Some(&5), "validator earns fee");
+
+        let resp = make_get_request(&config, "/tokens/balance/nobody").await;
+        let balance: Balance = test::read_body_json(resp).await;
+        assert_eq!(balance.balance, 0, "unknown peers have no tokens");
+    }
+
+    #[actix_rt::test]
+    async fn asset_ask_works() {
+        let config = init_network(Mode::Normal).await;
+        let assets = AssetLedger::generate(&config.peer_id).assets;
+        config.asset_addr.send(ReplaceLedger(assets)).await.unwrap();
+        let msg = InitNetworkBuilder::default()
+            .network_size(config.init_network_size)
+            .peer_id(config.peer_id.clone())
+            .build()
+            .unwrap();
+        config.asset_addr.send(msg).await.unwrap();
+        let asset_id = get_my_assets(&config).await[0].get_hash().to_owned();
+
+        let ask = |asset_id: &str| {
+            serde_json::to_string(&Ask {
+                asset_id: asset_id.into(),
+                price: 10,
+            })
+            .unwrap()
+        };
+        let resp = make_post_request(&config, Some(ask(&asset_id)), "/assets/ask").await;
+        assert!(resp.status().is_success(), "owners can ask for prices");
+
+        let resp = make_post_request(&config, Some(ask("nope")), "/assets/ask").await;
+        assert!(
+            resp.status().is_client_error(),
+            "unknown assets can't be sold"
+        );
+
+        let sale = TxBuilder::default()
+            .asset_id(asset_id)
+            .buyer_peer_id("victim.batsense.net".into())
+            .kind(TxKind::Sale { price: 10, fee: 1 })
+            .build()
+            .unwrap();
+        let resp = make_post_request_with_client(
+            &config,
+            serde_json::to_string(&sale).unwrap(),
+            "/assets/sell",
+        )
+        .await;
+        assert!(
+            resp.status().is_client_error(),
+            "sales for tokens need an offer"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! History of worldview uploads. Every upload is recorded in the
+//! [Timeline] as a [Snapshot], which can be looked up by ID or by time
+//! and compared with other snapshots.
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::diff::{diff_status, StatusDiff};
+use crate::payload::Status;
+
+/// number of snapshots kept by default, older snapshots are dropped
+pub const DEFAULT_LIMIT: usize = 1000;
+
+/// Worldview at a point in time
+#[derive(Deserialize, Serialize)]
+pub struct Snapshot {
+    pub id: usize,
+    /// milliseconds since UNIX epoch
+    pub timestamp: i64,
+    pub time: String,
+    pub states: Vec<Status>,
+}
+
+/// Summary of a [Snapshot]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct SnapshotInfo {
+    pub id: usize,
+    pub timestamp: i64,
+    pub time: String,
+    /// IDs of peers in the snapshot
+    pub peers: Vec<String>,
+}
+
+impl From<&Snapshot> for SnapshotInfo {
+    fn from(snapshot: &Snapshot) -> Self {
+        SnapshotInfo {
+            id: snapshot.id,
+            timestamp: snapshot.timestamp,
+            time: snapshot.time.clone(),
+            peers: snapshot
+                .states
+                .iter()
+                .map(|state| state.peer_id.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Time-indexed worldview uploads, oldest first
+#[derive(Deserialize, Serialize)]
+pub struct Timeline {
+    pub snapshots: Vec<Snapshot>,
+    /// maximum number of snapshots kept
+    pub limit: usize,
+    next_id: usize,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline::new(DEFAULT_LIMIT)
+    }
+}
+
+impl Timeline {
+    pub fn new(limit: usize) -> Self {
+        Timeline {
+            snapshots: Vec::new(),
+            limit,
+            next_id: 0,
+        }
+    }
+
+    /// record worldview at current time, returns snapshot ID
+    pub fn push(&mut self, states: Vec<Status>) -> usize {
+        self.push_at(states, Local::now().timestamp_millis())
+    }
+
+    /// record worldview at `timestamp`, milliseconds since UNIX epoch
+    pub fn push_at(&mut self, states: Vec<Status>, timestamp: i64) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.snapshots.push(Snapshot {
+            id,
+            timestamp,
+            time: Local.timestamp_millis(timestamp).to_string(),
+            states,
+        });
+        if self.snapshots.len() > self.limit {
+            self.snapshots.remove(0);
+        }
+        id
+    }
+
+    /// most recent snapshot
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.last()
+    }
+
+    /// summary of all snapshots
+    pub fn list(&self) -> Vec<SnapshotInfo> {
+        self.snapshots
+            .iter()
+            .map(|snapshot| snapshot.into())
+            .collect()
+    }
+
+    /// get snapshot by ID
+    pub fn get(&self, id: usize) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.id == id)
+    }
+
+    /// worldview as it was at `timestamp`: the last snapshot
+    /// recorded at or before `timestamp`
+    pub fn at(&self, timestamp: i64) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.timestamp <= timestamp)
+    }
+
+    /// compares every peer's state in snapshot `from` with its state in
+    /// snapshot `to`. Peers missing from a snapshot are compared with
+    /// empty ledgers. Returns `None` if either snapshot doesn't exist
+    pub fn diff(&self, from: usize, to: usize) -> Option<Vec<StatusDiff>> {
+        let (from, to) = (self.get(from)?, self.get(to)?);
+
+        let mut peers: Vec<&str> = from
+            .states
+            .iter()
+            .chain(to.states.iter())
+            .map(|state| state.peer_id.as_str())
+            .collect();
+        peers.sort_unstable();
+        peers.dedup();
+
+        fn find<'a>(snapshot: &'a Snapshot, peer_id: &str) -> Option<&'a Status> {
+            snapshot
+                .states
+                .iter()
+                .find(|state| state.peer_id == peer_id)
+        }
+        Some(
+            peers
+                .into_iter()
+                .map(|peer_id| {
+                    let empty = Status {
+                        peer_id: peer_id.into(),
+                        asset: Vec::new(),
+                        tampered_assets: None,
+                        chain: Vec::new(),
+                        tampered_chain: None,
+                    };
+                    diff_status(
+                        find(from, peer_id).unwrap_or(&empty),
+                        find(to, peer_id).unwrap_or(&empty),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asset::AssetLedger;
+    use crate::chain::Chain;
+    use crate::payload::StatusBuilder;
+
+    fn status(peer_id: &str, chain: &Chain, assets: &AssetLedger) -> Status {
+        StatusBuilder::default()
+            .peer_id(peer_id.into())
+            .asset(assets.assets.clone())
+            .chain(chain.blocks())
+            .tampered_assets(None)
+            .tampered_chain(None)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn timeline_works() {
+        let mut timeline = Timeline::new(3);
+        assert!(timeline.latest().is_none());
+
+        let chain = Chain::new("test");
+        let mut assets = AssetLedger::generate("victim");
+        timeline.push_at(vec![status("victim", &chain, &assets)], 1000);
+        assets.assets[0].set_owner("attacker");
+        timeline.push_at(
+            vec![
+                status("victim", &chain, &assets),
+                status("attacker", &chain, &assets),
+            ],
+            2000,
+        );
+
+        assert_eq!(timeline.latest().unwrap().id, 1);
+        assert_eq!(timeline.at(1500).unwrap().id, 0, "fetch by time works");
+        assert_eq!(timeline.at(2000).unwrap().id, 1);
+        assert!(timeline.at(999).is_none(), "nothing recorded yet");
+
+        let list = timeline.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].peers, vec!["victim", "attacker"]);
+
+        let diff = timeline.diff(0, 1).unwrap();
+        assert_eq!(diff.len(), 2, "peers from both snapshots are compared");
+        let attacker = diff.iter().find(|diff| diff.left == "attacker").unwrap();
+        assert_eq!(
+            attacker.chain.right.len(),
+            1,
+            "new peer compared with empty ledger"
+        );
+        let victim = diff.iter().find(|diff| diff.left == "victim").unwrap();
+        assert!(victim.chain.is_empty());
+        assert_eq!(victim.assets.len(), 1, "ownership change detected");
+        assert!(timeline.diff(0, 7).is_none());
+
+        timeline.push_at(Vec::new(), 3000);
+        timeline.push_at(Vec::new(), 4000);
+        assert_eq!(timeline.snapshots.len(), 3, "limit works");
+        assert!(timeline.get(0).is_none(), "oldest snapshot dropped");
+        assert_eq!(timeline.get(3).unwrap().timestamp, 4000);
+    }
+}
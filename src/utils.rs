@@ -14,12 +14,19 @@
 * You should have received a copy of the GNU Affero General Public License
 * along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
+use actix::prelude::*;
 use data_encoding::HEXUPPER;
 use sha2::{Digest, Sha256};
 
 use crate::asset::{Asset, AssetLedger, GetAssetInfo, Stake};
-use crate::block::Block;
-use crate::payload::{Peer, Status};
+use crate::block::{Block, Evidence, TxKind};
+use crate::config::Consensus;
+use crate::error::*;
+use crate::finality::Vote;
+use crate::logs::Action;
+use crate::payload::{Peer, Share, Status, Tx, ValidateTx};
+use crate::pbft::{PbftMessage, Proposal, Step};
+use crate::vrf::PublicKey;
 use crate::{Client, Config};
 
 /// helper function for generating sha256 hashes
@@ -57,10 +64,16 @@ pub fn get_current_time() -> String {
 //    unimplemented!()
 //}
 
-/// get stake from all peers in network and get the validator peer
-pub async fn consensus(config: &Config, block_id: usize, client: &Client) -> Peer {
+/// get stake from all peers in network and get the validator peer.
+/// When validators are chosen by lottery, returns this peer if it won
+/// the lottery or `None` otherwise, see [lottery]
+pub async fn consensus(config: &Config, block_id: usize, client: &Client) -> Option<Peer> {
     use crate::client::GetStake as ClientGetStake;
     use crate::discovery::DumpPeer;
+
+    if config.consensus == Consensus::Vrf {
+        return lottery(config, block_id).await;
+    }
     let mut stake: Vec<(String, Stake)> = Vec::new();
     let peers = config.network_addr.send(DumpPeer).await.unwrap();
 
@@ -71,17 +84,232 @@ pub async fn consensus(config: &Config, block_id: usize, client: &Client) -> Pee
         };
         config.debug(&format!("Requesting stake from peer {}", &peer.id));
 
-        stake.push((
-            peer.id.clone(),
-            client.get_stake(client_payload, &config).await,
-        ));
+        let peer_stake = client.get_stake(client_payload, &config).await;
+        config
+            .log_event(Action::StakeBroadcast(
+                peer.clone(),
+                peer_stake.stake.join(", "),
+            ))
+            .await;
+        stake.push((peer.id.clone(), peer_stake));
     }
 
     // now we have stake of all peers
     // time to calculate validator
     // asset ownership should be
     // verified before calculation
-    from_stake_to_validator(&config, stake).await
+    Some(from_stake_to_validator(&config, stake).await)
+}
+
+/// VRF public key of `peer`. Keys are fetched from peers the first
+/// time they are needed and never replaced, see [AddPeerKey](crate::vrf::AddPeerKey)
+pub async fn lottery_key(config: &Config, client: &Client, peer: &Peer) -> PublicKey {
+    use crate::vrf::{AddPeerKey, GetPeerKey, GetPublicKey};
+
+    if peer.id == config.peer_id {
+        return config.lottery_addr.send(GetPublicKey).await.unwrap();
+    }
+    if let Some(key) = config
+        .lottery_addr
+        .send(GetPeerKey(peer.id.clone()))
+        .await
+        .unwrap()
+    {
+        return key;
+    }
+    let key = client.get_lottery_key(peer).await;
+    config
+        .lottery_addr
+        .send(AddPeerKey {
+            peer_id: peer.id.clone(),
+            key,
+        })
+        .await
+        .unwrap()
+}
+
+/// stake weighted ticket of a peer with VRF output `output`, see
+/// [ticket](crate::vrf::ticket). Stake is recorded in the asset ledger
+async fn stake_ticket(config: &Config, peer_id: &str, output: &str) -> Option<f64> {
+    use crate::asset::GetVotingStake;
+    use crate::vrf::ticket;
+
+    let stake = config
+        .get_asset_ledger()
+        .await
+        .send(GetVotingStake)
+        .await
+        .unwrap();
+    let total = stake.values().sum();
+    ticket(
+        output,
+        stake.get(peer_id).cloned().unwrap_or_default(),
+        total,
+    )
+}
+
+/// ticket of the validator of `block` in the lottery for block
+/// `block_id`. Returns `None` if the validator isn't a known peer, its
+/// proof is invalid or doesn't sign the block, or it isn't eligible
+pub async fn check_ticket(
+    config: &Config,
+    client: &Client,
+    block: &Block,
+    block_id: usize,
+) -> Option<f64> {
+    use crate::discovery::GetPeer;
+    use crate::vrf::seed;
+
+    let (validator, prev, proof) = (
+        block.get_validator()?,
+        block.get_prev()?,
+        block.get_proof()?,
+    );
+    let peer = config
+        .network_addr
+        .send(GetPeer(validator.clone()))
+        .await
+        .unwrap()?;
+    let key = lottery_key(config, client, &peer).await;
+    let output = match key.verify(&seed(prev, block_id), &block.content_hash(), proof) {
+        Some(output) => output,
+        None => {
+            config.debug(&format!("Invalid lottery proof from {}", &peer.id));
+            return None;
+        }
+    };
+    stake_ticket(config, &peer.id, &output).await
+}
+
+/// checks if this peer won the lottery for block `block_id`. Peers only
+/// evaluate their own VRF: winners prove they won with the blocks they
+/// create, see [check_lottery]. Returns this peer if it is eligible
+pub async fn lottery(config: &Config, block_id: usize) -> Option<Peer> {
+    use crate::chain::GetLastBlock;
+    use crate::config::{GetMode, Mode};
+    use crate::vrf::{seed, Evaluate};
+
+    // like stake requests, attackers and peers under DoS don't take part
+    let current_mode = config.mode_addr.send(GetMode).await.unwrap();
+    if current_mode == Mode::Attacker(false) || current_mode == Mode::Victim(true) {
+        return None;
+    }
+    let tip = config
+        .get_chain_addr()
+        .await
+        .send(GetLastBlock)
+        .await
+        .unwrap();
+    let output = config
+        .lottery_addr
+        .send(Evaluate(seed(tip.get_hash(), block_id)))
+        .await
+        .unwrap();
+    match stake_ticket(config, &config.peer_id, &output).await {
+        Some(ticket) => {
+            config.debug(&format!(
+                "Eligible for block {} with ticket {}",
+                block_id, ticket
+            ));
+            Some(Peer {
+                id: config.peer_id.clone(),
+                ip: config.public_ip.clone(),
+            })
+        }
+        None => {
+            config.debug(&format!("Didn't win the lottery for block {}", block_id));
+            None
+        }
+    }
+}
+
+/// checks if the validator of `block` proved that it is eligible to
+/// create the next block on the tip of the chain
+pub async fn check_lottery(config: &Config, client: &Client, block: &Block) -> bool {
+    use crate::chain::GetLastBlock;
+
+    let tip = config
+        .get_chain_addr()
+        .await
+        .send(GetLastBlock)
+        .await
+        .unwrap();
+    if block.get_prev().map(String::as_str) != Some(tip.get_hash()) {
+        config.debug("Lottery proof isn't built on the tip");
+        return false;
+    }
+    let block_id = get_next_block_id(config).await;
+    check_ticket(config, client, block, block_id)
+        .await
+        .is_some()
+}
+
+/// competing lottery winners: when `block` competes with the tip of the
+/// chain for the same block, the tip is removed so that `block` can be
+/// checked and added in its place if its ticket is lower. Returns the
+/// removed tip, put it back with [restore_tip] if `block` is rejected
+pub async fn compete_for_tip(config: &Config, client: &Client, block: &Block) -> Option<Block> {
+    use crate::chain::{GetLastBlock, RemoveTip};
+
+    let chain_addr = config.get_chain_addr().await;
+    let tip = chain_addr.send(GetLastBlock).await.unwrap();
+    if tip.is_genesis()
+        || !tip.get_kind().is_validated()
+        || tip.get_hash() == block.get_hash()
+        || tip.get_prev() != block.get_prev()
+    {
+        return None;
+    }
+    let tip = match chain_addr.send(RemoveTip).await.unwrap() {
+        Ok(tip) => tip,
+        Err(e) => {
+            config.debug(&format!("Not replacing tip: {}", e));
+            return None;
+        }
+    };
+    reorg_assets(
+        &config.get_asset_ledger().await,
+        std::slice::from_ref(&tip),
+        &[],
+    )
+    .await;
+
+    // tickets are compared with the stake before the tip
+    let block_id = get_next_block_id(config).await;
+    let (challenger, current) = (
+        check_ticket(config, client, block, block_id).await,
+        check_ticket(config, client, &tip, block_id).await,
+    );
+    match (challenger, current) {
+        (Some(challenger), Some(current)) if challenger < current => (),
+        (Some(_), None) => (),
+        _ => {
+            restore_tip(config, tip).await;
+            return None;
+        }
+    }
+    config.debug(&format!(
+        "Block {} has a lower ticket than tip {}",
+        block.get_hash(),
+        tip.get_hash()
+    ));
+    Some(tip)
+}
+
+/// puts back a tip removed by [compete_for_tip]
+pub async fn restore_tip(config: &Config, tip: Block) {
+    use crate::chain::AddBlock;
+
+    let chain_addr = config.get_chain_addr().await;
+    if let Err(e) = chain_addr
+        .send(AddBlock(tip.clone(), config.init_network_size))
+        .await
+        .unwrap()
+    {
+        config.debug(&format!("Can't restore tip {}: {}", tip.get_hash(), e));
+        return;
+    }
+    reorg_assets(&config.get_asset_ledger().await, &[], &[tip]).await;
 }
 
 /// get peer utility
@@ -98,27 +326,24 @@ pub async fn get_peer(config: &Config, peer_id: &str) -> Peer {
 
 /// get validator peer from stakes of all peers
 async fn from_stake_to_validator(config: &Config, all_stakes: Vec<(String, Stake)>) -> Peer {
+    use crate::asset::GetAssetsInfo;
+
     let mut authenticated_stakes: Vec<Asset> = Vec::default();
     for (peer_id, stakes) in all_stakes.iter() {
-        for stake in stakes.stake.iter() {
-            if let Some(asset) = config
-                .asset_addr
-                .send(GetAssetInfo(stake.clone()))
-                .await
-                .unwrap()
-            {
-                if let Some(owner) = asset.get_owner() {
-                    if owner == peer_id {
-                        authenticated_stakes.push(asset);
-                    }
-                }
-            }
-        }
+        let assets = config
+            .asset_addr
+            .send(GetAssetsInfo(stakes.stake.clone()))
+            .await
+            .unwrap();
+        authenticated_stakes.extend(
+            assets
+                .into_iter()
+                .filter(|asset| asset.get_owner().as_ref() == Some(peer_id)),
+        );
     }
 
     config.debug("Ownership verified");
-    let mut stake_ledger = AssetLedger::new("stake_ledger");
-    stake_ledger.assets = authenticated_stakes;
+    let stake_ledger = AssetLedger::from_assets("stake_ledger", authenticated_stakes);
     let validator_peer_id = stake_ledger.choose_validator().unwrap();
     config.debug(&format!("Validator: {}", &validator_peer_id));
 
@@ -135,6 +360,10 @@ pub async fn check_ownership(config: &Config, owner: &str, asset_id: &str) -> bo
         .unwrap()
         .unwrap();
     config.debug(&format!("Owner: {:?}", asset_info.get_owner()));
+    if asset_info.is_spent() {
+        config.debug("Asset was split, merged or burned");
+        return false;
+    }
     if let Some(asset_owner) = asset_info.get_owner() {
         if asset_owner == owner {
             config.debug("Ownership verified");
@@ -147,6 +376,283 @@ pub async fn check_ownership(config: &Config, owner: &str, asset_id: &str) -> bo
     }
 }
 
+/// checks if a transaction can be applied: assets that change hands
+/// must be owned by the seller, splits, merges and burns must conserve
+/// value and tokens spent must be covered by the payer's balance.
+/// Mints aren't validated by peers, see [check_mint]
+pub async fn check_transaction(
+    config: &Config,
+    seller: &str,
+    buyer: &str,
+    asset_id: Option<&str>,
+    kind: &TxKind,
+) -> bool {
+    use crate::asset::CheckChange;
+    use crate::chain::GetBalance;
+
+    if !kind.is_validated() {
+        return false;
+    }
+    if kind.spends_asset() {
+        match asset_id {
+            Some(asset_id) if check_ownership(config, seller, asset_id).await => (),
+            _ => return false,
+        }
+    }
+    if (kind.changes_asset() || kind.bonds_asset()) && seller != buyer {
+        config.debug("Splits, merges, burns and bonds can't change owners");
+        return false;
+    }
+    if kind.changes_asset() {
+        let msg = CheckChange {
+            owner: seller.into(),
+            asset_id: asset_id.unwrap_or_default().into(),
+            kind: kind.clone(),
+        };
+        if let Err(e) = config.get_asset_ledger().await.send(msg).await.unwrap() {
+            config.debug(&format!("Asset change rejected: {}", e));
+            return false;
+        }
+    }
+    if let Err(e) = check_stake(config, seller, buyer, asset_id, kind).await {
+        config.debug(&format!("Stake lock violated: {}", e));
+        return false;
+    }
+    if let Some(payer) = kind.payer(seller, buyer) {
+        let cost = match kind.cost() {
+            Some(cost) => cost,
+            None => {
+                config.debug("Transaction amounts overflow");
+                return false;
+            }
+        };
+        let balance = config
+            .get_chain_addr()
+            .await
+            .send(GetBalance(payer.into()))
+            .await
+            .unwrap();
+        if balance < cost {
+            config.debug(&format!("Insufficient balance: {} has {}", payer, balance));
+            return false;
+        }
+    }
+    true
+}
+
+/// checks if a transaction from `seller` to `buyer` respects stake locks,
+/// see [AssetLedger::check_stake]. Slashing evidence must name `buyer` as
+/// the offending validator, its blocks must have been seen by this peer
+/// and it can only be used once
+pub async fn check_stake(
+    config: &Config,
+    seller: &str,
+    buyer: &str,
+    asset_id: Option<&str>,
+    kind: &TxKind,
+) -> TxResult<()> {
+    use crate::asset::CheckStake;
+    use crate::chain::HasEvidence;
+
+    if let TxKind::Slash { evidence, .. } = kind {
+        if !evidence.is_valid()
+            || evidence.offender().map(|offender| offender.as_str()) != Some(buyer)
+        {
+            return Err(TxError::InvalidEvidence);
+        }
+        if !has_seen(config, &evidence.first).await || !has_seen(config, &evidence.second).await {
+            return Err(TxError::UnseenEvidence);
+        }
+        if config
+            .get_chain_addr()
+            .await
+            .send(HasEvidence(evidence.id()))
+            .await
+            .unwrap()
+        {
+            return Err(TxError::EvidenceUsed);
+        }
+    }
+    let msg = CheckStake {
+        owner: seller.into(),
+        asset_id: asset_id.unwrap_or_default().into(),
+        kind: kind.clone(),
+        block_id: get_next_block_id(config).await,
+        period: config.unbonding_period,
+    };
+    config.get_asset_ledger().await.send(msg).await.unwrap()
+}
+
+/// checks if a block minting tokens was created by the auditor
+pub async fn check_mint(config: &Config, client: &Client, block: &Block) -> bool {
+    let auditor = client.get_identity(&config.auditor_node).await;
+    block.get_tx() == Some(&auditor.id) && block.get_validator() == Some(&auditor.id)
+}
+
+/// checks if `seller` can spend output `input` of asset `asset_id` on
+/// UTXO ledgers, transactions that don't move assets don't spend outputs
+pub async fn check_spend(
+    config: &Config,
+    seller: &str,
+    asset_id: Option<&str>,
+    input: Option<&str>,
+) -> TxResult<()> {
+    use crate::asset::CheckSpend;
+
+    match asset_id {
+        Some(asset_id) => config
+            .get_asset_ledger()
+            .await
+            .send(CheckSpend {
+                asset_id: asset_id.into(),
+                input: input.map(|input| input.into()),
+                owner: seller.into(),
+            })
+            .await
+            .unwrap(),
+        None => Ok(()),
+    }
+}
+
+/// splits, merges or burns asset `asset_id` of this peer as described
+/// by `kind`, after checking that the asset is owned by this peer and
+/// that values are conserved
+pub async fn change_asset(
+    config: &Config,
+    client: &Client,
+    asset_id: &str,
+    kind: TxKind,
+) -> TxResult<()> {
+    use crate::asset::{CheckChange, GetAssetInfo};
+
+    let asset_addr = config.get_asset_ledger().await;
+    let asset = asset_addr
+        .send(GetAssetInfo(asset_id.into()))
+        .await
+        .unwrap()
+        .ok_or(TxError::UnknownAsset)?;
+    asset_addr
+        .send(CheckChange {
+            owner: config.peer_id.clone(),
+            asset_id: asset_id.into(),
+            kind: kind.clone(),
+        })
+        .await
+        .unwrap()?;
+
+    let tx = Tx {
+        asset_id: asset_id.into(),
+        buyer_peer_id: config.peer_id.clone(),
+        kind,
+        input: asset.get_output().cloned(),
+    };
+    submit_tx(config, client, &config.peer_id, tx).await;
+    Ok(())
+}
+
+/// bonds asset `asset_id` of this peer or unbonds it, as described
+/// by `kind`, after checking that stake locks are respected
+pub async fn bond_asset(
+    config: &Config,
+    client: &Client,
+    asset_id: &str,
+    kind: TxKind,
+) -> TxResult<()> {
+    check_stake(
+        config,
+        &config.peer_id,
+        &config.peer_id,
+        Some(asset_id),
+        &kind,
+    )
+    .await?;
+    let tx = Tx {
+        asset_id: asset_id.into(),
+        buyer_peer_id: config.peer_id.clone(),
+        kind,
+        input: None,
+    };
+    submit_tx(config, client, &config.peer_id, tx).await;
+    Ok(())
+}
+
+/// submits `evidence` of conflicting blocks, slashing the assets
+/// locked by the validator that signed them. Blocks can be in any order
+pub async fn slash(config: &Config, client: &Client, evidence: Evidence) -> TxResult<()> {
+    use crate::asset::GetSlashable;
+
+    let evidence =
+        Evidence::new(evidence.first, evidence.second).ok_or(TxError::InvalidEvidence)?;
+    let offender = evidence
+        .offender()
+        .ok_or(TxError::InvalidEvidence)?
+        .to_owned();
+    let assets = config
+        .get_asset_ledger()
+        .await
+        .send(GetSlashable {
+            peer_id: offender.clone(),
+            block_id: get_next_block_id(config).await,
+        })
+        .await
+        .unwrap();
+    let kind = TxKind::Slash {
+        evidence: Box::new(evidence),
+        assets,
+    };
+    check_stake(config, &config.peer_id, &offender, None, &kind).await?;
+    let tx = Tx {
+        asset_id: String::default(),
+        buyer_peer_id: offender,
+        kind,
+        input: None,
+    };
+    submit_tx(config, client, &config.peer_id, tx).await;
+    Ok(())
+}
+
+/// sends transaction `tx` from `seller` to the validator chosen for
+/// the next block, to all replicas when peers use PBFT and to all peers
+/// when validators are chosen by lottery. The transaction carries the
+/// seller's nonce and the chain ID for replay protection
+pub async fn submit_tx(config: &Config, client: &Client, seller: &str, tx: Tx) {
+    use crate::chain::{GetChainId, GetNonce};
+    use crate::payload::ValidateTxBuilder;
+
+    let chain_addr = config.get_chain_addr().await;
+    let nonce = chain_addr.send(GetNonce(seller.into())).await.unwrap();
+    let chain_id = chain_addr.send(GetChainId).await.unwrap();
+    let validator_payload = ValidateTxBuilder::default()
+        .tx(tx)
+        .seller_peer_id(seller.into())
+        .nonce(Some(nonce))
+        .chain_id(Some(chain_id))
+        .build()
+        .unwrap();
+    if config.consensus == Consensus::Pbft {
+        pbft_broadcast(config, client, PbftMessage::Request(validator_payload)).await;
+        return;
+    }
+    // lottery winners aren't known before they create blocks
+    if config.consensus == Consensus::Vrf {
+        use crate::discovery::DumpPeer;
+
+        let peers = config.network_addr.send(DumpPeer).await.unwrap();
+        for peer in peers.iter() {
+            client
+                .send_tx_to_validator(config, peer, &validator_payload)
+                .await;
+        }
+        return;
+    }
+    let next_block_id = get_next_block_id(config).await;
+    if let Some(validator) = consensus(config, next_block_id, client).await {
+        client
+            .send_tx_to_validator(config, &validator, &validator_payload)
+            .await;
+    }
+}
+
 /// get next block ID utility
 pub async fn get_next_block_id(config: &Config) -> usize {
     use crate::chain::GetLastBlock;
@@ -156,7 +662,11 @@ pub async fn get_next_block_id(config: &Config) -> usize {
         .send(GetLastBlock)
         .await
         .unwrap();
+    next_serial_no(config, &current_block)
+}
 
+/// serial number of the block after `current_block`
+fn next_serial_no(config: &Config, current_block: &Block) -> usize {
     if current_block.get_serial_no().unwrap() == 0 {
         config.init_network_size + 1
     } else {
@@ -169,55 +679,52 @@ pub async fn get_next_block_id(config: &Config) -> usize {
 /// 2. mutate validation assets and sold last transaction
 /// 3. add block to chain
 pub async fn add_block_runner(config: &Config, client: &Client, block: &Block) {
-    use crate::asset::{ChangeAssetOwnerBuilder, SetLastTransationBuilder};
+    use crate::asset::{
+        ApplyBlocks, ChangeAssetOwnerBuilder, LockStake, SetLastTransactions,
+        SetLastTransationBuilder,
+    };
     use crate::chain::AddBlock;
     use crate::client::GetStake as ClientGetStake;
 
     let next_block_id = get_next_block_id(&config).await;
 
-    // changing asset ownsership
-    config.debug(&format!(
-        "Chainging ownership of asset: {}",
-        block.get_asset_id().unwrap()
-    ));
-    let change_ownsership_msg = ChangeAssetOwnerBuilder::default()
-        .asset_id(block.get_asset_id().unwrap().into())
-        .new_owner(block.get_rx().unwrap().into())
-        .build()
-        .unwrap();
-    config
-        .get_asset_ledger()
-        .await
-        .send(change_ownsership_msg)
-        .await
-        .unwrap();
-
-    // changing coinage of the asset transacted
-    config.debug(&format!(
-        "Chainging coinage of asset: {}",
-        block.get_asset_id().unwrap()
-    ));
-    let change_tx_msg = SetLastTransationBuilder::default()
-        .tx(next_block_id)
-        .asset_id(block.get_asset_id().unwrap().into())
-        .build()
-        .unwrap();
-    config
-        .get_asset_ledger()
-        .await
-        .send(change_tx_msg)
-        .await
-        .unwrap();
-
-    // changing coinage of the assets staked by the validator
-    let client_payload = ClientGetStake {
-        peer_id: block.get_validator().unwrap().into(),
-        block_id: next_block_id,
-    };
-    let validator_stakes = client.get_stake(client_payload, &config).await;
-    for asset_id in validator_stakes.stake.iter() {
-        config.debug(&format!("Chainging coinage of asset: {}", &asset_id));
+    // token transfers don't move assets
+    if block.get_kind().is_applied() {
+        // minting, splitting, merging, burning, bonding or slashing assets
+        config.debug(&format!(
+            "Applying asset changes of block {}",
+            block.get_hash()
+        ));
+        config
+            .get_asset_ledger()
+            .await
+            .send(ApplyBlocks(vec![block.to_owned()]))
+            .await
+            .unwrap();
+    } else if let Some(asset_id) = block.get_asset_id() {
+        // changing asset ownsership
+        config.debug(&format!("Chainging ownership of asset: {}", asset_id));
+        let change_ownsership_msg = ChangeAssetOwnerBuilder::default()
+            .asset_id(asset_id.into())
+            .new_owner(block.get_rx().unwrap().into())
+            .build()
+            .unwrap();
+        config
+            .get_asset_ledger()
+            .await
+            .send(change_ownsership_msg)
+            .await
+            .unwrap();
+        config
+            .log_event(Action::OwnershipChanged(
+                config.peer_id.clone(),
+                asset_id.into(),
+                block.get_rx().unwrap().into(),
+            ))
+            .await;
 
+        // changing coinage of the asset transacted
+        config.debug(&format!("Chainging coinage of asset: {}", asset_id));
         let change_tx_msg = SetLastTransationBuilder::default()
             .tx(next_block_id)
             .asset_id(asset_id.into())
@@ -231,15 +738,748 @@ pub async fn add_block_runner(config: &Config, client: &Client, block: &Block) {
             .unwrap();
     }
 
+    // changing coinage of the assets staked by the validator,
+    // mints aren't validated by stake holders and PBFT replicas
+    // don't choose validators by coinage
+    if block.get_kind().is_validated() && config.consensus == Consensus::Stake {
+        let client_payload = ClientGetStake {
+            peer_id: block.get_validator().unwrap().into(),
+            block_id: next_block_id,
+        };
+        let validator_stakes = client.get_stake(client_payload, &config).await;
+        config.debug(&format!(
+            "Chainging coinage of assets: {:?}",
+            &validator_stakes.stake
+        ));
+        let change_tx_msgs = validator_stakes
+            .stake
+            .iter()
+            .map(|asset_id| {
+                SetLastTransationBuilder::default()
+                    .tx(next_block_id)
+                    .asset_id(asset_id.into())
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        let asset_addr = config.get_asset_ledger().await;
+        asset_addr
+            .send(SetLastTransactions(change_tx_msgs))
+            .await
+            .unwrap();
+        // staked assets can't be moved until the validator can no
+        // longer be slashed for the block
+        asset_addr
+            .send(LockStake {
+                assets: validator_stakes.stake,
+                unlock: next_block_id.saturating_add(config.unbonding_period),
+            })
+            .await
+            .unwrap();
+    }
+
     // adding block to chain
     config.info(&format!("Adding block {} to chain", block.get_hash()));
-    config
+    let serial_no = config
         .get_chain_addr()
         .await
         .send(AddBlock(block.to_owned(), config.init_network_size))
         .await
         .unwrap()
         .unwrap();
+    config
+        .log_event(Action::BlockAdded(config.peer_id.clone(), block.to_owned()))
+        .await;
+    vote(config, client, block, serial_no).await;
+}
+
+/// votes for `block`, which was added to the chain with serial number
+/// `serial_no`, and sends the vote to all peers
+pub async fn vote(config: &Config, client: &Client, block: &Block, serial_no: usize) {
+    use crate::discovery::DumpPeer;
+
+    let vote = Vote {
+        voter: config.peer_id.clone(),
+        hash: block.get_hash().into(),
+        serial_no,
+    };
+    if !record_vote(config, vote.clone()).await {
+        config.debug(&format!("Already voted for block {}", serial_no));
+        return;
+    }
+    let peers = config.network_addr.send(DumpPeer).await.unwrap();
+    for peer in peers.iter().filter(|peer| peer.id != config.peer_id) {
+        client.send_vote(config, peer, &vote).await;
+    }
+}
+
+/// checks if a vote gossiped by a peer was cast by its voter: the voter
+/// must be a registered peer and must return the same vote from its
+/// registered address
+pub async fn confirm_vote(config: &Config, client: &Client, vote: &Vote) -> bool {
+    use crate::discovery::GetPeer;
+
+    if vote.voter == config.peer_id {
+        return false;
+    }
+    match config
+        .network_addr
+        .send(GetPeer(vote.voter.clone()))
+        .await
+        .unwrap()
+    {
+        Some(voter) => {
+            client
+                .get_vote(config, &voter, vote.serial_no)
+                .await
+                .as_ref()
+                == Some(vote)
+        }
+        None => false,
+    }
+}
+
+/// records `vote` and finalizes the block it is for once votes for the
+/// block represent more than 2/3 of stake. Returns false if the voter
+/// already voted for a block with the same serial number
+pub async fn record_vote(config: &Config, vote: Vote) -> bool {
+    use crate::asset::GetVotingStake;
+    use crate::chain::Finalize;
+    use crate::finality::{AddVote, IsFinal};
+
+    if !config
+        .finality_addr
+        .send(AddVote(vote.clone()))
+        .await
+        .unwrap()
+    {
+        return false;
+    }
+
+    let stake = config.asset_addr.send(GetVotingStake).await.unwrap();
+    let is_final = config
+        .finality_addr
+        .send(IsFinal {
+            hash: vote.hash.clone(),
+            stake,
+        })
+        .await
+        .unwrap();
+    if is_final
+        && config
+            .chain_addr
+            .send(Finalize(vote.hash.clone()))
+            .await
+            .unwrap()
+    {
+        config.info(&format!("Block {} is final", vote.hash));
+        config
+            .log_event(Action::BlockFinalized(config.peer_id.clone(), vote.hash))
+            .await;
+    }
+    true
+}
+
+/// PBFT replicas: all peers and the auditor, sorted by ID
+pub async fn pbft_replicas(config: &Config, client: &Client) -> Vec<Peer> {
+    use crate::config::{GetMode, Mode};
+    use crate::discovery::DumpPeer;
+
+    let mut replicas = config.network_addr.send(DumpPeer).await.unwrap();
+    let auditor = if config.mode_addr.send(GetMode).await.unwrap() == Mode::Auditor {
+        Peer {
+            id: config.peer_id.clone(),
+            ip: config.public_ip.clone(),
+        }
+    } else {
+        Peer {
+            ip: config.auditor_node.clone(),
+            ..client.get_identity(&config.auditor_node).await
+        }
+    };
+    replicas.push(auditor);
+    replicas.sort_by(|a, b| a.id.cmp(&b.id));
+    replicas.dedup_by(|a, b| a.id == b.id);
+    replicas
+}
+
+/// sends PBFT message `msg` to all replicas, including this one.
+/// Messages are sent in the background so that replicas handling each
+/// other's messages don't wait on each other. Messages are recorded
+/// first, so that replicas can confirm them
+pub async fn pbft_broadcast(config: &Config, client: &Client, msg: PbftMessage) {
+    use crate::pbft::RecordSent;
+
+    config
+        .pbft_addr
+        .send(RecordSent(msg.clone()))
+        .await
+        .unwrap();
+    for replica in pbft_replicas(config, client).await.into_iter() {
+        let (config, client, msg) = (config.clone(), client.clone(), msg.clone());
+        actix::spawn(async move {
+            client.send_pbft(&config, &replica, &msg).await;
+        });
+    }
+}
+
+/// checks a transaction request the same way validators do before
+/// creating blocks
+pub async fn check_request(config: &Config, request: &ValidateTx) -> bool {
+    if let Err(e) = check_replay(config, request).await {
+        config.debug(&format!("Request rejected: {}", e));
+        return false;
+    }
+    if request.tx.kind.spends_asset() {
+        if let Err(e) = check_spend(
+            config,
+            &request.seller_peer_id,
+            Some(&request.tx.asset_id),
+            request.tx.input.as_deref(),
+        )
+        .await
+        {
+            config.debug(&format!("Request rejected: {}", e));
+            return false;
+        }
+    }
+    check_transaction(
+        config,
+        &request.seller_peer_id,
+        &request.tx.buyer_peer_id,
+        Some(&request.tx.asset_id),
+        &request.tx.kind,
+    )
+    .await
+}
+
+/// checks if the block of a PBFT proposal executes its request, was
+/// created by a replica and extends the chain. Proposals for later
+/// serial numbers wait for this replica to execute earlier blocks
+pub async fn check_proposal(config: &Config, proposal: &Proposal, replicas: &[String]) -> bool {
+    use crate::chain::GetLastBlock;
+    use actix::clock::delay_for;
+    use std::time::Duration;
+
+    let (block, request) = (&proposal.block, &proposal.request);
+    let interval = 100;
+    for _ in 0..=config.pbft_timeout / interval {
+        if get_next_block_id(config).await >= proposal.seq {
+            break;
+        }
+        delay_for(Duration::from_millis(interval)).await;
+    }
+
+    let tip = config
+        .get_chain_addr()
+        .await
+        .send(GetLastBlock)
+        .await
+        .unwrap();
+    if get_next_block_id(config).await != proposal.seq
+        || block.get_prev().map(|prev| prev.as_str()) != Some(tip.get_hash())
+    {
+        config.debug(&format!("Proposal {} doesn't extend chain", proposal.seq));
+        return false;
+    }
+    if block.get_tx() != Some(&request.seller_peer_id)
+        || block.get_rx() != Some(&request.tx.buyer_peer_id)
+        || block.get_asset_id() != Some(&request.tx.asset_id)
+        || block.get_kind() != &request.tx.kind
+        || !block
+            .get_validator()
+            .map(|validator| replicas.contains(validator))
+            .unwrap_or(false)
+    {
+        config.debug(&format!("Proposal {} doesn't match request", proposal.seq));
+        return false;
+    }
+    check_request(config, request).await
+}
+
+/// checks if PBFT message `msg` was sent by the replica it names: the
+/// replica must return it from the address it registered with.
+/// Requests come from clients and always pass
+pub async fn confirm_pbft(
+    config: &Config,
+    client: &Client,
+    msg: &PbftMessage,
+    replicas: &[Peer],
+) -> bool {
+    use crate::pbft::HasSent;
+
+    let sender = match msg.replica() {
+        Some(sender) => sender,
+        None => return true,
+    };
+    if sender == &config.peer_id {
+        return config.pbft_addr.send(HasSent(msg.digest())).await.unwrap();
+    }
+    match replicas.iter().find(|replica| &replica.id == sender) {
+        Some(replica) => client.has_sent_pbft(config, replica, msg).await,
+        None => false,
+    }
+}
+
+/// handles PBFT message `msg` from a client or replica
+pub async fn pbft_receive(config: &Config, client: &Client, msg: PbftMessage) {
+    use crate::pbft::Receive;
+
+    let peers = pbft_replicas(config, client).await;
+    if !confirm_pbft(config, client, &msg, &peers).await {
+        config.debug(&format!(
+            "Ignoring PBFT message not sent by {}",
+            msg.replica().map(String::as_str).unwrap_or_default()
+        ));
+        return;
+    }
+    let replicas: Vec<String> = peers.into_iter().map(|replica| replica.id).collect();
+    let accepted = match &msg {
+        PbftMessage::Request(request) => check_request(config, request).await,
+        PbftMessage::PrePrepare { proposal, .. } => {
+            check_proposal(config, proposal, &replicas).await
+        }
+        _ => true,
+    };
+    if !accepted {
+        return;
+    }
+    let steps = config
+        .pbft_addr
+        .send(Receive {
+            msg,
+            replicas: replicas.clone(),
+            me: config.peer_id.clone(),
+        })
+        .await
+        .unwrap();
+    pbft_steps(config, client, steps, &replicas).await;
+}
+
+/// takes the steps returned by the PBFT actor, in order
+pub async fn pbft_steps(config: &Config, client: &Client, steps: Vec<Step>, replicas: &[String]) {
+    for step in steps.into_iter() {
+        match step {
+            Step::Broadcast(msg) => pbft_broadcast(config, client, msg).await,
+            Step::Execute(proposal) => pbft_execute(config, client, proposal).await,
+            Step::NewView(view) => {
+                config.info(&format!("Moved to view {}", view));
+                config
+                    .log_event(Action::ViewChanged(config.peer_id.clone(), view))
+                    .await;
+            }
+            Step::Timer(request_digest) => pbft_timer(config, client, request_digest).await,
+            Step::Propose => pbft_propose(config, client, replicas).await,
+        }
+    }
+}
+
+/// queues committed block `proposal` and adds queued blocks to the
+/// chain in order. When the next queued block doesn't extend the chain,
+/// this replica missed blocks and syncs its chain with the network
+async fn pbft_execute(config: &Config, client: &Client, proposal: Box<Proposal>) {
+    use crate::chain::GetLastBlock;
+    use crate::pbft::{GetPbftStatus, NextBlock, QueueBlock};
+
+    config.pbft_addr.send(QueueBlock(proposal)).await.unwrap();
+    let mut synced = false;
+    loop {
+        let tip = config
+            .get_chain_addr()
+            .await
+            .send(GetLastBlock)
+            .await
+            .unwrap();
+        let next = NextBlock {
+            seq: get_next_block_id(config).await,
+            tip: tip.get_hash().into(),
+        };
+        match config.pbft_addr.send(next).await.unwrap() {
+            Some(proposal) => {
+                config.info(&format!("Executing block {}", proposal.seq));
+                add_block_runner(config, client, &proposal.block).await;
+            }
+            None => {
+                let queued = config.pbft_addr.send(GetPbftStatus).await.unwrap().queued;
+                if queued.is_empty() {
+                    break;
+                }
+                if synced {
+                    config.debug(&format!("Blocks {:?} wait for earlier blocks", queued));
+                    break;
+                }
+                config.debug("Fetching missing blocks");
+                sync_chain(config, client).await;
+                synced = true;
+            }
+        }
+    }
+    pbft_prune(config).await;
+}
+
+/// drops pending requests that can't be executed anymore, like
+/// conflicting sales of an asset that was already sold
+async fn pbft_prune(config: &Config) {
+    use crate::pbft::{digest, DropRequest, GetPbftStatus};
+
+    let status = config.pbft_addr.send(GetPbftStatus).await.unwrap();
+    for request in status.requests.iter() {
+        if !check_request(config, request).await {
+            config.debug("Dropping request that can't be executed");
+            config
+                .pbft_addr
+                .send(DropRequest(digest(request)))
+                .await
+                .unwrap();
+        }
+    }
+}
+
+/// starts view change timer for request `request_digest` in the
+/// current view
+async fn pbft_timer(config: &Config, client: &Client, request_digest: String) {
+    use crate::pbft::{GetPbftStatus, Timeout};
+    use actix::clock::delay_for;
+    use std::time::Duration;
+
+    let view = config.pbft_addr.send(GetPbftStatus).await.unwrap().view;
+    let (config, client) = (config.clone(), client.clone());
+    actix::spawn(async move {
+        delay_for(Duration::from_millis(config.pbft_timeout)).await;
+        let timeout = Timeout {
+            view,
+            digest: request_digest,
+            me: config.peer_id.clone(),
+        };
+        if let Some(msg) = config.pbft_addr.send(timeout).await.unwrap() {
+            config.info(&format!("Request timed out in view {}", view));
+            pbft_broadcast(&config, &client, msg).await;
+        }
+    });
+}
+
+/// proposes the next request if this replica is the primary for the
+/// next block. Requests that can't be executed anymore are dropped
+async fn pbft_propose(config: &Config, client: &Client, replicas: &[String]) {
+    use crate::block::BlockBuilder;
+    use crate::chain::GetLastBlock;
+    use crate::pbft::{digest, primary, DropRequest, GetPbftStatus, NextProposal};
+
+    loop {
+        let seq = get_next_block_id(config).await;
+        let view = config.pbft_addr.send(GetPbftStatus).await.unwrap().view;
+        if primary(replicas, view, seq) != Some(&config.peer_id) {
+            return;
+        }
+        let (request, carried) = match config.pbft_addr.send(NextProposal(seq)).await.unwrap() {
+            Some(next) => next,
+            None => return,
+        };
+        let block = match carried {
+            Some(block) => block,
+            None => {
+                if !check_request(config, &request).await {
+                    config
+                        .pbft_addr
+                        .send(DropRequest(digest(&request)))
+                        .await
+                        .unwrap();
+                    continue;
+                }
+                let last_block = config
+                    .get_chain_addr()
+                    .await
+                    .send(GetLastBlock)
+                    .await
+                    .unwrap();
+                let block = BlockBuilder::default()
+                    .set_tx(&request.seller_peer_id)
+                    .set_rx(&request.tx.buyer_peer_id)
+                    .set_asset_id(&request.tx.asset_id)
+                    .set_validator(&config.peer_id)
+                    .set_kind(request.tx.kind.clone())
+                    .set_input(request.tx.input.as_deref().unwrap_or_default())
+                    .set_nonce(request.nonce)
+                    .set_chain_id(request.chain_id.as_deref())
+                    .set_prev(&last_block)
+                    .build();
+                config.log_event(Action::BlockCreation(block.clone())).await;
+                block
+            }
+        };
+        config.info(&format!("Proposing block {} in view {}", seq, view));
+        let proposal = Proposal {
+            seq,
+            block,
+            request,
+        };
+        let msg = PbftMessage::PrePrepare {
+            view,
+            replica: config.peer_id.clone(),
+            proposal: Box::new(proposal),
+        };
+        pbft_broadcast(config, client, msg).await;
+        return;
+    }
+}
+
+/// updates assets after a reorg: transactions in `discarded` blocks
+/// are undone and transactions in `adopted` blocks are applied
+pub async fn reorg_assets(asset_addr: &Addr<AssetLedger>, discarded: &[Block], adopted: &[Block]) {
+    use crate::asset::{ApplyBlocks, UndoBlocks};
+
+    let undo = discarded
+        .iter()
+        .rev()
+        .filter(|block| !block.is_genesis())
+        .cloned()
+        .collect();
+    let redo = adopted
+        .iter()
+        .filter(|block| !block.is_genesis())
+        .cloned()
+        .collect();
+
+    asset_addr.send(UndoBlocks(undo)).await.unwrap();
+    asset_addr.send(ApplyBlocks(redo)).await.unwrap();
+}
+
+/// catches up with the longest chain in the network. Peers that don't
+/// take part in consensus, like the auditor, call this before creating
+/// blocks. Assets are updated with [reorg_assets]
+pub async fn sync_chain(config: &Config, client: &Client) {
+    use crate::chain::Reorg;
+    use crate::discovery::DumpPeer;
+
+    let chain_addr = config.get_chain_addr().await;
+    let peers = config.network_addr.send(DumpPeer).await.unwrap();
+    for peer in peers.iter().filter(|peer| peer.id != config.peer_id) {
+        let chain = client.get_chain(config, &peer.ip).await;
+        match chain_addr
+            .send(Reorg {
+                chain: chain.clone(),
+                checkpoint: config.checkpoint,
+            })
+            .await
+            .unwrap()
+        {
+            Ok((fork_point, discarded)) => {
+                config.debug(&format!(
+                    "Synced chain with {}, adopting {} blocks",
+                    peer.id,
+                    chain.len() - fork_point
+                ));
+                reorg_assets(
+                    &config.get_asset_ledger().await,
+                    &discarded,
+                    &chain[fork_point..],
+                )
+                .await;
+            }
+            Err(e) => config.debug(&format!("Not syncing chain with {}: {}", peer.id, e)),
+        }
+    }
+}
+
+/// checks if minting the asset of `block` respects the supply policy
+/// of this peer, blocks that don't mint assets always pass
+pub async fn check_supply(config: &Config, block: &Block) -> TxResult<()> {
+    use crate::asset::GetSupply;
+    use crate::chain::GetMinted;
+
+    let value = match block.get_kind() {
+        TxKind::MintAsset { value, .. } => *value,
+        _ => return Ok(()),
+    };
+    let asset_addr = config.get_asset_ledger().await;
+    if let Some(asset_id) = block.get_asset_id() {
+        if asset_addr
+            .send(GetAssetInfo(asset_id.into()))
+            .await
+            .unwrap()
+            .is_some()
+        {
+            return Err(TxError::AssetExists);
+        }
+    }
+    let supply = asset_addr.send(GetSupply).await.unwrap();
+    let minted = config.get_chain_addr().await.send(GetMinted).await.unwrap();
+    let block_id = get_next_block_id(config).await;
+    config.supply_policy.check(supply, minted, block_id, value)
+}
+
+/// copies legitimate chain and assets into the tampered chain and assets
+pub async fn fork_state(config: &Config) {
+    use crate::asset::{DumpLedger as DumpAsset, ReplaceLedger as ReplaceAsset};
+    use crate::chain::{DumpLedger, ReplaceChain};
+
+    config.info("Forking chain");
+    let chain = config.chain_addr.send(DumpLedger).await.unwrap();
+    config
+        .tampered_chain_addr
+        .send(ReplaceChain(chain))
+        .await
+        .unwrap()
+        .unwrap();
+    let assets = config.asset_addr.send(DumpAsset).await.unwrap();
+    config
+        .tampered_asset_addr
+        .send(ReplaceAsset(assets))
+        .await
+        .unwrap();
+}
+
+/// get serial number of the next block of the tampered chain
+pub async fn get_next_private_block_id(config: &Config) -> usize {
+    use crate::chain::GetLastBlock;
+    let current_block = config.tampered_chain_addr.send(GetLastBlock).await.unwrap();
+    next_serial_no(config, &current_block)
+}
+
+/// privately extends the tampered chain with a block validated by
+/// `validator`, which must be this peer or one of its sybils. The
+/// validator of the next block of the tampered chain is chosen with
+/// [consensus] like any other block.
+/// Returns the number of blocks that the tampered chain leads the
+/// legitimate chain by
+pub async fn mine_private_block(config: &Config, validator: &Peer) -> PeerResult<usize> {
+    use crate::asset::GetPeerAssets;
+    use crate::attack::GetSelfish;
+    use crate::block::BlockBuilder;
+    use crate::chain::{AddBlock, DumpLedger, GetLastBlock};
+
+    if !config.attack_addr.send(GetSelfish).await.unwrap() {
+        return Err(PeerError::NotSelfish);
+    }
+    if !is_own_identity(config, &validator.id).await {
+        config.debug(&format!("{} won validator selection", &validator.id));
+        return Err(PeerError::NotValidator);
+    }
+
+    let stake = config
+        .tampered_asset_addr
+        .send(GetPeerAssets(validator.id.clone()))
+        .await
+        .unwrap();
+    let private = config.tampered_chain_addr.send(DumpLedger).await.unwrap();
+    let asset = stake
+        .get(private.len() % stake.len().max(1))
+        .ok_or(PeerError::NoStake)?;
+
+    let last_block = config.tampered_chain_addr.send(GetLastBlock).await.unwrap();
+    let block = BlockBuilder::default()
+        .set_tx(&config.peer_id)
+        .set_rx(&config.peer_id)
+        .set_asset_id(asset.get_hash())
+        .set_validator(&validator.id)
+        .set_prev(&last_block)
+        .build();
+    config.debug(&format!("Withholding block {}", block.get_hash()));
+    config
+        .tampered_chain_addr
+        .send(AddBlock(block, config.init_network_size))
+        .await
+        .unwrap()
+        .map_err(PeerError::ChainError)?;
+
+    let public = config.chain_addr.send(DumpLedger).await.unwrap();
+    Ok((private.len() + 1).saturating_sub(public.len()))
+}
+
+/// releases withheld blocks: the tampered chain replaces the legitimate
+/// chain and is sent to all peers. Without `force`, blocks are only released
+/// when honest peers have caught up to within one block of the tampered
+/// chain, which orphans their blocks. When honest peers overtake the
+/// tampered chain, it is abandoned and forked again.
+/// Returns number of blocks released
+pub async fn release_withheld_blocks(config: &Config, client: &Client, force: bool) -> usize {
+    use crate::chain::{Chain, DumpLedger, Reorg};
+    use crate::discovery::DumpPeer;
+
+    let public = config.chain_addr.send(DumpLedger).await.unwrap();
+    let private = config.tampered_chain_addr.send(DumpLedger).await.unwrap();
+    let fork_point = Chain::fork_point(&public, &private);
+
+    if private.len() <= public.len() {
+        if fork_point < public.len() {
+            config.debug("Honest chain overtook withheld blocks, forking again");
+            fork_state(config).await;
+        }
+        return 0;
+    }
+
+    if !force && private.len() - public.len() > 1 {
+        return 0;
+    }
+
+    match config
+        .chain_addr
+        .send(Reorg {
+            chain: private.clone(),
+            checkpoint: None,
+        })
+        .await
+        .unwrap()
+    {
+        Ok((_, discarded)) => {
+            config.info(&format!(
+                "Releasing {} withheld blocks, orphaning {} blocks",
+                private.len() - fork_point,
+                discarded.len()
+            ));
+            reorg_assets(&config.asset_addr, &discarded, &private[fork_point..]).await;
+        }
+        Err(e) => {
+            config.debug(&format!("Releasing withheld blocks failed: {}", e));
+            return 0;
+        }
+    }
+
+    let peers = config.network_addr.send(DumpPeer).await.unwrap();
+    for peer in peers.iter().filter(|peer| peer.id != config.peer_id) {
+        client.send_chain_to_peer(config, peer, &private).await;
+    }
+    private.len() - fork_point
+}
+
+/// computes share of blocks validated by every peer and its
+/// share of stake
+pub fn shares(chain: &[Block], assets: &[Asset]) -> Vec<Share> {
+    let blocks: Vec<&String> = chain
+        .iter()
+        .filter_map(|block| block.get_validator())
+        .collect();
+    let owners: Vec<&String> = assets
+        .iter()
+        .filter_map(|asset| asset.get_owner().as_ref())
+        .collect();
+
+    let mut peers: Vec<&String> = blocks.iter().chain(owners.iter()).copied().collect();
+    peers.sort();
+    peers.dedup();
+
+    let fraction = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    };
+
+    peers
+        .iter()
+        .map(|peer_id| {
+            let validated = blocks.iter().filter(|id| id == &peer_id).count();
+            let stake = owners.iter().filter(|id| id == &peer_id).count();
+            Share {
+                peer_id: peer_id.to_string(),
+                blocks: validated,
+                block_share: fraction(validated, blocks.len()),
+                stake,
+                stake_share: fraction(stake, owners.len()),
+            }
+        })
+        .collect()
 }
 
 /// broadcast block to all peers
@@ -247,6 +1487,10 @@ pub async fn broadcast_block(config: &Config, client: &Client, block: &Block) {
     use crate::config::{GetMode, Mode};
     use crate::discovery::DumpPeer;
     let peers = config.network_addr.send(DumpPeer).await.unwrap();
+    let broadcaster = Peer {
+        id: config.peer_id.clone(),
+        ip: config.public_ip.clone(),
+    };
     for peer in peers.iter() {
         config.debug(&format!("cheking broadcast. peer ID {}", &peer.id));
         if peer.id != config.peer_id {
@@ -261,6 +1505,12 @@ pub async fn broadcast_block(config: &Config, client: &Client, block: &Block) {
                         &peer.id
                     ));
 
+                    config
+                        .log_event(Action::TransactionBroadcasting(
+                            broadcaster.clone(),
+                            peer.clone(),
+                        ))
+                        .await;
                     return client.send_block_to_peer(&config, &peer, &block).await;
                 }
             } else {
@@ -270,12 +1520,200 @@ pub async fn broadcast_block(config: &Config, client: &Client, block: &Block) {
                     &peer.id
                 ));
 
+                config
+                    .log_event(Action::TransactionBroadcasting(
+                        broadcaster.clone(),
+                        peer.clone(),
+                    ))
+                    .await;
                 client.send_block_to_peer(&config, &peer, &block).await;
             }
         }
     }
 }
 
+/// records a block received from a peer, evidence of the block's
+/// validator signing conflicting blocks is gossiped. Blocks that are
+/// final or too deep to conflict with new blocks are forgotten
+pub async fn observe_block(config: &Config, client: &Client, block: &Block) {
+    use crate::chain::{GetFinalized, GetLastBlock};
+    use crate::evidence::{ObserveBlock, PruneSeen, EVIDENCE_DEPTH};
+
+    let chain_addr = config.get_chain_addr().await;
+    let height = chain_addr
+        .send(GetLastBlock)
+        .await
+        .unwrap()
+        .get_serial_no()
+        .unwrap_or(0);
+    let finalized = chain_addr
+        .send(GetFinalized)
+        .await
+        .unwrap()
+        .get_serial_no()
+        .unwrap_or(0);
+    config
+        .evidence_addr
+        .send(PruneSeen(
+            height.saturating_sub(EVIDENCE_DEPTH).max(finalized),
+        ))
+        .await
+        .unwrap();
+
+    if let Some(evidence) = config
+        .evidence_addr
+        .send(ObserveBlock {
+            block: block.to_owned(),
+            height,
+        })
+        .await
+        .unwrap()
+    {
+        announce_evidence(config, client, &evidence).await;
+    }
+}
+
+/// checks if `block` was seen by this peer: it's in the chain or was
+/// recorded by [EvidenceLog](crate::evidence::EvidenceLog)
+pub async fn has_seen(config: &Config, block: &Block) -> bool {
+    use crate::chain::GetBlock;
+    use crate::evidence::HasSeen;
+
+    config
+        .get_chain_addr()
+        .await
+        .send(GetBlock(block.get_hash().into()))
+        .await
+        .unwrap()
+        .is_some()
+        || config
+            .evidence_addr
+            .send(HasSeen(block.get_hash().into()))
+            .await
+            .unwrap()
+}
+
+/// logs new equivocation evidence and sends it to all peers and the auditor
+pub async fn announce_evidence(config: &Config, client: &Client, evidence: &Evidence) {
+    use crate::config::{GetMode, Mode};
+    use crate::discovery::DumpPeer;
+
+    let offender = match evidence.offender() {
+        Some(offender) => offender,
+        None => return,
+    };
+    log::warn!(
+        "[{}]: Validator {} signed conflicting blocks {} and {}",
+        &config.peer_id,
+        offender,
+        evidence.first.get_hash(),
+        evidence.second.get_hash()
+    );
+    config
+        .log_event(Action::EquivocationDetected(
+            config.peer_id.clone(),
+            Box::new(evidence.to_owned()),
+        ))
+        .await;
+
+    let mut peers = config.network_addr.send(DumpPeer).await.unwrap();
+    if config.mode_addr.send(GetMode).await.unwrap() != Mode::Auditor {
+        let auditor = client.get_identity(&config.auditor_node).await;
+        peers.push(Peer {
+            ip: config.auditor_node.clone(),
+            ..auditor
+        });
+    }
+    for peer in peers.iter().filter(|peer| peer.id != config.peer_id) {
+        client.send_evidence(config, peer, evidence).await;
+    }
+}
+
+/// checks if `requester` is being eclipsed by this peer and returns
+/// its ID. Only attacking peers can eclipse other peers
+pub async fn is_eclipsed(config: &Config, requester: &Option<String>) -> Option<String> {
+    use crate::attack::GetEclipse;
+    use crate::config::{GetMode, Mode};
+
+    if let Mode::Attacker(_) = config.mode_addr.send(GetMode).await.unwrap() {
+        let victim = config.attack_addr.send(GetEclipse).await.unwrap();
+        if victim.is_some() && &victim == requester {
+            return victim;
+        }
+    }
+    None
+}
+
+/// checks if a transaction is being replayed. Transactions must carry
+/// the seller's next nonce and the ID of the chain that they are meant for.
+/// Always passes when replay protection is disabled
+pub async fn check_replay(config: &Config, payload: &ValidateTx) -> TxResult<()> {
+    check_nonce(
+        config,
+        &payload.seller_peer_id,
+        payload.nonce,
+        payload.chain_id.as_deref(),
+    )
+    .await
+}
+
+/// checks if a block replays a transaction, see [check_replay]. Nonce
+/// and chain ID are part of the block hash, so they can't be changed
+/// without changing the block
+pub async fn check_block_replay(config: &Config, block: &Block) -> TxResult<()> {
+    check_nonce(
+        config,
+        block.get_tx().map(String::as_str).unwrap_or_default(),
+        block.get_nonce(),
+        block.get_chain_id().map(String::as_str),
+    )
+    .await
+}
+
+async fn check_nonce(
+    config: &Config,
+    seller: &str,
+    nonce: Option<usize>,
+    chain_id: Option<&str>,
+) -> TxResult<()> {
+    use crate::chain::{GetChainId, GetNonce};
+
+    if !config.replay_protection {
+        return Ok(());
+    }
+
+    if let (Some(nonce), Some(chain_id)) = (nonce, chain_id) {
+        let chain_addr = config.get_chain_addr().await;
+        if chain_id != chain_addr.send(GetChainId).await.unwrap() {
+            return Err(TxError::WrongChain);
+        }
+        let expected = chain_addr.send(GetNonce(seller.into())).await.unwrap();
+        if nonce != expected {
+            return Err(TxError::InvalidNonce);
+        }
+        Ok(())
+    } else {
+        Err(TxError::MissingReplayProtection)
+    }
+}
+
+/// checks if `peer_id` is this peer or one of the fake
+/// identities(sybils) controlled by it
+pub async fn is_own_identity(config: &Config, peer_id: &str) -> bool {
+    use crate::attack::GetSybils;
+
+    if config.peer_id == peer_id {
+        return true;
+    }
+    config
+        .attack_addr
+        .send(GetSybils)
+        .await
+        .unwrap()
+        .iter()
+        .any(|sybil| sybil == peer_id)
+}
+
 /// get state from all peers in network
 pub async fn state(config: &Config, client: &Client) -> Vec<Status> {
     use crate::discovery::DumpPeer;
@@ -291,6 +1729,35 @@ pub async fn state(config: &Config, client: &Client) -> Vec<Status> {
     state
 }
 
+/// state of this peer, tampered ledgers are included for attackers
+pub async fn local_state(config: &Config) -> Status {
+    use crate::asset::DumpLedger as DumpAsset;
+    use crate::chain::DumpLedger as DumpChain;
+    use crate::config::{GetMode, Mode};
+    use crate::payload::StatusBuilder;
+
+    let mode = config.mode_addr.send(GetMode).await.unwrap();
+    let assets = config.asset_addr.send(DumpAsset).await.unwrap();
+    let chain = config.chain_addr.send(DumpChain).await.unwrap();
+    let mut status_builder = StatusBuilder::default();
+    status_builder
+        .asset(assets)
+        .chain(chain)
+        .tampered_chain(None)
+        .tampered_assets(None)
+        .peer_id(config.peer_id.clone());
+    if let Mode::Attacker(_) = mode {
+        let tampered_assets = config.tampered_asset_addr.send(DumpAsset).await.unwrap();
+        let tampered_chain = config.tampered_chain_addr.send(DumpChain).await.unwrap();
+
+        status_builder
+            .tampered_assets(Some(tampered_assets))
+            .tampered_chain(Some(tampered_chain));
+    }
+
+    status_builder.build().unwrap()
+}
+
 /// get state from all peers in network and upload to remote server
 pub async fn upload_to_server(config: &Config, client: &Client) {
     let state = state(&config, &client).await;
@@ -314,6 +1781,114 @@ mod tests {
     //        assert_eq!(validator.id, "victim.batsense.net");
     //    }
 
+    #[actix_rt::test]
+    async fn check_replay_works() {
+        use crate::block::BlockBuilder;
+        use crate::chain::{AddBlock, GetChainId, GetLastBlock};
+        use crate::payload::{TxBuilder, ValidateTxBuilder};
+
+        let mut config = init_network(Mode::Normal).await;
+        let seller = "victim.batsense.net";
+        let chain_id = config.chain_addr.send(GetChainId).await.unwrap();
+        let tx = |nonce: Option<usize>, chain_id: Option<String>| {
+            ValidateTxBuilder::default()
+                .tx(TxBuilder::default()
+                    .asset_id("asset".into())
+                    .buyer_peer_id("attacker.batsense.net".into())
+                    .build()
+                    .unwrap())
+                .seller_peer_id(seller.into())
+                .nonce(nonce)
+                .chain_id(chain_id)
+                .build()
+                .unwrap()
+        };
+
+        // replay protection disabled
+        assert_eq!(check_replay(&config, &tx(None, None)).await, Ok(()));
+
+        config.replay_protection = true;
+        assert_eq!(
+            check_replay(&config, &tx(None, None)).await,
+            Err(TxError::MissingReplayProtection)
+        );
+        assert_eq!(
+            check_replay(&config, &tx(Some(0), Some("forked".into()))).await,
+            Err(TxError::WrongChain)
+        );
+        assert_eq!(
+            check_replay(&config, &tx(Some(0), Some(chain_id.clone()))).await,
+            Ok(())
+        );
+
+        // transaction gets processed, seller's nonce changes
+        let prev = config.chain_addr.send(GetLastBlock).await.unwrap();
+        let block = BlockBuilder::default()
+            .set_tx(seller)
+            .set_rx("attacker.batsense.net")
+            .set_prev(&prev)
+            .set_asset_id("asset")
+            .set_validator("normal.batsense.net")
+            .set_nonce(Some(0))
+            .set_chain_id(Some(&chain_id))
+            .build();
+        assert_eq!(check_block_replay(&config, &block).await, Ok(()));
+        config
+            .chain_addr
+            .send(AddBlock(block.clone(), config.init_network_size))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            check_replay(&config, &tx(Some(0), Some(chain_id.clone()))).await,
+            Err(TxError::InvalidNonce),
+            "replayed transaction rejected"
+        );
+        assert_eq!(
+            check_block_replay(&config, &block).await,
+            Err(TxError::InvalidNonce),
+            "replayed block rejected"
+        );
+        assert_eq!(
+            check_replay(&config, &tx(Some(1), Some(chain_id))).await,
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn shares_works() {
+        use crate::block::BlockBuilder;
+        use crate::chain::Chain;
+
+        let mut assets = AssetLedger::generate("auditor").assets;
+        assets.truncate(4);
+        assets[0].set_owner("attacker");
+        assets[1].set_owner("victim");
+        assets[2].set_owner("victim");
+        assets[3].set_owner("victim");
+
+        let mut chain = Chain::new("test");
+        for validator in ["attacker", "attacker", "victim"].iter() {
+            let block = BlockBuilder::default()
+                .set_tx("victim")
+                .set_rx(validator)
+                .set_asset_id("asset")
+                .set_validator(validator)
+                .set_prev(chain.get_last_block())
+                .build();
+            chain.add_block(block, 3).unwrap();
+        }
+
+        let shares = shares(&chain.blocks(), &assets);
+        assert_eq!(shares.len(), 2);
+        let attacker = shares.iter().find(|s| s.peer_id == "attacker").unwrap();
+        assert_eq!(attacker.blocks, 2);
+        assert_eq!(attacker.stake, 1);
+        assert!((attacker.block_share - 2.0 / 3.0).abs() < f64::EPSILON);
+        assert!((attacker.stake_share - 0.25).abs() < f64::EPSILON);
+    }
+
     #[actix_rt::test]
     async fn get_next_block_id_works() {
         let config = init_network(Mode::Normal).await;
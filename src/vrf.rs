@@ -0,0 +1,346 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Verifiable random validator selection. Every peer evaluates a
+//! verifiable random function(VRF) over the previous block's hash and
+//! the serial number of the next block. The output can only be computed
+//! with the peer's secret key but anyone can check it with the peer's
+//! [PublicKey], so outputs can't be predicted before the previous block
+//! exists and can't be chosen by peers.
+//!
+//! The output is turned into a stake weighted [ticket]: a peer is
+//! eligible to create the block if its ticket is below a threshold and
+//! the lowest eligible ticket wins. Peers win with probability
+//! proportional to their stake. Peers only evaluate their own VRF, an
+//! eligible peer proves it won with the block it creates.
+//!
+//! The VRF is schnorrkel's VRF over Ristretto. Proofs also sign the
+//! contents of the block they are in, so a proof can't be moved to a
+//! block with other transactions.
+//!
+//! # [Lottery] actor supports the following messages:
+//! - [GetPublicKey]: Get this peer's public key
+//! - [Evaluate]: Get VRF output of this peer over a seed
+//! - [Prove]: Evaluate VRF over a seed and sign a message with the proof
+//! - [GetPeerKey]: Get public key of a peer, if known
+//! - [AddPeerKey]: Record public key of a peer, keys are never replaced
+
+use std::collections::HashMap;
+
+use actix::prelude::*;
+use data_encoding::HEXUPPER;
+use schnorrkel::vrf::{VRFInOut, VRFPreOut, VRFProof, VRF_PREOUT_LENGTH};
+use schnorrkel::{signing_context, Keypair as SrKeypair, PublicKey as SrPublicKey};
+use serde::{Deserialize, Serialize};
+
+/// expected number of eligible peers per block when stake is spread
+/// over many peers. Nobody is eligible with probability `e^-1`, the
+/// transaction isn't added then and has to be submitted again
+pub const EXPECTED_WINNERS: f64 = 1.0;
+
+/// domain separation of VRF inputs
+const SEED_CONTEXT: &[u8] = b"dwb-lottery-seed";
+/// domain separation of messages signed by proofs
+const MESSAGE_CONTEXT: &[u8] = b"dwb-lottery-block";
+/// domain separation of VRF outputs
+const OUTPUT_CONTEXT: &[u8] = b"dwb-lottery-output";
+
+/// Public key of a peer, hex encoded
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PublicKey(pub String);
+
+/// VRF key pair
+#[derive(Clone, Debug)]
+pub struct Keypair(SrKeypair);
+
+/// VRF output of an input and output pair, hex encoded
+fn encode_output(inout: &VRFInOut) -> String {
+    HEXUPPER.encode(&inout.make_bytes::<[u8; 32]>(OUTPUT_CONTEXT))
+}
+
+impl Keypair {
+    /// generates a random key pair
+    pub fn generate() -> Self {
+        Keypair(SrKeypair::generate())
+    }
+
+    /// public key
+    pub fn public(&self) -> PublicKey {
+        PublicKey(HEXUPPER.encode(&self.0.public.to_bytes()))
+    }
+
+    /// VRF output over `seed`, without a proof
+    pub fn evaluate(&self, seed: &str) -> String {
+        let input = signing_context(SEED_CONTEXT).bytes(seed.as_bytes());
+        encode_output(&self.0.vrf_create_hash(input))
+    }
+
+    /// evaluates VRF over `seed` and signs `message` with the proof.
+    /// Returns the proof, see [PublicKey::verify]
+    pub fn prove(&self, seed: &str, message: &str) -> String {
+        let input = signing_context(SEED_CONTEXT).bytes(seed.as_bytes());
+        let extra = signing_context(MESSAGE_CONTEXT).bytes(message.as_bytes());
+        let (inout, proof, _) = self.0.vrf_sign_extra(input, extra);
+        let mut bytes = inout.to_preout().to_bytes().to_vec();
+        bytes.extend_from_slice(&proof.to_bytes());
+        HEXUPPER.encode(&bytes)
+    }
+}
+
+impl PublicKey {
+    /// checks if `proof` is the VRF proof of the key's owner over `seed`
+    /// and signs `message`. Returns the VRF output
+    pub fn verify(&self, seed: &str, message: &str, proof: &str) -> Option<String> {
+        let key = HEXUPPER.decode(self.0.as_bytes()).ok()?;
+        let key = SrPublicKey::from_bytes(&key).ok()?;
+        let proof = HEXUPPER.decode(proof.as_bytes()).ok()?;
+        if proof.len() <= VRF_PREOUT_LENGTH {
+            return None;
+        }
+        let (preout, proof) = proof.split_at(VRF_PREOUT_LENGTH);
+        let preout = VRFPreOut::from_bytes(preout).ok()?;
+        let proof = VRFProof::from_bytes(proof).ok()?;
+        let input = signing_context(SEED_CONTEXT).bytes(seed.as_bytes());
+        let extra = signing_context(MESSAGE_CONTEXT).bytes(message.as_bytes());
+        let (inout, _) = key.vrf_verify_extra(input, &preout, &proof, extra).ok()?;
+        Some(encode_output(&inout))
+    }
+}
+
+/// seed of the lottery for block `block_id` built on block `prev`
+pub fn seed(prev: &str, block_id: usize) -> String {
+    format!("{}:{}", prev, block_id)
+}
+
+/// stake weighted ticket of VRF `output` for a peer that owns `stake`
+/// out of `total`, lower tickets are better. Tickets are exponentially
+/// distributed with rate `stake`, so the lowest ticket belongs to a
+/// peer with probability proportional to its stake. Returns `None`
+/// when the peer isn't eligible
+pub fn ticket(output: &str, stake: usize, total: usize) -> Option<f64> {
+    if stake == 0 || total == 0 {
+        return None;
+    }
+    let draw = u64::from_str_radix(output.get(..16)?, 16).ok()?;
+    // uniform in (0, 1]
+    let uniform = (draw as f64 + 1.0) / 2f64.powi(64);
+    let ticket = -uniform.ln() / stake as f64;
+    if ticket * total as f64 <= EXPECTED_WINNERS {
+        Some(ticket)
+    } else {
+        None
+    }
+}
+
+/// VRF key of this peer and public keys of peers
+#[derive(Clone, Debug)]
+pub struct Lottery {
+    keypair: Keypair,
+    keys: HashMap<String, PublicKey>,
+}
+
+impl Default for Lottery {
+    fn default() -> Self {
+        Lottery {
+            keypair: Keypair::generate(),
+            keys: HashMap::default(),
+        }
+    }
+}
+
+impl Actor for Lottery {
+    type Context = Context<Self>;
+}
+
+/// Get this peer's public key
+#[derive(Message)]
+#[rtype(result = "PublicKey")]
+pub struct GetPublicKey;
+
+/// Get VRF output of this peer over a seed, see [Keypair::evaluate]
+#[derive(Message)]
+#[rtype(result = "String")]
+pub struct Evaluate(pub String);
+
+/// Evaluate VRF over `seed` and sign `message` with the proof, returns
+/// proof
+#[derive(Message)]
+#[rtype(result = "String")]
+pub struct Prove {
+    pub seed: String,
+    pub message: String,
+}
+
+/// Get public key of a peer, if known
+#[derive(Message)]
+#[rtype(result = "Option<PublicKey>")]
+pub struct GetPeerKey(pub String);
+
+/// Record public key of a peer. Keys are recorded the first time they
+/// are seen and never replaced, so peers can't switch keys to win.
+/// Returns the recorded key
+#[derive(Message)]
+#[rtype(result = "PublicKey")]
+pub struct AddPeerKey {
+    pub peer_id: String,
+    pub key: PublicKey,
+}
+
+impl Handler<GetPublicKey> for Lottery {
+    type Result = MessageResult<GetPublicKey>;
+
+    fn handle(&mut self, _msg: GetPublicKey, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.keypair.public())
+    }
+}
+
+impl Handler<Evaluate> for Lottery {
+    type Result = MessageResult<Evaluate>;
+
+    fn handle(&mut self, msg: Evaluate, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.keypair.evaluate(&msg.0))
+    }
+}
+
+impl Handler<Prove> for Lottery {
+    type Result = MessageResult<Prove>;
+
+    fn handle(&mut self, msg: Prove, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.keypair.prove(&msg.seed, &msg.message))
+    }
+}
+
+impl Handler<GetPeerKey> for Lottery {
+    type Result = MessageResult<GetPeerKey>;
+
+    fn handle(&mut self, msg: GetPeerKey, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.keys.get(&msg.0).cloned())
+    }
+}
+
+impl Handler<AddPeerKey> for Lottery {
+    type Result = MessageResult<AddPeerKey>;
+
+    fn handle(&mut self, msg: AddPeerKey, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.keys.entry(msg.peer_id).or_insert(msg.key).clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vrf_works() {
+        let keypair = Keypair::generate();
+        let key = keypair.public();
+        let seed = seed("prev", 5);
+        let proof = keypair.prove(&seed, "block");
+        let output = key.verify(&seed, "block", &proof).unwrap();
+        assert_eq!(output, keypair.evaluate(&seed));
+        assert_eq!(
+            key.verify(&seed, "block", &keypair.prove(&seed, "block")),
+            Some(output.clone()),
+            "outputs are deterministic"
+        );
+        assert_eq!(
+            key.verify(&seed, "other", &keypair.prove(&seed, "other")),
+            Some(output),
+            "outputs don't depend on messages"
+        );
+        assert_eq!(
+            key.verify("prev:6", "block", &proof),
+            None,
+            "proofs are bound to seeds"
+        );
+        assert_eq!(
+            key.verify(&seed, "other", &proof),
+            None,
+            "proofs sign messages"
+        );
+        assert_eq!(
+            Keypair::generate().public().verify(&seed, "block", &proof),
+            None,
+            "proofs are bound to keys"
+        );
+        assert_eq!(key.verify(&seed, "block", "not a proof"), None);
+        assert_eq!(key.verify(&seed, "block", &proof[..64]), None);
+        assert_eq!(PublicKey("00".into()).verify(&seed, "block", &proof), None);
+    }
+
+    #[test]
+    fn ticket_works() {
+        let low = format!("{:064X}", 0);
+        let high = "F".repeat(64);
+        assert_eq!(ticket(&low, 0, 10), None, "peers without stake can't win");
+        assert!(ticket(&high, 1, 10).is_some(), "highest draws win");
+        assert_eq!(ticket(&low, 1, 10), None, "lowest draws lose");
+        assert!(ticket(&low, 10, 10).is_none());
+
+        // peers win with probability proportional to stake, and nobody
+        // wins with probability e^-1
+        let keypair = Keypair::generate();
+        let mut wins = [0; 3];
+        for block_id in 0..2000 {
+            let draws: Vec<Option<f64>> = [(1, "a"), (3, "b")]
+                .iter()
+                .map(|(stake, peer)| ticket(&keypair.evaluate(&seed(peer, block_id)), *stake, 4))
+                .collect();
+            match (draws[0], draws[1]) {
+                (Some(a), Some(b)) if a < b => wins[0] += 1,
+                (Some(_), None) => wins[0] += 1,
+                (_, Some(_)) => wins[1] += 1,
+                (None, None) => wins[2] += 1,
+            }
+        }
+        assert!(wins[2] > 640 && wins[2] < 840, "wins: {:?}", wins);
+        assert!(wins[0] > 240 && wins[0] < 400, "wins: {:?}", wins);
+        assert!(wins[1] > 840 && wins[1] < 1100, "wins: {:?}", wins);
+    }
+
+    #[actix_rt::test]
+    async fn lottery_works() {
+        let lottery = Lottery::default().start();
+        let key = lottery.send(GetPublicKey).await.unwrap();
+        let prove = Prove {
+            seed: "seed".into(),
+            message: "block".into(),
+        };
+        let proof = lottery.send(prove).await.unwrap();
+        assert_eq!(
+            key.verify("seed", "block", &proof),
+            Some(lottery.send(Evaluate("seed".into())).await.unwrap())
+        );
+
+        assert_eq!(lottery.send(GetPeerKey("a".into())).await.unwrap(), None);
+        let add = |key: PublicKey| AddPeerKey {
+            peer_id: "a".into(),
+            key,
+        };
+        assert_eq!(lottery.send(add(key.clone())).await.unwrap(), key);
+        let other = Keypair::generate().public();
+        assert_eq!(
+            lottery.send(add(other)).await.unwrap(),
+            key,
+            "keys aren't replaced"
+        );
+        assert_eq!(
+            lottery.send(GetPeerKey("a".into())).await.unwrap(),
+            Some(key)
+        );
+    }
+}
@@ -7,178 +7,162 @@
 * License, or (at your option) any later version.
 *
 * This program is distributed in the hope that it will be useful,
-* but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
 * GNU Affero General Public License for more details.
 *
 * You should have received a copy of the GNU Affero General Public License
 * along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
-//! Client wrapper for p2p communication
+//! HTTP client for operating peers of a running network
 
 use actix_web::client::Client as awc;
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
-use damn_vuln_blockchain::asset::{Asset, ReplaceLedger, Stake};
+use damn_vuln_blockchain::asset::Asset;
 use damn_vuln_blockchain::block::Block;
-use damn_vuln_blockchain::client::*;
-use damn_vuln_blockchain::config::Config;
-use damn_vuln_blockchain::discovery::AddPeer;
-use damn_vuln_blockchain::payload::{Peer, Status, Tx, ValidateTx};
-use damn_vuln_blockchain::utils::*;
-
-use log::debug;
-//use crate::logs::SellAsset;
-
-// NOTE these URLs are subject to change
-// if tests are failing, come check the URLs
-// here
-/// Client wrapper for p2p communication
-#[derive(Clone, Default)]
+use damn_vuln_blockchain::client::{
+    GET_ALL_ASSETS, GET_CHAIN, PEER_DISCOVER_ALL, SELL_ASSET, SET_ATTACK, STATE,
+};
+use damn_vuln_blockchain::payload::{Peer, Status, Tx};
+
+/// worldview payloads grow with the chain
+const PAYLOAD_LIMIT: usize = 10 * 1024 * 1024;
+
+/// fork route of attacking peers
+const FORK: &str = "/fork";
+/// worldview route of peers and the worldview server
+const WORLDVIEW: &str = "/worldview";
+
+/// Client for peers in a network, peers are looked up with the auditor
+#[derive(Clone)]
 pub struct Client {
     pub client: awc,
-}
-
-/// Get stake using client
-#[derive(Clone, Deserialize, Serialize)]
-pub struct GetStake {
-    pub block_id: usize,
-    pub peer_id: String,
+    /// address of auditor node
+    pub auditor: String,
 }
 
 impl Client {
-    /// set attack
-    pub async fn set_attack(&self, peer: Peer) {
-        let addr = Client::make_uri(&peer.ip, SET_ATTACK);
-        self.client.post(addr).send().await.unwrap();
+    pub fn new(auditor: &str) -> Self {
+        Client {
+            client: awc::default(),
+            auditor: Client::make_uri(auditor),
+        }
     }
 
-    /// get stake for a block
-    pub async fn get_stake(&self, peer: GetStake, config: &Config) -> Stake {
-        use damn_vuln_blockchain::payload::GetStake as PayloadGetStake;
-
-        let payload = PayloadGetStake {
-            block_id: peer.block_id,
-        };
-
-        let peer_addr = get_peer(&config, &peer.peer_id).await;
-        let addr = Client::make_uri(&peer_addr.ip, GET_STAKE);
-        loop {
-            if let Ok(mut val) = self
-                .client
-                .post(&addr)
-                .header("content-type", "application/json")
-                .send_json(&payload)
-                .await
-            {
-                if let Ok(stake) = val.json().await {
-                    return stake;
-                }
-            }
+    /// accepts `localhost:7000` and `http://localhost:7000`
+    fn make_uri(address: &str) -> String {
+        let address = address.trim_end_matches('/');
+        if address.starts_with("http://") || address.starts_with("https://") {
+            address.into()
+        } else {
+            format!("http://{}", address)
         }
     }
 
-    fn make_uri(address: &str, path: &str) -> String {
-        format!("http://{}{}", address, path)
+    async fn get<T: DeserializeOwned>(&self, addr: &str, path: &str) -> Result<T, String> {
+        let url = format!("{}{}", addr, path);
+        let mut resp = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("couldn't reach {}: {}", url, e))?;
+        if !resp.status().is_success() {
+            return Err(format!("{} responded with {}", url, resp.status()));
+        }
+        resp.json()
+            .limit(PAYLOAD_LIMIT)
+            .await
+            .map_err(|e| format!("invalid response from {}: {}", url, e))
     }
 
-    /// gets list of peers from auditor, should be called periodically
-    pub async fn peer_discovery(&self, config: &Config) {
-        // gets peers from Auditor and replaces peers
-        // in local Network
-        let addr = Client::make_uri(&config.auditor_node, PEER_DISCOVER_ALL);
-        loop {
-            if let Ok(mut val) = self.client.get(&addr).send().await {
-                config.debug("Peer discovery request success");
-                let peers: Result<Vec<Peer>, _> = val.json().await;
-                if let Ok(val) = peers {
-                    println!("{:#?}", val);
-                    break;
-                }
-            }
+    async fn post<T: Serialize>(&self, addr: &str, path: &str, payload: &T) -> Result<(), String> {
+        let url = format!("{}{}", addr, path);
+        let mut resp = self
+            .client
+            .post(&url)
+            .send_json(payload)
+            .await
+            .map_err(|e| format!("couldn't reach {}: {}", url, e))?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            let body = resp.body().await.unwrap_or_default();
+            Err(format!(
+                "{} responded with {}: {}",
+                url,
+                resp.status(),
+                String::from_utf8_lossy(&body)
+            ))
         }
     }
 
-    /// send Tx request to validator
-    pub async fn sell_asset(&self, config: &Config, seller_id: &str, payload: &Tx) {
-        let seller = get_peer(&config, seller_id).await;
-        let addr = Client::make_uri(&seller.ip, SELL_ASSET);
-
-        loop {
-            if let Ok(_) = self
-                .client
-                .post(&addr)
-                .header("content-type", "application/json")
-                .send_json(&payload)
-                .await
-            {
-                break;
-            }
-        }
+    /// peers enrolled with the auditor
+    pub async fn peers(&self) -> Result<Vec<Peer>, String> {
+        self.get(&self.auditor, PEER_DISCOVER_ALL).await
     }
 
-    /// Get chain dump
-    pub async fn get_chain(&self, peer: Peer) -> Vec<Block> {
-        let addr = Client::make_uri(&peer.ip, GET_CHAIN);
-        loop {
-            if let Ok(mut val) = self.client.get(&addr).send().await {
-                let blocks: Result<Vec<Block>, _> = val.json().await;
-                if let Ok(blocks) = blocks {
-                    println!("{:#?}", blocks);
-                }
-            }
+    /// address of `peer`, which is either a peer ID or an address
+    pub async fn resolve(&self, peer: &str) -> Result<String, String> {
+        if peer.contains(':') {
+            return Ok(Client::make_uri(peer));
         }
+        self.peers()
+            .await?
+            .into_iter()
+            .find(|enrolled| enrolled.id == peer)
+            .map(|enrolled| Client::make_uri(&enrolled.ip))
+            .ok_or_else(|| format!("peer {} isn't enrolled with the auditor", peer))
     }
 
-    /// gets list of peers from auditor, should be called periodically
-    pub async fn peer_dump(&self, peer: &Peer) -> Vec<Peer> {
-        // gets peers from Auditor and replaces peers
-        // in local Network
-        let addr = Client::make_uri(&peer.ip, PEER_DISCOVER_ALL);
-        loop {
-            if let Ok(mut val) = self.client.get(&addr).send().await {
-                //                config.debug("Peer discovery request success");
-                let peers: Result<Vec<Peer>, _> = val.json().await;
-                if let Ok(val) = peers {
-                    println!("{:#?}", val);
-                }
-            }
-        }
+    /// asset ledger of peer at `addr`
+    pub async fn assets(&self, addr: &str) -> Result<Vec<Asset>, String> {
+        self.get(addr, GET_ALL_ASSETS).await
     }
 
-    /// gets asset ledger from auditor node, should be called periodically
-    pub async fn get_peer_assets(&self, peer: &Peer) {
-        // gets assets from Auditor and replaces assets
-        // in local AssetsLedger
-        let addr = Client::make_uri(&peer.ip, GET_ALL_ASSETS);
-        loop {
-            if let Ok(mut val) = self.client.get(&addr).send().await {
-                let peers: Result<Vec<Asset>, _> = val.json().await;
-                if let Ok(val) = peers {
-                    println!("{:#?}", val);
-                    break;
-                }
-            }
-        }
+    /// chain of peer at `addr`
+    pub async fn chain(&self, addr: &str) -> Result<Vec<Block>, String> {
+        self.get(addr, GET_CHAIN).await
+    }
+
+    /// state of peer at `addr`
+    pub async fn state(&self, addr: &str) -> Result<Status, String> {
+        self.get(addr, STATE).await
     }
 
-    /// gets list of peers from auditor, should be called periodically
-    pub async fn discovery(&self, target_peer: &str, auditor_ip: &str) -> Option<Peer> {
-        let addr = Client::make_uri(&auditor_ip, PEER_DISCOVER_ALL);
-        loop {
-            if let Ok(mut val) = self.client.get(&addr).send().await {
-                //                config.debug("Peer discovery request success");
-                let peers: Result<Vec<Peer>, _> = val.json().await;
-                if let Ok(val) = peers {
-                    // NOTE: need to return target peer's Peer config
-                    debug!("{:#?}", &val);
-                    for peer in val {
-                        if peer.id == target_peer {
-                            return Some(peer);
-                        }
-                    }
-                    return None;
-                }
+    /// state of every peer, from a peer or the worldview server at `addr`
+    pub async fn worldview(&self, addr: &str) -> Result<Vec<Status>, String> {
+        self.get(&Client::make_uri(addr), WORLDVIEW).await
+    }
+
+    /// asks peer at `addr` to sell an asset that it owns
+    pub async fn sell(&self, addr: &str, tx: &Tx) -> Result<(), String> {
+        let seller = self.state(addr).await?;
+        match seller
+            .asset
+            .iter()
+            .find(|asset| asset.get_hash() == tx.asset_id)
+        {
+            Some(asset) if asset.get_owner().as_ref() == Some(&seller.peer_id) => {
+                self.post(addr, SELL_ASSET, tx).await
             }
+            Some(_) => Err(format!(
+                "peer {} doesn't own asset {}",
+                seller.peer_id, tx.asset_id
+            )),
+            None => Err(format!("asset {} doesn't exist", tx.asset_id)),
         }
     }
+
+    /// toggles attack mode of peer at `addr`
+    pub async fn toggle_attack(&self, addr: &str) -> Result<(), String> {
+        self.post(addr, SET_ATTACK, &()).await
+    }
+
+    /// forks chain of attacking peer at `addr`
+    pub async fn fork(&self, addr: &str) -> Result<(), String> {
+        self.post(addr, FORK, &()).await
+    }
 }
@@ -0,0 +1,42 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Diagrams of chains and asset transfers from a real run
+
+use std::fs;
+
+use damn_vuln_blockchain::graph::{render, Format};
+
+use crate::client::Client;
+
+/// renders worldview at `url`(a peer or the worldview server)
+/// and writes it to `output` or stdout
+pub async fn export_graph(
+    client: &Client,
+    url: &str,
+    format: Format,
+    output: Option<&str>,
+) -> Result<(), String> {
+    let states = client.worldview(url).await?;
+    let graph = render(&states, format);
+    match output {
+        Some(path) => fs::write(path, graph).map_err(|e| format!("couldn't write {}: {}", path, e)),
+        None => {
+            print!("{}", graph);
+            Ok(())
+        }
+    }
+}
@@ -1,74 +1,181 @@
-use std::sync::Arc;
-use std::sync::RwLock;
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! `wv`: worldview server and operator tool for a damn-vuln-blockchain network
 
-use actix_web::{
-    error::InternalError, get, http::StatusCode, post, web::Data, web::Json, web::JsonConfig,
-    web::ServiceConfig, HttpResponse, Responder,
-};
+use clap::{App, AppSettings, Arg, SubCommand};
 
-use damn_vuln_blockchain::payload::Status;
+mod client;
+mod export;
+mod operator;
+mod output;
+mod server;
+
+/// `--peer` argument of operator commands
+fn peer_arg() -> Arg<'static, 'static> {
+    Arg::with_name("peer")
+        .help("peer ID or address, defaults to the auditor")
+        .short("-p")
+        .long("--peer")
+        .takes_value(true)
+}
 
 #[actix_web::main]
 #[cfg(not(tarpaulin_include))]
 async fn main() -> std::io::Result<()> {
-    use actix_web::{
-        middleware::{normalize, Compress, Logger, NormalizePath},
-        App, HttpServer,
-    };
-
     pretty_env_logger::init();
 
-    let data: StakeData = StakeData::default();
-
-    let ip = format!("0.0.0.0:{}", std::env::var("PORT").unwrap());
-    let server_fut = HttpServer::new(move || {
-        App::new()
-            .app_data(get_json_err())
-            .data(data.clone())
-            .wrap(Logger::default())
-            .wrap(Compress::default())
-            .wrap(NormalizePath::new(normalize::TrailingSlash::Trim))
-            .configure(services)
-    })
-    .bind(ip)
-    .unwrap()
-    .run();
-
-    server_fut.await
-}
-
-type StakeData = Arc<RwLock<Vec<Status>>>;
-
-#[cfg(not(tarpaulin_include))]
-fn get_json_err() -> JsonConfig {
-    JsonConfig::default().error_handler(|err, _| {
-        //debug!("JSON deserialization error: {:?}", &err);
-        InternalError::new(err, StatusCode::BAD_REQUEST).into()
-    })
-}
-
-#[get("/")]
-async fn auditor() -> impl Responder {
-    const INDEX: &str = include_str!("../../frontend/index.html");
-    HttpResponse::Ok().content_type("text/html").body(INDEX)
-}
-
-#[get("/worldview")]
-async fn worldview(d: Data<StakeData>) -> impl Responder {
-    let state = d.read().unwrap();
-    HttpResponse::Ok().json(&*state)
-}
-
-#[post("/worldview")]
-async fn update(d: Data<StakeData>, payload: Json<Vec<Status>>) -> impl Responder {
-    let mut state = d.write().unwrap();
-    *state = payload.into_inner();
-    drop(state);
-    HttpResponse::Ok()
-}
+    let matches = App::new("wv")
+        .version("0.1")
+        .author("Aravinth Manivannan <realaravinth@batsense.net>")
+        .about("Worldview server and operator tool for Damn Vulnerable Blockchain")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("auditor")
+                .help("address of auditor node, used to look up peers")
+                .short("-a")
+                .long("--auditor")
+                .default_value("localhost:7000")
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .help("print JSON instead of tables")
+                .long("--json")
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("run worldview server on $PORT, default when no command is given"),
+        )
+        .subcommand(SubCommand::with_name("peers").about("list peers in the network"))
+        .subcommand(
+            SubCommand::with_name("assets")
+                .about("show assets in a peer's ledger")
+                .arg(peer_arg())
+                .arg(
+                    Arg::with_name("owner")
+                        .help("only show assets owned by this peer")
+                        .short("-o")
+                        .long("--owner")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sell")
+                .about("sell an asset owned by a peer")
+                .arg(peer_arg().help("seller peer ID or address").required(true))
+                .arg(
+                    Arg::with_name("asset")
+                        .help("asset ID")
+                        .long("--asset")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("buyer")
+                        .help("buyer peer ID")
+                        .short("-b")
+                        .long("--buyer")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fork")
+                .about("fork the chain of an attacker")
+                .arg(
+                    peer_arg()
+                        .help("attacker peer ID or address")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("attack")
+                .about("toggle attack mode of an attacker or a victim")
+                .arg(peer_arg().help("peer ID or address").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("chain")
+                .about("dump a peer's chain")
+                .arg(peer_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("show state of the network and print changes as they happen")
+                .arg(
+                    Arg::with_name("url")
+                        .help("peer or worldview server to watch, defaults to the auditor")
+                        .short("-u")
+                        .long("--url")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .help("seconds between updates")
+                        .short("-i")
+                        .long("--interval")
+                        .default_value("2")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export-graph")
+                .about("render chains, forks and asset transfers as a diagram")
+                .arg(
+                    Arg::with_name("url")
+                        .help("address of a peer or the worldview server")
+                        .short("-u")
+                        .long("--url")
+                        .default_value("http://localhost:3000")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("diagram format")
+                        .short("-f")
+                        .long("--format")
+                        .possible_values(&["dot", "mermaid"])
+                        .default_value("dot")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("file to write the diagram to, prints to stdout by default")
+                        .short("-o")
+                        .long("--output")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches();
 
-pub fn services(cfg: &mut ServiceConfig) {
-    cfg.service(worldview);
-    cfg.service(update);
-    cfg.service(auditor);
+    match matches.subcommand() {
+        ("serve", _) | ("", None) => server::serve().await,
+        (name, Some(sub_matches)) => {
+            let output = output::Output {
+                json: sub_matches.is_present("json"),
+            };
+            let client = client::Client::new(sub_matches.value_of("auditor").unwrap());
+            if let Err(e) = operator::run(&client, output, name, sub_matches).await {
+                output.error(&e);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        _ => unreachable!(),
+    }
 }
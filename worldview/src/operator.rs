@@ -0,0 +1,113 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Operator commands: inspect and drive peers of a running network
+
+use std::time::Duration;
+
+use actix_web::rt::time::delay_for;
+use clap::ArgMatches;
+
+use damn_vuln_blockchain::block::TxKind;
+use damn_vuln_blockchain::graph::Format;
+use damn_vuln_blockchain::logs::changes;
+use damn_vuln_blockchain::payload::Tx;
+
+use crate::client::Client;
+use crate::export::export_graph;
+use crate::output::Output;
+
+/// runs operator command `name`
+pub async fn run(
+    client: &Client,
+    output: Output,
+    name: &str,
+    matches: &ArgMatches<'_>,
+) -> Result<(), String> {
+    // commands that default to the auditor when no peer is given
+    let peer = matches.value_of("peer").unwrap_or(&client.auditor);
+
+    match name {
+        "peers" => output.peers(&client.peers().await?),
+        "assets" => {
+            let mut assets = client.assets(&client.resolve(peer).await?).await?;
+            if let Some(owner) = matches.value_of("owner") {
+                assets.retain(|asset| asset.get_owner().as_deref() == Some(owner));
+            }
+            output.assets(&assets);
+        }
+        "chain" => output.chain(&client.chain(&client.resolve(peer).await?).await?),
+        "sell" => {
+            let tx = Tx {
+                asset_id: matches.value_of("asset").unwrap().into(),
+                buyer_peer_id: matches.value_of("buyer").unwrap().into(),
+                kind: TxKind::default(),
+                input: None,
+            };
+            client.sell(&client.resolve(peer).await?, &tx).await?;
+            output.done(&format!(
+                "Asset {} is being sold to {}",
+                tx.asset_id, tx.buyer_peer_id
+            ));
+        }
+        "fork" => {
+            client.fork(&client.resolve(peer).await?).await?;
+            output.done(&format!("Forked chain of {}", peer));
+        }
+        "attack" => {
+            client.toggle_attack(&client.resolve(peer).await?).await?;
+            output.done(&format!("Toggled attack mode of {}", peer));
+        }
+        "watch" => {
+            let url = matches.value_of("url").unwrap_or(&client.auditor);
+            let interval: u64 = matches
+                .value_of("interval")
+                .unwrap()
+                .parse()
+                .map_err(|_| "interval should be a number of seconds".to_string())?;
+            watch(client, output, url, interval).await?;
+        }
+        "export-graph" => {
+            let format: Format = matches.value_of("format").unwrap().parse()?;
+            export_graph(
+                client,
+                matches.value_of("url").unwrap(),
+                format,
+                matches.value_of("output"),
+            )
+            .await?;
+        }
+        _ => return Err(format!("unknown command {}", name)),
+    }
+    Ok(())
+}
+
+/// prints worldview at `url` and then changes to it every `interval` seconds
+async fn watch(client: &Client, output: Output, url: &str, interval: u64) -> Result<(), String> {
+    let mut states = client.worldview(url).await?;
+    output.worldview(&states);
+    loop {
+        delay_for(Duration::from_secs(interval)).await;
+        match client.worldview(url).await {
+            Ok(new_states) => {
+                output.actions(&changes(&states, &new_states));
+                states = new_states;
+            }
+            // peers might be restarting, keep watching
+            Err(e) => output.error(&e),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/*
+* Copyright (C) 2020  Aravinth Manivannan <realaravinth@batsense.net>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU Affero General Public License as
+* published by the Free Software Foundation, either version 3 of the
+* License, or (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU Affero General Public License for more details.
+*
+* You should have received a copy of the GNU Affero General Public License
+* along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Output of operator commands, as aligned tables or JSON
+
+use serde::Serialize;
+
+use damn_vuln_blockchain::asset::Asset;
+use damn_vuln_blockchain::block::Block;
+use damn_vuln_blockchain::logs::Action;
+use damn_vuln_blockchain::payload::{Peer, Status};
+
+/// Prints command output
+#[derive(Clone, Copy)]
+pub struct Output {
+    /// print JSON instead of tables
+    pub json: bool,
+}
+
+/// formats rows into columns that are as wide as their widest cell
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    let mut out = format_row(headers.to_vec());
+    for row in rows.iter() {
+        out.push('\n');
+        out.push_str(&format_row(row.iter().map(|cell| cell.as_str()).collect()));
+    }
+    out
+}
+
+fn or_dash(value: Option<&String>) -> String {
+    value.cloned().unwrap_or_else(|| "-".into())
+}
+
+impl Output {
+    fn json<T: Serialize + ?Sized>(&self, value: &T) {
+        println!("{}", serde_json::to_string_pretty(value).unwrap());
+    }
+
+    pub fn peers(&self, peers: &[Peer]) {
+        if self.json {
+            return self.json(peers);
+        }
+        let rows = peers
+            .iter()
+            .map(|peer| vec![peer.id.clone(), peer.ip.clone()])
+            .collect();
+        println!("{}", table(&["ID", "ADDRESS"], rows));
+    }
+
+    pub fn assets(&self, assets: &[Asset]) {
+        if self.json {
+            return self.json(assets);
+        }
+        let rows = assets
+            .iter()
+            .map(|asset| {
+                vec![
+                    asset.get_hash().into(),
+                    asset.get_name().into(),
+                    asset.get_value().to_string(),
+                    or_dash(asset.get_owner().as_ref()),
+                    asset.get_last_transaction().to_string(),
+                ]
+            })
+            .collect();
+        println!(
+            "{}",
+            table(&["ASSET", "NAME", "VALUE", "OWNER", "LAST TX"], rows)
+        );
+    }
+
+    pub fn chain(&self, chain: &[Block]) {
+        if self.json {
+            return self.json(chain);
+        }
+        let rows = chain
+            .iter()
+            .map(|block| {
+                vec![
+                    block
+                        .get_serial_no()
+                        .map(|serial_no| serial_no.to_string())
+                        .unwrap_or_else(|| "-".into()),
+                    block.get_hash().into(),
+                    or_dash(block.get_tx()),
+                    or_dash(block.get_rx()),
+                    or_dash(block.get_asset_id()),
+                    or_dash(block.get_validator()),
+                ]
+            })
+            .collect();
+        println!(
+            "{}",
+            table(&["SERIAL", "HASH", "TX", "RX", "ASSET", "VALIDATOR"], rows)
+        );
+    }
+
+    /// summary of every peer's ledgers
+    pub fn worldview(&self, states: &[Status]) {
+        if self.json {
+            return self.json(states);
+        }
+        let rows = states
+            .iter()
+            .map(|state| {
+                vec![
+                    state.peer_id.clone(),
+                    state.chain.len().to_string(),
+                    state
+                        .chain
+                        .last()
+                        .map(|block| block.get_hash().to_owned())
+                        .unwrap_or_else(|| "-".into()),
+                    state
+                        .tampered_chain
+                        .as_ref()
+                        .map(|chain| chain.len().to_string())
+                        .unwrap_or_else(|| "-".into()),
+                ]
+            })
+            .collect();
+        println!(
+            "{}",
+            table(&["PEER", "BLOCKS", "TIP", "TAMPERED BLOCKS"], rows)
+        );
+    }
+
+    /// changes observed while watching, one per line
+    pub fn actions(&self, actions: &[Action]) {
+        for action in actions.iter() {
+            if self.json {
+                println!("{}", serde_json::to_string(action).unwrap());
+            } else {
+                println!("{}", action);
+            }
+        }
+    }
+
+    /// outcome of commands that don't return data
+    pub fn done(&self, message: &str) {
+        if self.json {
+            println!("{}", serde_json::json!({ "ok": true, "message": message }));
+        } else {
+            println!("{}", message);
+        }
+    }
+
+    pub fn error(&self, error: &str) {
+        if self.json {
+            eprintln!("{}", serde_json::json!({ "ok": false, "error": error }));
+        } else {
+            eprintln!("error: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_works() {
+        let rows = vec![
+            vec!["attacker.batsense.net".into(), "localhost:7001".into()],
+            vec!["victim".into(), "localhost:7002".into()],
+        ];
+        assert_eq!(
+            table(&["ID", "ADDRESS"], rows),
+            "ID                     ADDRESS\n\
+             attacker.batsense.net  localhost:7001\n\
+             victim                 localhost:7002"
+        );
+    }
+}
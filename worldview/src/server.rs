@@ -0,0 +1,168 @@
+//! Worldview server: collects uploads from peers and serves the web UI
+
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use actix::prelude::*;
+use actix_web::{
+    error::InternalError, get, http::StatusCode, post, web::Data, web::Json, web::JsonConfig,
+    web::Path, web::Query, web::ServiceConfig, HttpResponse, Responder,
+};
+use serde::Deserialize;
+
+use damn_vuln_blockchain::diff::diff_worldview;
+use damn_vuln_blockchain::logs::{changes, stream_events, EventFilter, EventLog, LogEvent};
+use damn_vuln_blockchain::payload::Status;
+use damn_vuln_blockchain::timeline::Timeline;
+
+/// runs worldview server, listens on `$PORT`
+#[cfg(not(tarpaulin_include))]
+pub async fn serve() -> std::io::Result<()> {
+    use actix_web::{
+        middleware::{normalize, Compress, Logger, NormalizePath},
+        App, HttpServer,
+    };
+
+    let data: StakeData = StakeData::default();
+    let event_addr = EventLog::default().start();
+
+    let ip = format!("0.0.0.0:{}", std::env::var("PORT").unwrap());
+    let server_fut = HttpServer::new(move || {
+        App::new()
+            .app_data(get_json_err())
+            .data(data.clone())
+            .data(event_addr.clone())
+            .wrap(Logger::default())
+            .wrap(Compress::default())
+            .wrap(NormalizePath::new(normalize::TrailingSlash::Trim))
+            .configure(services)
+    })
+    .bind(ip)
+    .unwrap()
+    .run();
+
+    server_fut.await
+}
+
+type StakeData = Arc<RwLock<Timeline>>;
+
+#[cfg(not(tarpaulin_include))]
+fn get_json_err() -> JsonConfig {
+    JsonConfig::default().error_handler(|err, _| {
+        //debug!("JSON deserialization error: {:?}", &err);
+        InternalError::new(err, StatusCode::BAD_REQUEST).into()
+    })
+}
+
+#[get("/")]
+async fn auditor() -> impl Responder {
+    const INDEX: &str = include_str!("../../frontend/index.html");
+    HttpResponse::Ok().content_type("text/html").body(INDEX)
+}
+
+#[get("/worldview")]
+async fn worldview(d: Data<StakeData>) -> impl Responder {
+    let timeline = d.read().unwrap();
+    match timeline.latest() {
+        Some(snapshot) => HttpResponse::Ok().json(&snapshot.states),
+        None => HttpResponse::Ok().json(Vec::<Status>::new()),
+    }
+}
+
+#[post("/worldview")]
+async fn update(
+    d: Data<StakeData>,
+    event_addr: Data<Addr<EventLog>>,
+    payload: Json<Vec<Status>>,
+) -> impl Responder {
+    let actions = {
+        let mut timeline = d.write().unwrap();
+        let new_state = payload.into_inner();
+        let actions = match timeline.latest() {
+            Some(snapshot) => changes(&snapshot.states, &new_state),
+            None => Vec::new(),
+        };
+        timeline.push(new_state);
+        actions
+    };
+
+    for action in actions.into_iter() {
+        event_addr.send(LogEvent(action)).await.unwrap();
+    }
+    HttpResponse::Ok()
+}
+
+#[get("/worldview/diff")]
+async fn worldview_diff(d: Data<StakeData>) -> impl Responder {
+    let timeline = d.read().unwrap();
+    let states = timeline
+        .latest()
+        .map(|snapshot| snapshot.states.as_slice())
+        .unwrap_or(&[]);
+    HttpResponse::Ok().json(diff_worldview(states))
+}
+
+#[get("/worldview/history")]
+async fn history(d: Data<StakeData>) -> impl Responder {
+    let timeline = d.read().unwrap();
+    HttpResponse::Ok().json(timeline.list())
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: usize,
+    to: usize,
+}
+
+#[get("/worldview/history/diff")]
+async fn history_diff(d: Data<StakeData>, query: Query<DiffQuery>) -> impl Responder {
+    let timeline = d.read().unwrap();
+    match timeline.diff(query.from, query.to) {
+        Some(diff) => HttpResponse::Ok().json(diff),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[get("/worldview/history/{id}")]
+async fn history_snapshot(d: Data<StakeData>, id: Path<usize>) -> impl Responder {
+    let timeline = d.read().unwrap();
+    match timeline.get(id.into_inner()) {
+        Some(snapshot) => HttpResponse::Ok().json(snapshot),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// `time` is in milliseconds since UNIX epoch
+#[derive(Deserialize)]
+struct AtQuery {
+    time: i64,
+}
+
+#[get("/worldview/at")]
+async fn history_at(d: Data<StakeData>, query: Query<AtQuery>) -> impl Responder {
+    let timeline = d.read().unwrap();
+    match timeline.at(query.time) {
+        Some(snapshot) => HttpResponse::Ok().json(snapshot),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[get("/events/stream")]
+async fn events_stream(
+    event_addr: Data<Addr<EventLog>>,
+    query: Query<EventFilter>,
+) -> impl Responder {
+    stream_events(&event_addr, query.into_inner()).await
+}
+
+pub fn services(cfg: &mut ServiceConfig) {
+    cfg.service(worldview);
+    cfg.service(update);
+    cfg.service(worldview_diff);
+    cfg.service(history);
+    cfg.service(history_diff);
+    cfg.service(history_snapshot);
+    cfg.service(history_at);
+    cfg.service(events_stream);
+    cfg.service(auditor);
+}